@@ -12,10 +12,22 @@ pub enum APIError {
     General(String),
     #[error("HTTP: {0}")]
     Http(String),
-    #[error("IO: {0}")]
-    Cookie(String),
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+    #[error("Timeout ({0:?}): {1}")]
+    Timeout(TimeoutPhase, String),
+    #[error("TLS: {0}")]
+    Tls(String),
+    #[error("Auth: {0}")]
+    Auth(String),
+    #[error("Unresolved variables: {}", .0.join(", "))]
+    UnresolvedVariables(Vec<String>),
     #[error("Cookie: {0}")]
+    Cookie(String),
+    #[error("IO: {0}")]
     Io(String),
+    #[error("Database: {0}")]
+    Database(String),
     #[error("Parser: {0}")]
     Parser(String),
     #[error("Zip: {0}")]
@@ -26,6 +38,15 @@ pub enum APIError {
     Kube(KubeError),
 }
 
+/// The phase of an HTTP exchange whose time limit expired.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub enum TimeoutPhase {
+    Connect,
+    Response,
+    Read,
+    Total,
+}
+
 pub type ApiResult<T> = anyhow::Result<T, APIError>;
 impl From<VarError> for APIError{
     fn from(value: VarError) -> Self {
@@ -37,6 +58,11 @@ impl From<reqwest::Error> for APIError {
         APIError::Http(error.to_string())
     }
 }
+impl From<rustls::Error> for APIError {
+    fn from(error: rustls::Error) -> Self {
+        APIError::Tls(error.to_string())
+    }
+}
 impl From<InvalidUri> for APIError {
     fn from(error: InvalidUri) -> Self {
         APIError::Http(error.to_string())
@@ -60,6 +86,32 @@ impl From<reqwest::header::ToStrError> for APIError {
     }
 }
 
+impl From<tokio_tungstenite::tungstenite::Error> for APIError {
+    fn from(error: tokio_tungstenite::tungstenite::Error) -> Self {
+        APIError::Http(error.to_string())
+    }
+}
+
+impl From<hyper::Error> for APIError {
+    fn from(error: hyper::Error) -> Self {
+        APIError::Http(error.to_string())
+    }
+}
+impl From<prost::DecodeError> for APIError {
+    fn from(error: prost::DecodeError) -> Self {
+        APIError::Parser(error.to_string())
+    }
+}
+impl From<prost_reflect::DescriptorError> for APIError {
+    fn from(error: prost_reflect::DescriptorError) -> Self {
+        APIError::Parser(error.to_string())
+    }
+}
+impl From<protox::Error> for APIError {
+    fn from(error: protox::Error) -> Self {
+        APIError::Parser(error.to_string())
+    }
+}
 impl From<std::io::Error> for APIError {
     fn from(error: std::io::Error) -> Self {
         APIError::Io(error.to_string())
@@ -89,6 +141,24 @@ impl From<serde_json::Error> for APIError {
     }
 }
 
+impl From<rusqlite::Error> for APIError {
+    fn from(error: rusqlite::Error) -> Self {
+        APIError::Database(error.to_string())
+    }
+}
+
+impl From<csv::Error> for APIError {
+    fn from(error: csv::Error) -> Self {
+        APIError::Parser(error.to_string())
+    }
+}
+
+impl From<serde_yaml::Error> for APIError {
+    fn from(error: serde_yaml::Error) -> Self {
+        APIError::Parser(error.to_string())
+    }
+}
+
 impl From<base64::DecodeError> for APIError {
     fn from(error: base64::DecodeError) -> Self {
         APIError::Parser(error.to_string())
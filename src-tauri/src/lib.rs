@@ -97,10 +97,12 @@ pub fn run() {
         .plugin(tauri_plugin_system_info::init())
         // .plugin(tauri_plugin_window_state::Builder::new().build())
         .manage(SharedAppState::new(Mutex::new(states::AppState::default())))
+        .manage(http::HTTPClients::default())
         .setup(|app| {
             // Initialize crash reporting
             let crash_log_path = app.path().app_log_dir()?.join("msms-dev-tools-crash.log");
             utils::crash_reporter::init_crash_reporting(crash_log_path);
+            store::init(app.path().app_data_dir()?);
 
             setup_menu(app)?;
 
@@ -134,10 +136,17 @@ pub fn run() {
             e
         })
         .expect("error while running tauri application");
+}
 
-    // tracing_subscriber::registry()
-    //     .with(http::HTTPTraceLayer)
-    //     .init();
+/// Runs a collection from the command line instead of starting the app, see
+/// [`services::http_runner::cli`]. Returns the process exit code.
+pub fn run_cli(args: &[String]) -> i32 {
+    init_logging();
+    let _ = env_logger::builder()
+        .filter_level(log::LevelFilter::Warn)
+        .parse_default_env()
+        .try_init();
+    services::http_runner::cli::main(args)
 }
 
 fn setup_menu(app: &mut App) -> Result<(), Box<dyn Error>> {
@@ -237,9 +246,7 @@ fn handle_window_event() -> fn(&Window, &WindowEvent) {
 fn init_logging() {
     let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
 
-    let subscriber = Registry::default()
-        .with(env_filter)
-        .with(http::HTTPTraceLayer); // Make sure this is the correct type that implements Layer
+    let subscriber = Registry::default().with(env_filter);
 
     if let Err(e) = tracing::subscriber::set_global_default(subscriber) {
         eprintln!("Failed to set tracing subscriber: {}", e);
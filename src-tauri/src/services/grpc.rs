@@ -0,0 +1,1259 @@
+use crate::errors::{APIError, ApiResult, TimeoutPhase};
+use crate::services::http::{self, HTTPRequestKVParam};
+use crate::services::http_env::{HTTPEnvironment, Variables};
+use crate::services::http_tls::{HTTPCertificate, HTTPTlsOptions};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use hyper::client::conn::{http1, http2};
+use hyper::{Method, Request, Response};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use once_cell::sync::Lazy;
+use percent_encoding::percent_decode_str;
+use prost::Message;
+use prost_reflect::bytes::{Buf, BufMut, Bytes, BytesMut};
+use prost_reflect::prost_types::FileDescriptorProto;
+use prost_reflect::{
+    DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor, SerializeOptions,
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use rustls::pki_types::ServerName;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio::task::AbortHandle;
+use tokio_rustls::TlsConnector;
+use url::Url;
+
+/// Names of the gRPC status codes, indexed by code.
+const STATUS_NAMES: [&str; 17] = [
+    "OK",
+    "CANCELLED",
+    "UNKNOWN",
+    "INVALID_ARGUMENT",
+    "DEADLINE_EXCEEDED",
+    "NOT_FOUND",
+    "ALREADY_EXISTS",
+    "PERMISSION_DENIED",
+    "RESOURCE_EXHAUSTED",
+    "FAILED_PRECONDITION",
+    "ABORTED",
+    "OUT_OF_RANGE",
+    "UNIMPLEMENTED",
+    "INTERNAL",
+    "UNAVAILABLE",
+    "DATA_LOSS",
+    "UNAUTHENTICATED",
+];
+const UNKNOWN: u32 = 2;
+const UNIMPLEMENTED: u32 = 12;
+
+/// Limits [`Channel::connect`], also when the call has no deadline of its own.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reflection services tried in order, older servers only know `v1alpha`.
+const REFLECTION_SERVICES: [&str; 2] = [
+    "grpc.reflection.v1.ServerReflection",
+    "grpc.reflection.v1alpha.ServerReflection",
+];
+
+/// Descriptors loaded by [`descriptors`], keyed by [`GRPCSource::key`].
+static DESCRIPTORS: Lazy<Mutex<HashMap<String, DescriptorPool>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Abort handles of calls started through [`call`], keyed by request id.
+static IN_FLIGHT_CALLS: Lazy<tokio::sync::Mutex<HashMap<String, AbortHandle>>> =
+    Lazy::new(|| tokio::sync::Mutex::new(HashMap::new()));
+
+/// Where the descriptors of the called services come from.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum GRPCSource {
+    /// `.proto` files, compiled along with their imports. Imports are looked up in
+    /// `includes` and the directories of `files`.
+    Protos {
+        files: Vec<String>,
+        #[serde(default)]
+        includes: Vec<String>,
+    },
+    /// The server reflection service of the called server.
+    #[default]
+    Reflection,
+}
+
+impl GRPCSource {
+    /// Reflected descriptors are kept per server, compiled ones per set of files.
+    fn key(&self, url: &Url) -> String {
+        match self {
+            GRPCSource::Protos { files, includes } => {
+                format!("protos:{}|{}", files.join(","), includes.join(","))
+            }
+            GRPCSource::Reflection => format!("reflection:{}", url.origin().ascii_serialization()),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct GRPCRequest {
+    /// Id of the call, generated when left out.
+    #[serde(default)]
+    pub id: Option<String>,
+    /// An `http://` url for plaintext and an `https://` one for TLS.
+    pub url: String,
+    /// `package.Service/Method`.
+    #[serde(default)]
+    pub method: String,
+    /// The request message as protobuf JSON, an empty message when blank.
+    #[serde(default)]
+    pub message: String,
+    /// Sent as request headers, `-bin` keys take base64 values.
+    #[serde(default)]
+    pub metadata: Vec<HTTPRequestKVParam>,
+    #[serde(default)]
+    pub source: GRPCSource,
+    #[serde(default)]
+    pub tls: HTTPTlsOptions,
+    /// Speaks gRPC-Web over HTTP/1.1 instead of gRPC over HTTP/2.
+    #[serde(default)]
+    pub web: bool,
+    /// Deadline of the call in ms, also sent as `grpc-timeout`.
+    #[serde(default)]
+    pub timeout: Option<u64>,
+    /// Name of the [`HTTPEnvironment`] `{{name}}` placeholders are resolved from.
+    #[serde(default)]
+    pub environment: Option<String>,
+}
+
+impl GRPCRequest {
+    /// Replaces the `{{name}}` placeholders of the url, method, message and metadata.
+    fn resolve(&mut self) -> ApiResult<()> {
+        let variables = match self.environment.take() {
+            Some(name) => Variables::from(&HTTPEnvironment::load(&name)?),
+            None => Variables::default(),
+        };
+        let mut missing = BTreeSet::new();
+        let mut values: Vec<&mut String> = vec![&mut self.url, &mut self.method, &mut self.message];
+        for param in self.metadata.iter_mut() {
+            values.extend([&mut param.key, &mut param.value]);
+        }
+        for value in values {
+            *value = variables.render(value, &mut missing);
+        }
+        if !missing.is_empty() {
+            return Err(APIError::UnresolvedVariables(missing.into_iter().collect()));
+        }
+        Ok(())
+    }
+
+    fn metadata(&self) -> ApiResult<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for m in self.metadata.iter().filter(|m| m.enabled) {
+            headers.append(
+                m.key.parse::<HeaderName>()?,
+                HeaderValue::from_str(&m.value)?,
+            );
+        }
+        Ok(headers)
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GRPCService {
+    /// Fully qualified name, e.g. `package.Service`.
+    pub name: String,
+    pub methods: Vec<GRPCMethod>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GRPCMethod {
+    pub name: String,
+    /// `package.Service/Method`, as expected in [`GRPCRequest::method`].
+    pub path: String,
+    pub input_type: String,
+    pub output_type: String,
+    pub client_streaming: bool,
+    pub server_streaming: bool,
+    /// The input message with every field set to its default, to start a request from.
+    pub input_template: Value,
+}
+
+impl GRPCMethod {
+    fn describe(method: &MethodDescriptor) -> ApiResult<GRPCMethod> {
+        Ok(GRPCMethod {
+            name: method.name().to_string(),
+            path: format!("{}/{}", method.parent_service().full_name(), method.name()),
+            input_type: method.input().full_name().to_string(),
+            output_type: method.output().full_name().to_string(),
+            client_streaming: method.is_client_streaming(),
+            server_streaming: method.is_server_streaming(),
+            input_template: to_json(&DynamicMessage::new(method.input()))?,
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GRPCResponse {
+    pub id: String,
+    /// gRPC status code, 0 when the call succeeded.
+    pub status: u32,
+    /// Name of the status code, e.g. `NOT_FOUND`.
+    pub status_name: String,
+    /// The percent-decoded `grpc-message`.
+    pub message: String,
+    /// Status of the HTTP response carrying the call.
+    pub http_status: u16,
+    pub headers: HashMap<String, Vec<String>>,
+    /// Trailers of the response, empty for trailers-only responses.
+    pub trailers: HashMap<String, Vec<String>>,
+    /// Response messages as JSON, at most one for unary methods.
+    pub messages: Vec<Value>,
+    /// Certificates presented by the server, leaf first.
+    pub certificates: Vec<HTTPCertificate>,
+    /// Time in ms until the status was received.
+    pub latency: u64,
+}
+
+/// A message of a server-streaming response, passed on as it arrives.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct GRPCMessage {
+    pub seq: u64,
+    /// Unix timestamp in ms.
+    pub time: i64,
+    pub message: Value,
+}
+
+pub type GRPCListener = Box<dyn Fn(GRPCMessage) + Send + Sync>;
+
+/// The gRPC status of a finished call.
+#[derive(Debug, Clone, PartialEq)]
+struct Status {
+    code: u32,
+    message: String,
+}
+
+impl Status {
+    fn name(&self) -> String {
+        STATUS_NAMES
+            .get(self.code as usize)
+            .map_or_else(|| self.code.to_string(), |name| name.to_string())
+    }
+
+    fn into_error(self, path: &str) -> APIError {
+        APIError::Http(format!(
+            "{} failed with {}: {}",
+            path,
+            self.name(),
+            self.message
+        ))
+    }
+}
+
+/// Status code of a response without `grpc-status`, as mapped by the gRPC HTTP/2 spec.
+fn http_status_code(status: u16) -> u32 {
+    match status {
+        400 => 13,
+        401 => 16,
+        403 => 7,
+        404 => UNIMPLEMENTED,
+        429 | 502 | 503 | 504 => 14,
+        _ => UNKNOWN,
+    }
+}
+
+/// Prefixes `message` with the uncompressed flag and its length.
+fn frame(message: &[u8]) -> Bytes {
+    let mut frame = BytesMut::with_capacity(message.len() + 5);
+    frame.put_u8(0);
+    frame.put_u32(message.len() as u32);
+    frame.put_slice(message);
+    frame.freeze()
+}
+
+/// Splits a response body into its length-prefixed frames.
+#[derive(Default)]
+struct FrameDecoder {
+    buffer: BytesMut,
+}
+
+impl FrameDecoder {
+    fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// The flags and payload of the next complete frame.
+    fn next(&mut self) -> Option<(u8, Bytes)> {
+        if self.buffer.len() < 5 {
+            return None;
+        }
+        let length = u32::from_be_bytes([
+            self.buffer[1],
+            self.buffer[2],
+            self.buffer[3],
+            self.buffer[4],
+        ]) as usize;
+        if self.buffer.len() < length + 5 {
+            return None;
+        }
+        let flags = self.buffer.get_u8();
+        self.buffer.advance(4);
+        Some((flags, self.buffer.split_to(length).freeze()))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+/// The `name: value` lines gRPC-Web sends its trailers as.
+fn web_trailers(block: &[u8]) -> HashMap<String, Vec<String>> {
+    let mut trailers: HashMap<String, Vec<String>> = HashMap::new();
+    for line in String::from_utf8_lossy(block).split("\r\n") {
+        if let Some((name, value)) = line.split_once(':') {
+            trailers
+                .entry(name.trim().to_lowercase())
+                .or_default()
+                .push(value.trim().to_string());
+        }
+    }
+    trailers
+}
+
+trait Io: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Io for T {}
+
+enum Sender {
+    Http1(http1::SendRequest<Full<Bytes>>),
+    Http2(http2::SendRequest<Full<Bytes>>),
+}
+
+/// A connection calls are sent on, over HTTP/2 or over HTTP/1.1 for gRPC-Web.
+struct Channel {
+    sender: Sender,
+    scheme: String,
+    authority: String,
+    certificates: Vec<HTTPCertificate>,
+}
+
+impl Channel {
+    async fn connect(url: &Url, tls: &HTTPTlsOptions, web: bool) -> ApiResult<Channel> {
+        tokio::time::timeout(CONNECT_TIMEOUT, Self::open(url, tls, web))
+            .await
+            .map_err(|_| {
+                APIError::Timeout(
+                    TimeoutPhase::Connect,
+                    format!(
+                        "Could not connect to {} within {} s",
+                        url,
+                        CONNECT_TIMEOUT.as_secs()
+                    ),
+                )
+            })?
+    }
+
+    async fn open(url: &Url, tls: &HTTPTlsOptions, web: bool) -> ApiResult<Channel> {
+        let host = url
+            .host_str()
+            .ok_or_else(|| APIError::Http(format!("{} has no host", url)))?;
+        let port = url
+            .port_or_known_default()
+            .ok_or_else(|| APIError::Http(format!("{} has no port", url)))?;
+        let tcp = TcpStream::connect(format!("{}:{}", host, port)).await?;
+        tcp.set_nodelay(true)?;
+
+        let chain = Arc::new(Mutex::new(Vec::new()));
+        let io: Box<dyn Io> = match url.scheme() {
+            "http" => Box::new(tcp),
+            "https" => {
+                let peer_chain = chain.clone();
+                let mut config = tls.client_config(Arc::new(move |certificates| {
+                    if let Ok(mut chain) = peer_chain.lock() {
+                        *chain = certificates;
+                    }
+                }))?;
+                config.alpn_protocols = if web {
+                    vec![b"http/1.1".to_vec()]
+                } else {
+                    vec![b"h2".to_vec()]
+                };
+                let name = host.trim_start_matches('[').trim_end_matches(']');
+                let name = ServerName::try_from(name.to_string())
+                    .map_err(|e| APIError::Tls(e.to_string()))?;
+                Box::new(
+                    TlsConnector::from(Arc::new(config))
+                        .connect(name, tcp)
+                        .await?,
+                )
+            }
+            scheme => {
+                return Err(APIError::Http(format!(
+                    "Unsupported gRPC scheme {}, use http or https",
+                    scheme
+                )))
+            }
+        };
+
+        let io = TokioIo::new(io);
+        let sender = if web {
+            let (sender, connection) = http1::handshake(io).await?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    log::debug!("gRPC-Web connection closed: {}", e);
+                }
+            });
+            Sender::Http1(sender)
+        } else {
+            let (sender, connection) = http2::handshake(TokioExecutor::new(), io).await?;
+            tokio::spawn(async move {
+                if let Err(e) = connection.await {
+                    log::debug!("gRPC connection closed: {}", e);
+                }
+            });
+            Sender::Http2(sender)
+        };
+        Ok(Channel {
+            sender,
+            scheme: url.scheme().to_string(),
+            authority: match url.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host.to_string(),
+            },
+            certificates: chain.lock().map(|c| c.clone()).unwrap_or_default(),
+        })
+    }
+
+    /// Sends the already framed `body` to `path`, e.g. `/package.Service/Method`.
+    async fn call(
+        &mut self,
+        path: &str,
+        metadata: &HeaderMap,
+        timeout: Option<u64>,
+        body: Bytes,
+    ) -> ApiResult<Reply> {
+        let mut request = Request::new(Full::new(body));
+        *request.method_mut() = Method::POST;
+        let headers = request.headers_mut();
+        headers.extend(metadata.clone());
+        if let Some(timeout) = timeout {
+            headers.insert(
+                "grpc-timeout",
+                HeaderValue::from_str(&format!("{}m", timeout))?,
+            );
+        }
+        let response = match &mut self.sender {
+            Sender::Http1(sender) => {
+                *request.uri_mut() = path.parse()?;
+                let headers = request.headers_mut();
+                headers.insert("host", HeaderValue::from_str(&self.authority)?);
+                headers.insert(
+                    "content-type",
+                    HeaderValue::from_static("application/grpc-web+proto"),
+                );
+                headers.insert("x-grpc-web", HeaderValue::from_static("1"));
+                sender.ready().await?;
+                sender.send_request(request).await?
+            }
+            Sender::Http2(sender) => {
+                *request.uri_mut() =
+                    format!("{}://{}{}", self.scheme, self.authority, path).parse()?;
+                let headers = request.headers_mut();
+                headers.insert("content-type", HeaderValue::from_static("application/grpc"));
+                headers.insert("te", HeaderValue::from_static("trailers"));
+                sender.ready().await?;
+                sender.send_request(request).await?
+            }
+        };
+        Reply::new(response)
+    }
+}
+
+/// The response to a call, read message by message.
+struct Reply {
+    http_status: u16,
+    headers: HashMap<String, Vec<String>>,
+    trailers: HashMap<String, Vec<String>>,
+    body: Incoming,
+    frames: FrameDecoder,
+}
+
+impl Reply {
+    fn new(response: Response<Incoming>) -> ApiResult<Reply> {
+        Ok(Reply {
+            http_status: response.status().as_u16(),
+            headers: http::response_headers(response.headers())?,
+            trailers: HashMap::new(),
+            body: response.into_body(),
+            frames: FrameDecoder::default(),
+        })
+    }
+
+    /// The next message of the response, None once the body ended.
+    async fn message(&mut self) -> ApiResult<Option<Bytes>> {
+        loop {
+            if let Some((flags, payload)) = self.frames.next() {
+                // gRPC-Web sends its trailers as the last frame
+                if flags & 0x80 != 0 {
+                    self.trailers = web_trailers(&payload);
+                    continue;
+                }
+                if flags & 0x01 != 0 {
+                    return Err(APIError::Http(
+                        "Compressed gRPC messages are not supported".to_string(),
+                    ));
+                }
+                return Ok(Some(payload));
+            }
+            match self.body.frame().await {
+                Some(frame) => match frame?.into_data() {
+                    Ok(data) => self.frames.push(&data),
+                    Err(frame) => {
+                        if let Ok(trailers) = frame.into_trailers() {
+                            self.trailers = http::response_headers(&trailers)?;
+                        }
+                    }
+                },
+                None if self.frames.is_empty() => return Ok(None),
+                None => {
+                    return Err(APIError::Http(
+                        "gRPC response ended in the middle of a message".to_string(),
+                    ))
+                }
+            }
+        }
+    }
+
+    fn status(&self) -> Status {
+        status(self.http_status, &self.headers, &self.trailers)
+    }
+}
+
+/// The status from the trailers, or from the headers of a trailers-only response.
+fn status(
+    http_status: u16,
+    headers: &HashMap<String, Vec<String>>,
+    trailers: &HashMap<String, Vec<String>>,
+) -> Status {
+    let value = |name: &str| {
+        trailers
+            .get(name)
+            .or_else(|| headers.get(name))
+            .and_then(|values| values.first())
+    };
+    let message = value("grpc-message")
+        .map(|m| percent_decode_str(m).decode_utf8_lossy().into_owned())
+        .unwrap_or_default();
+    match value("grpc-status") {
+        Some(code) => Status {
+            code: code.trim().parse().unwrap_or(UNKNOWN),
+            message,
+        },
+        None if http_status != 200 => Status {
+            code: http_status_code(http_status),
+            message: format!("HTTP status {}", http_status),
+        },
+        None => Status {
+            code: UNKNOWN,
+            message: "Response has no grpc-status".to_string(),
+        },
+    }
+}
+
+fn to_json(message: &DynamicMessage) -> ApiResult<Value> {
+    let options = SerializeOptions::new().skip_default_fields(false);
+    Ok(message.serialize_with_options(serde_json::value::Serializer, &options)?)
+}
+
+fn from_json(descriptor: MessageDescriptor, json: &str) -> ApiResult<DynamicMessage> {
+    let json = if json.trim().is_empty() { "{}" } else { json };
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let message = DynamicMessage::deserialize(descriptor, &mut deserializer)?;
+    deserializer.end()?;
+    Ok(message)
+}
+
+/// Compiles `files` with their imports, the well-known types are built in.
+fn compile(files: &[String], includes: &[String]) -> ApiResult<DescriptorPool> {
+    let mut paths: Vec<PathBuf> = includes.iter().map(PathBuf::from).collect();
+    for file in files {
+        let parent = match Path::new(file).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        if !paths.contains(&parent) {
+            paths.push(parent);
+        }
+    }
+    let mut compiler = protox::Compiler::new(paths)?;
+    compiler.include_imports(true);
+    compiler.open_files(files)?;
+    Ok(compiler.descriptor_pool())
+}
+
+/// Messages of the `grpc.reflection.v1` protocol, `v1alpha` uses the same ones.
+mod reflection {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ServerReflectionRequest {
+        #[prost(string, tag = "1")]
+        pub host: String,
+        #[prost(oneof = "MessageRequest", tags = "3, 4, 7")]
+        pub message_request: Option<MessageRequest>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum MessageRequest {
+        #[prost(string, tag = "3")]
+        FileByFilename(String),
+        #[prost(string, tag = "4")]
+        FileContainingSymbol(String),
+        #[prost(string, tag = "7")]
+        ListServices(String),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ServerReflectionResponse {
+        #[prost(oneof = "MessageResponse", tags = "4, 6, 7")]
+        pub message_response: Option<MessageResponse>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum MessageResponse {
+        #[prost(message, tag = "4")]
+        FileDescriptorResponse(FileDescriptorResponse),
+        #[prost(message, tag = "6")]
+        ListServicesResponse(ListServiceResponse),
+        #[prost(message, tag = "7")]
+        ErrorResponse(ErrorResponse),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct FileDescriptorResponse {
+        /// Serialized `FileDescriptorProto`s.
+        #[prost(bytes = "vec", repeated, tag = "1")]
+        pub file_descriptor_proto: Vec<Vec<u8>>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ListServiceResponse {
+        #[prost(message, repeated, tag = "1")]
+        pub service: Vec<ServiceResponse>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ServiceResponse {
+        #[prost(string, tag = "1")]
+        pub name: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ErrorResponse {
+        #[prost(int32, tag = "1")]
+        pub error_code: i32,
+        #[prost(string, tag = "2")]
+        pub error_message: String,
+    }
+}
+
+use reflection::{MessageRequest, MessageResponse};
+
+/// Asks the reflection service of a server, one request per call.
+struct Reflection {
+    channel: Channel,
+    metadata: HeaderMap,
+    path: String,
+}
+
+impl Reflection {
+    async fn ask(
+        &mut self,
+        path: &str,
+        request: MessageRequest,
+    ) -> ApiResult<(Status, Option<MessageResponse>)> {
+        let request = reflection::ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(request),
+        };
+        let body = frame(&request.encode_to_vec());
+        let mut reply = self.channel.call(path, &self.metadata, None, body).await?;
+        let mut response = None;
+        while let Some(message) = reply.message().await? {
+            response = reflection::ServerReflectionResponse::decode(message)?.message_response;
+        }
+        Ok((reply.status(), response))
+    }
+
+    /// Names of the services the server offers, settling on the reflection service it knows.
+    async fn services(&mut self) -> ApiResult<Vec<String>> {
+        for service in REFLECTION_SERVICES {
+            let path = format!("/{}/ServerReflectionInfo", service);
+            let (status, response) = self
+                .ask(&path, MessageRequest::ListServices(String::new()))
+                .await?;
+            if status.code == UNIMPLEMENTED {
+                continue;
+            }
+            if status.code != 0 {
+                return Err(status.into_error(&path));
+            }
+            self.path = path;
+            return match response {
+                Some(MessageResponse::ListServicesResponse(list)) => {
+                    Ok(list.service.into_iter().map(|s| s.name).collect())
+                }
+                response => Err(Self::unexpected(response)),
+            };
+        }
+        Err(APIError::Http(
+            "The server does not offer gRPC reflection".to_string(),
+        ))
+    }
+
+    async fn files(&mut self, request: MessageRequest) -> ApiResult<Vec<FileDescriptorProto>> {
+        let path = self.path.clone();
+        let (status, response) = self.ask(&path, request).await?;
+        if status.code != 0 {
+            return Err(status.into_error(&path));
+        }
+        match response {
+            Some(MessageResponse::FileDescriptorResponse(files)) => files
+                .file_descriptor_proto
+                .iter()
+                .map(|file| Ok(FileDescriptorProto::decode(file.as_slice())?))
+                .collect(),
+            response => Err(Self::unexpected(response)),
+        }
+    }
+
+    fn unexpected(response: Option<MessageResponse>) -> APIError {
+        match response {
+            Some(MessageResponse::ErrorResponse(e)) => APIError::Http(format!(
+                "Reflection failed with {}: {}",
+                e.error_code, e.error_message
+            )),
+            _ => APIError::Http("Unexpected gRPC reflection response".to_string()),
+        }
+    }
+}
+
+/// Fetches the files defining every service of the server along with their imports.
+async fn reflect(request: &GRPCRequest, url: &Url) -> ApiResult<DescriptorPool> {
+    let mut reflection = Reflection {
+        channel: Channel::connect(url, &request.tls, request.web).await?,
+        metadata: request.metadata()?,
+        path: String::new(),
+    };
+    let mut pending: Vec<_> = reflection
+        .services()
+        .await?
+        .into_iter()
+        .map(MessageRequest::FileContainingSymbol)
+        .collect();
+    let mut files: HashMap<String, FileDescriptorProto> = HashMap::new();
+    let mut requested = HashSet::new();
+    while let Some(ask) = pending.pop() {
+        if let MessageRequest::FileByFilename(name) = &ask {
+            if files.contains_key(name) {
+                continue;
+            }
+        }
+        for file in reflection.files(ask).await? {
+            for dependency in &file.dependency {
+                if !files.contains_key(dependency) && requested.insert(dependency.clone()) {
+                    pending.push(MessageRequest::FileByFilename(dependency.clone()));
+                }
+            }
+            files.insert(file.name().to_string(), file);
+        }
+    }
+    let mut pool = DescriptorPool::new();
+    pool.add_file_descriptor_protos(files.into_values())?;
+    Ok(pool)
+}
+
+/// The descriptors of `request.source`, loaded once unless `refresh` is set.
+async fn descriptors(request: &GRPCRequest, refresh: bool) -> ApiResult<DescriptorPool> {
+    let url = Url::parse(&request.url)?;
+    let key = request.source.key(&url);
+    if !refresh {
+        let cached = DESCRIPTORS
+            .lock()
+            .map_err(|e| APIError::General(e.to_string()))?
+            .get(&key)
+            .cloned();
+        if let Some(pool) = cached {
+            return Ok(pool);
+        }
+    }
+    let pool = match &request.source {
+        GRPCSource::Protos { files, includes } => compile(files, includes)?,
+        GRPCSource::Reflection => reflect(request, &url).await?,
+    };
+    DESCRIPTORS
+        .lock()
+        .map_err(|e| APIError::General(e.to_string()))?
+        .insert(key, pool.clone());
+    Ok(pool)
+}
+
+/// Lists the services of `request.source`, the reflection services themselves left out.
+pub async fn services(mut request: GRPCRequest, refresh: bool) -> ApiResult<Vec<GRPCService>> {
+    request.resolve()?;
+    let pool = descriptors(&request, refresh).await?;
+    let mut services = vec![];
+    for service in pool.services() {
+        if REFLECTION_SERVICES.contains(&service.full_name()) {
+            continue;
+        }
+        services.push(GRPCService {
+            name: service.full_name().to_string(),
+            methods: service
+                .methods()
+                .map(|method| GRPCMethod::describe(&method))
+                .collect::<ApiResult<_>>()?,
+        });
+    }
+    services.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(services)
+}
+
+fn find_method(pool: &DescriptorPool, path: &str) -> ApiResult<MethodDescriptor> {
+    let (service, method) = path
+        .trim_start_matches('/')
+        .split_once('/')
+        .ok_or_else(|| APIError::General(format!("{} is not a package.Service/Method", path)))?;
+    pool.get_service_by_name(service)
+        .and_then(|service| service.methods().find(|m| m.name() == method))
+        .ok_or_else(|| APIError::General(format!("Method {} not found", path)))
+}
+
+/// Runs [`invoke`] on its own task so it can be aborted with [`cancel`].
+pub async fn call(
+    mut request: GRPCRequest,
+    on_message: Option<GRPCListener>,
+) -> ApiResult<GRPCResponse> {
+    let id = request
+        .id
+        .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+        .clone();
+    let mut in_flight = IN_FLIGHT_CALLS.lock().await;
+    if in_flight.contains_key(&id) {
+        return Err(APIError::Http(format!("Call {} is already in flight", id)));
+    }
+    let task = tokio::spawn(invoke(request, on_message));
+    let task_id = task.id();
+    in_flight.insert(id.clone(), task.abort_handle());
+    drop(in_flight);
+
+    let result = task.await;
+    // a call cancelled meanwhile may have handed its id to a new one
+    let mut in_flight = IN_FLIGHT_CALLS.lock().await;
+    if in_flight
+        .get(&id)
+        .is_some_and(|handle| handle.id() == task_id)
+    {
+        in_flight.remove(&id);
+    }
+    drop(in_flight);
+    match result {
+        Ok(response) => response,
+        Err(e) if e.is_cancelled() => Err(APIError::Cancelled(id)),
+        Err(e) => Err(APIError::General(e.to_string())),
+    }
+}
+
+/// Aborts an in-flight call, returns false when no call with that id is running.
+pub async fn cancel(id: &str) -> bool {
+    match IN_FLIGHT_CALLS.lock().await.remove(id) {
+        Some(handle) => {
+            handle.abort();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Calls a unary or server-streaming method, passing each response message to
+/// `on_message` as it arrives. A failed call is a response with a non-zero status.
+pub async fn invoke(
+    mut request: GRPCRequest,
+    on_message: Option<GRPCListener>,
+) -> ApiResult<GRPCResponse> {
+    request.resolve()?;
+    let pool = descriptors(&request, false).await?;
+    let method = find_method(&pool, &request.method)?;
+    if method.is_client_streaming() {
+        return Err(APIError::General(format!(
+            "{} is client streaming, only unary and server-streaming methods can be called",
+            request.method
+        )));
+    }
+    let body = frame(&from_json(method.input(), &request.message)?.encode_to_vec());
+    let path = format!("/{}/{}", method.parent_service().full_name(), method.name());
+    let metadata = request.metadata()?;
+    let output = method.output();
+    let started = Instant::now();
+
+    let calling = async {
+        let url = Url::parse(&request.url)?;
+        let mut channel = Channel::connect(&url, &request.tls, request.web).await?;
+        let mut reply = channel
+            .call(&path, &metadata, request.timeout, body)
+            .await?;
+        let mut messages = vec![];
+        while let Some(payload) = reply.message().await? {
+            let message = to_json(&DynamicMessage::decode(output.clone(), payload)?)?;
+            if let Some(listener) = &on_message {
+                listener(GRPCMessage {
+                    seq: messages.len() as u64,
+                    time: chrono::Utc::now().timestamp_millis(),
+                    message: message.clone(),
+                });
+            }
+            messages.push(message);
+        }
+        Ok::<_, APIError>((channel.certificates, reply, messages))
+    };
+    let (certificates, reply, messages) = match request.timeout {
+        Some(timeout) => tokio::time::timeout(Duration::from_millis(timeout), calling)
+            .await
+            .map_err(|_| {
+                APIError::Timeout(
+                    TimeoutPhase::Total,
+                    format!("{} did not finish within {} ms", path, timeout),
+                )
+            })??,
+        None => calling.await?,
+    };
+
+    let status = reply.status();
+    Ok(GRPCResponse {
+        id: request.id.unwrap_or_default(),
+        status: status.code,
+        status_name: status.name(),
+        message: status.message,
+        http_status: reply.http_status,
+        headers: reply.headers,
+        trailers: reply.trailers,
+        messages,
+        certificates,
+        latency: started.elapsed().as_millis() as u64,
+    })
+}
+
+pub mod commands {
+    use super::*;
+    use tauri::{command, AppHandle, Emitter};
+
+    #[command(async)]
+    pub async fn grpc_services(
+        req: GRPCRequest,
+        refresh: Option<bool>,
+    ) -> ApiResult<Vec<GRPCService>> {
+        log::info!("Loading gRPC services for {}", req.url);
+        services(req, refresh.unwrap_or(false)).await
+    }
+
+    #[command(async)]
+    pub async fn grpc_call(app: AppHandle, mut req: GRPCRequest) -> ApiResult<GRPCResponse> {
+        log::info!("Calling {} on {}", req.method, req.url);
+        let id = req
+            .id
+            .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+            .clone();
+        let on_message: GRPCListener = Box::new(move |message| {
+            if let Err(e) = app.emit(format!("grpc_message_{}", id).as_str(), &message) {
+                log::error!("Failed to emit gRPC message: {}", e);
+            }
+        });
+        call(req, Some(on_message)).await
+    }
+
+    #[command(async)]
+    pub async fn grpc_cancel(id: String) -> ApiResult<bool> {
+        log::info!("Cancelling gRPC call {}", id);
+        Ok(cancel(&id).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::reflection::{
+        FileDescriptorResponse, ListServiceResponse, ServerReflectionRequest,
+        ServerReflectionResponse, ServiceResponse,
+    };
+    use super::*;
+    use http_body_util::combinators::BoxBody;
+    use hyper::service::service_fn;
+    use serde_json::json;
+    use std::convert::Infallible;
+    use std::fs;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const GREETER: &str = r#"syntax = "proto3";
+package greet;
+
+service Greeter {
+  rpc Hello (HelloRequest) returns (HelloReply);
+  rpc Count (HelloRequest) returns (stream HelloReply);
+  rpc Chat (stream HelloRequest) returns (stream HelloReply);
+}
+
+message HelloRequest {
+  string name = 1;
+  int32 times = 2;
+}
+
+message HelloReply {
+  string message = 1;
+}
+"#;
+
+    fn trailer_frame(block: &str) -> Vec<u8> {
+        let mut frame = vec![0x80];
+        frame.extend((block.len() as u32).to_be_bytes());
+        frame.extend(block.as_bytes());
+        frame
+    }
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, Vec<String>> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), vec![value.to_string()]))
+            .collect()
+    }
+
+    /// Answers like a gRPC server over HTTP/2 that only knows the `v1alpha` reflection
+    /// service and replies `{"message": "hi"}` to `greet.Greeter/Hello`.
+    async fn serve_h2(pool: DescriptorPool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let pool = pool.clone();
+                let service = service_fn(move |request| answer(pool.clone(), request));
+                tokio::spawn(
+                    hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                        .serve_connection(TokioIo::new(socket), service),
+                );
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    async fn answer(
+        pool: DescriptorPool,
+        request: Request<Incoming>,
+    ) -> Result<Response<BoxBody<Bytes, Infallible>>, Infallible> {
+        let path = request.uri().path().to_string();
+        let body = match request.into_body().collect().await {
+            Ok(body) => body.to_bytes(),
+            Err(_) => Bytes::new(),
+        };
+        let payload = body.slice(body.len().min(5)..);
+        let reply = match path.as_str() {
+            "/greet.Greeter/Hello" => Some(b"\x0a\x02hi".to_vec()),
+            "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo" => {
+                let asked = ServerReflectionRequest::decode(payload).unwrap();
+                let response = match asked.message_request {
+                    Some(MessageRequest::ListServices(_)) => {
+                        MessageResponse::ListServicesResponse(ListServiceResponse {
+                            service: vec![ServiceResponse {
+                                name: "greet.Greeter".to_string(),
+                            }],
+                        })
+                    }
+                    _ => MessageResponse::FileDescriptorResponse(FileDescriptorResponse {
+                        file_descriptor_proto: pool
+                            .files()
+                            .filter(|file| file.package_name() == "greet")
+                            .map(|file| file.file_descriptor_proto().encode_to_vec())
+                            .collect(),
+                    }),
+                };
+                let response = ServerReflectionResponse {
+                    message_response: Some(response),
+                };
+                Some(response.encode_to_vec())
+            }
+            _ => None,
+        };
+        let response = Response::builder().header("content-type", "application/grpc");
+        let response = match reply {
+            Some(message) => {
+                let mut trailers = HeaderMap::new();
+                trailers.insert("grpc-status", HeaderValue::from_static("0"));
+                let body = Full::new(frame(&message)).with_trailers(async { Some(Ok(trailers)) });
+                response.body(body.boxed())
+            }
+            // trailers-only, like servers answering a service they do not implement
+            None => response
+                .header("grpc-status", "12")
+                .body(Full::new(Bytes::new()).boxed()),
+        };
+        Ok(response.unwrap())
+    }
+
+    #[test]
+    fn test_frames_and_status() {
+        let mut stream = frame(b"one").to_vec();
+        stream.extend(frame(b""));
+        stream.extend(trailer_frame(
+            "grpc-status: 5\r\ngrpc-message: no%20such%20user\r\n",
+        ));
+        let mut decoder = FrameDecoder::default();
+        let mut frames = vec![];
+        // split on every byte to cover frames cut between chunks
+        for byte in stream.chunks(1) {
+            decoder.push(byte);
+            frames.extend(std::iter::from_fn(|| decoder.next()));
+        }
+        assert!(decoder.is_empty());
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0], (0, Bytes::from_static(b"one")));
+        assert_eq!(frames[1], (0, Bytes::new()));
+        assert_eq!(frames[2].0, 0x80);
+
+        let trailers = web_trailers(&frames[2].1);
+        let not_found = status(200, &HashMap::new(), &trailers);
+        assert_eq!(
+            (not_found.code, not_found.name()),
+            (5, "NOT_FOUND".to_string())
+        );
+        assert_eq!(not_found.message, "no such user");
+
+        let trailers_only = values(&[("grpc-status", "16"), ("grpc-message", "expired")]);
+        let unauthenticated = status(200, &trailers_only, &HashMap::new());
+        assert_eq!(unauthenticated.name(), "UNAUTHENTICATED");
+        assert_eq!(unauthenticated.message, "expired");
+        assert_eq!(status(503, &HashMap::new(), &HashMap::new()).code, 14);
+        assert_eq!(status(200, &HashMap::new(), &HashMap::new()).code, UNKNOWN);
+    }
+
+    #[tokio::test]
+    async fn test_web_call_from_protos() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("greet.proto");
+        fs::write(&file, GREETER).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().unwrap();
+        let (received, mut requests) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 16384];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let _ = received.send(String::from_utf8_lossy(&buf[..n]).to_string());
+                // two HelloReply messages, `{"message": "hi"}` and `{"message": "bye"}`
+                let mut body = frame(b"\x0a\x02hi").to_vec();
+                body.extend(frame(b"\x0a\x03bye"));
+                body.extend(trailer_frame(
+                    "grpc-status: 0\r\ngrpc-message: all%20good\r\n",
+                ));
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/grpc-web+proto\r\n\
+                    Content-Length: {}\r\n\r\n",
+                    body.len()
+                );
+                let _ = socket.write_all(head.as_bytes()).await;
+                let _ = socket.write_all(&body).await;
+            }
+        });
+
+        let request = GRPCRequest {
+            url: format!("http://{}", addr),
+            source: GRPCSource::Protos {
+                files: vec![file.to_string_lossy().to_string()],
+                includes: vec![],
+            },
+            web: true,
+            ..Default::default()
+        };
+        let listed = services(request.clone(), false).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "greet.Greeter");
+        let hello = &listed[0].methods[0];
+        assert_eq!(hello.path, "greet.Greeter/Hello");
+        assert_eq!(hello.input_template, json!({"name": "", "times": 0}));
+        assert!(listed[0].methods[1].server_streaming);
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let on_message: GRPCListener = {
+            let seen = seen.clone();
+            Box::new(move |message| seen.lock().unwrap().push(message.seq))
+        };
+        let response = invoke(
+            GRPCRequest {
+                method: "greet.Greeter/Count".to_string(),
+                message: r#"{"name": "grpc", "times": 2}"#.to_string(),
+                ..request.clone()
+            },
+            Some(on_message),
+        )
+        .await
+        .unwrap();
+        assert_eq!((response.status, response.status_name.as_str()), (0, "OK"));
+        assert_eq!(response.message, "all good");
+        assert_eq!(response.trailers["grpc-status"], ["0"]);
+        assert_eq!(
+            response.messages,
+            [json!({"message": "hi"}), json!({"message": "bye"})]
+        );
+        assert_eq!(*seen.lock().unwrap(), [0, 1]);
+        let sent = requests.recv().await.unwrap();
+        assert!(sent.starts_with("POST /greet.Greeter/Count HTTP/1.1"));
+        assert!(sent
+            .to_lowercase()
+            .contains("content-type: application/grpc-web+proto"));
+
+        let chat = GRPCRequest {
+            method: "greet.Greeter/Chat".to_string(),
+            ..request.clone()
+        };
+        assert!(invoke(chat, None).await.is_err());
+        let missing = GRPCRequest {
+            method: "greet.Greeter/Missing".to_string(),
+            ..request
+        };
+        assert!(invoke(missing, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_call_over_http2_with_reflection() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("greet.proto");
+        fs::write(&file, GREETER).unwrap();
+        let pool = compile(&[file.to_string_lossy().to_string()], &[]).unwrap();
+        let request = GRPCRequest {
+            url: serve_h2(pool).await,
+            source: GRPCSource::Reflection,
+            ..Default::default()
+        };
+
+        // the v1 service answers UNIMPLEMENTED, v1alpha is used instead
+        let listed = services(request.clone(), false).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].name, "greet.Greeter");
+        assert_eq!(listed[0].methods.len(), 3);
+
+        let response = call(
+            GRPCRequest {
+                method: "greet.Greeter/Hello".to_string(),
+                message: r#"{"name": "grpc"}"#.to_string(),
+                ..request
+            },
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!((response.http_status, response.status), (200, 0));
+        assert_eq!(response.trailers["grpc-status"], ["0"]);
+        assert_eq!(response.messages, [json!({"message": "hi"})]);
+    }
+}
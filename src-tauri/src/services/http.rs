@@ -1,20 +1,51 @@
 // use crate::cookies;
-use crate::errors::APIError;
+use crate::errors::{APIError, TimeoutPhase};
+use crate::services::http_assert::{HTTPAssertion, HTTPTestReport};
+use crate::services::http_auth::HTTPAuth;
+use crate::services::http_body::{decode_text, BodyDecoder, BodyWriter, HTTPBodyKind};
+use crate::services::http_cookies::CookieJar;
+use crate::services::http_env::{HTTPEnvironment, Variables};
+use crate::services::http_graphql::{self, HTTPGraphQLResult};
+use crate::services::http_proxy::{self, HTTPProxySettings};
+use crate::services::http_tls::{HTTPCertificate, HTTPTlsOptions, PeerChainListener};
 use anyhow::{Context, Result};
 // use log::trace;
-use once_cell::sync::OnceCell;
-// use base64::{engine::general_purpose, Engine as _};
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
-use reqwest::{Body, Client, Method};
+use once_cell::sync::{Lazy, OnceCell};
+use base64::{engine::general_purpose, Engine as _};
+use hyper_util::client::legacy::connect::Connection;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT_ENCODING, AUTHORIZATION, CONTENT_ENCODING,
+    CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION, PROXY_AUTHORIZATION,
+};
+use reqwest::multipart::{Form, Part};
+use reqwest::redirect::Policy;
+use reqwest::{Client, Method, Proxy, Request, RequestBuilder, Response, StatusCode, Version};
+use rustls::client::{
+    ClientSessionMemoryCache, ClientSessionStore, Resumption, Tls12ClientSessionValue,
+    Tls13ClientSessionValue,
+};
+use rustls::crypto::tls12::Prf;
+use rustls::crypto::tls13::{Hkdf, HkdfExpander, OkmBlock};
+use rustls::crypto::{hmac, ActiveKeyExchange, CryptoProvider};
+use rustls::pki_types::ServerName;
+use rustls::{
+    CipherSuite, CipherSuiteCommon, NamedGroup, SupportedCipherSuite, Tls12CipherSuite,
+    Tls13CipherSuite,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
 use std::time::Duration;
 use std::vec;
+use tokio::task::AbortHandle;
 use tracing::debug;
-use tracing_subscriber::Layer;
 use url::Url;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -25,35 +56,285 @@ pub struct HTTPRequestKVParam {
     pub enabled: bool,
 }
 
+/// A `multipart/form-data` part, read from `file` when set and from `value` otherwise.
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
+pub struct HTTPMultipartPart {
+    pub name: String,
+    #[serde(default)]
+    pub value: String,
+    /// Path of a file to upload as the part content.
+    #[serde(default)]
+    pub file: Option<String>,
+    /// Defaults to the name of `file`.
+    #[serde(default)]
+    pub file_name: Option<String>,
+    /// Defaults to `text/plain` for values and to a guess from the extension for files.
+    #[serde(default)]
+    pub content_type: Option<String>,
+    pub enabled: bool,
+}
+
+/// Which redirects [`request`] follows, hops are reported in [`HTTPResponse::redirects`].
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HTTPRedirectPolicy {
+    /// Follows up to 10 redirects, like browsers and reqwest do.
+    #[default]
+    Follow,
+    /// Returns the redirect response itself.
+    None,
+    Limit {
+        max: usize,
+    },
+}
+
+impl HTTPRedirectPolicy {
+    fn max_redirects(&self) -> usize {
+        match self {
+            HTTPRedirectPolicy::Follow => 10,
+            HTTPRedirectPolicy::None => 0,
+            HTTPRedirectPolicy::Limit { max } => *max,
+        }
+    }
+}
+
+/// The HTTP version a request is sent with, see [`HTTPResponse::version`] for the one used.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum HTTPVersion {
+    /// HTTP/2 when the server picks it through ALPN, HTTP/1.1 otherwise and without TLS.
+    #[default]
+    Negotiate,
+    Http1,
+    /// HTTP/2 with prior knowledge, also over plain TCP.
+    Http2,
+}
+
+impl HTTPVersion {
+    /// Protocols offered through ALPN during the TLS handshake.
+    fn alpn(&self) -> Vec<Vec<u8>> {
+        match self {
+            HTTPVersion::Negotiate => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+            HTTPVersion::Http1 => vec![b"http/1.1".to_vec()],
+            HTTPVersion::Http2 => vec![b"h2".to_vec()],
+        }
+    }
+}
+
+/// The `Accept-Encoding` sent unless the request sets one itself. Compressed bodies are
+/// decoded either way, see [`HTTPResponse::compression`].
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum HTTPCompression {
+    /// Accepts gzip and brotli.
+    #[default]
+    Auto,
+    /// Sends no `Accept-Encoding`, asking for the body uncompressed.
+    None,
+}
+
+/// How the request body is built. `Raw` and `Json` send [`HTTPRequest::body`] as is.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HTTPBodyType {
+    #[default]
+    Raw,
+    Json,
+    UrlEncoded {
+        params: Vec<HTTPRequestKVParam>,
+    },
+    Multipart {
+        parts: Vec<HTTPMultipartPart>,
+    },
+    /// A GraphQL operation, sent as the standard JSON body.
+    #[serde(rename = "graphql")]
+    GraphQL {
+        query: String,
+        /// JSON text of the variables object, empty for none.
+        #[serde(default)]
+        variables: String,
+        #[serde(default)]
+        operation_name: Option<String>,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
 pub struct HTTPRequest {
+    /// Id chunk events and cancellation refer to, required when streaming so the UI can
+    /// subscribe to `http_body_{id}` before the first chunk arrives.
+    #[serde(default)]
+    pub id: Option<String>,
     pub method: String,
     pub url: String,
     pub body: String,
     pub content_type: String,
     pub headers: Vec<HTTPRequestKVParam>,
     pub query: Vec<HTTPRequestKVParam>,
+    /// Deliver the body as [`HTTPBodyChunk`]s while it downloads instead of buffering it.
+    #[serde(default)]
+    pub stream: bool,
+    /// Name of the [`CookieJar`] that sends and stores cookies for this request.
+    #[serde(default)]
+    pub cookie_jar: Option<String>,
+    #[serde(default)]
+    pub body_type: HTTPBodyType,
+    #[serde(default)]
+    pub redirect: HTTPRedirectPolicy,
+    /// Overrides the global proxy settings.
+    #[serde(default)]
+    pub proxy: Option<HTTPProxySettings>,
+    #[serde(default)]
+    pub tls: HTTPTlsOptions,
+    #[serde(default)]
+    pub auth: Option<HTTPAuth>,
+    /// Name of the [`HTTPEnvironment`] `{{name}}` placeholders are resolved from.
+    #[serde(default)]
+    pub environment: Option<String>,
+    /// Checks run on the response, reported in [`HTTPResponse::tests`].
+    #[serde(default)]
+    pub assertions: Vec<HTTPAssertion>,
+    /// Opens a new connection instead of reusing one kept alive by an earlier request.
+    #[serde(default)]
+    pub fresh_connection: bool,
+    #[serde(default)]
+    pub http_version: HTTPVersion,
+    #[serde(default)]
+    pub compression: HTTPCompression,
+    /// Leaves out the copy of the body kept on disk for saving it, so
+    /// [`HTTPResponse::body_file`] stays empty. Set for requests sent in bulk.
+    #[serde(skip)]
+    pub skip_body_file: bool,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+impl HTTPRequest {
+    /// Resolves placeholders from [`HTTPRequest::environment`], which is cleared as
+    /// nothing is left to resolve.
+    pub fn resolve(&mut self) -> Result<(), APIError> {
+        let variables = match self.environment.take() {
+            Some(name) => Variables::from(&HTTPEnvironment::load(&name)?),
+            None => Variables::default(),
+        };
+        self.resolve_variables(&variables)
+    }
+
+    /// Resolves placeholders like [`HTTPRequest::resolve`], returning a copy in which those
+    /// of secret variables are kept, the one to store in place of the request sent. The copy
+    /// keeps its environment so they resolve again when it is sent later.
+    pub fn resolve_keeping_secrets(&mut self) -> Result<HTTPRequest, APIError> {
+        let environment = match &self.environment {
+            Some(name) => HTTPEnvironment::load(name)?,
+            None => {
+                self.resolve_variables(&Variables::default())?;
+                return Ok(self.clone());
+            }
+        };
+        // dynamic values are generated once, so the copy holds those that were sent
+        self.resolve_variables(&Variables::without_secrets(&environment))?;
+        let kept = self.clone();
+        self.environment = None;
+        self.resolve_variables(&Variables::secrets(&environment))?;
+        Ok(kept)
+    }
+
+    /// Replaces the `{{name}}` placeholders of the url, headers, query, body and auth,
+    /// failing with every name that has no value.
+    pub fn resolve_variables(&mut self, variables: &Variables) -> Result<(), APIError> {
+        let mut missing = BTreeSet::new();
+        let mut values: Vec<&mut String> = vec![&mut self.url, &mut self.body];
+        for param in self.headers.iter_mut().chain(self.query.iter_mut()) {
+            values.extend([&mut param.key, &mut param.value]);
+        }
+        match &mut self.body_type {
+            HTTPBodyType::UrlEncoded { params } => {
+                for param in params {
+                    values.extend([&mut param.key, &mut param.value]);
+                }
+            }
+            HTTPBodyType::Multipart { parts } => {
+                for part in parts {
+                    values.extend([&mut part.name, &mut part.value]);
+                    values.extend(part.file.as_mut());
+                }
+            }
+            HTTPBodyType::GraphQL {
+                query, variables, ..
+            } => values.extend([query, variables]),
+            HTTPBodyType::Raw | HTTPBodyType::Json => {}
+        }
+        if let Some(auth) = &mut self.auth {
+            values.extend(auth.values_mut());
+        }
+        for value in values {
+            *value = variables.render(value, &mut missing);
+        }
+        if !missing.is_empty() {
+            return Err(APIError::UnresolvedVariables(missing.into_iter().collect()));
+        }
+        Ok(())
+    }
+}
+
+/// Timeouts in seconds, 0 disables a limit.
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct RequestTimeout {
+    /// Establishing the connection, TLS handshake included.
     pub connect: u64,
-    pub write: u64,
+    /// Waiting for the response head once the request is handed over, connecting and
+    /// uploading included. Applies to each redirect hop.
+    #[serde(alias = "write")]
+    pub response: u64,
+    /// Idle time between two reads of the response.
     pub read: u64,
+    /// Deadline for the whole exchange, body download included.
+    #[serde(default)]
+    pub total: u64,
+}
+
+impl Default for RequestTimeout {
+    fn default() -> Self {
+        RequestTimeout {
+            connect: 10,
+            response: 0,
+            read: 0,
+            total: 0,
+        }
+    }
+}
+
+impl RequestTimeout {
+    fn limit(secs: u64) -> Option<Duration> {
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug, Default)]
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct HTTPStats {
     pub remote_addr: String,
+    /// Proxy the request went through, empty when connected directly.
+    pub proxy: String,
     pub is_https: bool,
     pub cipher: String,
+    /// `h2` when the server picked HTTP/2 through ALPN, None otherwise and without TLS.
+    #[serde(default)]
+    pub alpn: Option<String>,
+    /// Certificates presented by the server, leaf first.
+    pub certificates: Vec<HTTPCertificate>,
     pub dns_lookup: u32,
+    /// Time spent opening a new connection (TCP and TLS), 0 when none was opened.
+    pub connect: u32,
+    /// Whether the request went over a connection kept alive from an earlier one.
+    #[serde(default)]
+    pub reused: bool,
     pub tcp: u32,
     pub tls: u32,
+    /// Always 0, hyper writes the request from its own connection task so the upload
+    /// counts towards `server_processing`.
     pub send: u32,
+    /// From handing the request to a ready connection until the response head arrives.
     pub server_processing: u32,
     pub content_transfer: u32,
     pub total: u32,
@@ -67,38 +348,494 @@ impl HTTPStats {
     }
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct HTTPResponse {
+    pub id: String,
     pub url: String,
     // pub req: HTTPRequest,
     pub latency: u32,
     pub status: u16,
     pub headers: HashMap<String, Vec<String>>,
+    /// The decoded body for textual kinds, empty otherwise and when streamed.
     pub body: String,
+    /// Base64 encoded body for image and binary kinds.
+    pub body_base64: Option<String>,
+    pub body_kind: HTTPBodyKind,
+    /// Copy of the raw body on disk, see [`crate::services::http_body::save_body`].
+    pub body_file: String,
     pub stats: HTTPStats,
     pub length: u64,
+    /// Redirects followed before reaching `url`, in order.
+    pub redirects: Vec<HTTPRedirectHop>,
+    /// Outcome of [`HTTPRequest::assertions`], `None` when the request has none.
+    #[serde(default)]
+    pub tests: Option<HTTPTestReport>,
+    /// `data` and `errors` of the response to a [`HTTPBodyType::GraphQL`] request.
+    #[serde(default)]
+    pub graphql: Option<HTTPGraphQLResult>,
+    /// Protocol of the response, e.g. `HTTP/1.1` or `HTTP/2`.
+    #[serde(default)]
+    pub version: String,
+    /// `Content-Encoding` the body was decoded from, None when it came uncompressed.
+    #[serde(default)]
+    pub compression: Option<String>,
 }
 
-struct JsonVisitor<'a>(&'a mut BTreeMap<String, String>);
+#[cfg(test)]
+impl HTTPResponse {
+    /// A 200 `application/json` response with `body`, for tests.
+    pub(crate) fn json(body: &str) -> HTTPResponse {
+        HTTPResponse {
+            id: "1".to_string(),
+            url: "https://example.com".to_string(),
+            latency: 0,
+            status: 200,
+            headers: HashMap::from([(
+                "content-type".to_string(),
+                vec!["application/json".to_string()],
+            )]),
+            body: body.to_string(),
+            body_base64: None,
+            body_kind: HTTPBodyKind::Json,
+            body_file: String::new(),
+            stats: HTTPStats::default(),
+            length: body.len() as u64,
+            redirects: vec![],
+            tests: None,
+            graphql: None,
+            version: "HTTP/1.1".to_string(),
+            compression: None,
+        }
+    }
+}
 
-impl<'a> tracing::field::Visit for JsonVisitor<'a> {
-    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
-        self.0
-            .insert(field.name().to_string(), format!("{:?}", value));
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPRedirectHop {
+    pub url: String,
+    pub status: u16,
+    /// The resolved `Location` the request was sent to next.
+    pub location: String,
+    pub headers: HashMap<String, Vec<String>>,
+}
+
+/// A piece of a streamed response body, emitted as `http_body_{id}`.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPBodyChunk {
+    pub id: String,
+    pub seq: u64,
+    /// Base64 encoded chunk bytes.
+    pub data: String,
+    pub received: u64,
+    pub total: Option<u64>,
+}
+
+pub type BodyListener = Box<dyn Fn(HTTPBodyChunk) + Send + Sync>;
+
+/// Abort handles of requests started through [`request_cancellable`], keyed by request id.
+static IN_FLIGHT_REQUESTS: Lazy<tokio::sync::Mutex<HashMap<String, AbortHandle>>> =
+    Lazy::new(|| tokio::sync::Mutex::new(HashMap::new()));
+
+/// Runs [`request_on`] on its own task so it can be aborted with [`cancel_request`].
+pub async fn request_cancellable(
+    client: Option<HTTPClient>,
+    mut http_request: HTTPRequest,
+    timeout: Option<RequestTimeout>,
+    on_chunk: Option<BodyListener>,
+) -> Result<HTTPResponse, APIError> {
+    if http_request.stream && http_request.id.is_none() {
+        return Err(APIError::Http(
+            "a streamed request needs an id to listen for its body chunks".to_string(),
+        ));
+    }
+    let id = http_request
+        .id
+        .get_or_insert_with(|| uuid::Uuid::new_v4().to_string())
+        .clone();
+    let mut in_flight = IN_FLIGHT_REQUESTS.lock().await;
+    if in_flight.contains_key(&id) {
+        return Err(APIError::Http(format!("Request {} is already in flight", id)));
+    }
+    let task = tokio::spawn(request_on(client, http_request, timeout, on_chunk));
+    let task_id = task.id();
+    in_flight.insert(id.clone(), task.abort_handle());
+    drop(in_flight);
+
+    let result = task.await;
+    // once cancelled, the id may already belong to a new request
+    let mut in_flight = IN_FLIGHT_REQUESTS.lock().await;
+    if in_flight.get(&id).is_some_and(|handle| handle.id() == task_id) {
+        in_flight.remove(&id);
+    }
+    drop(in_flight);
+    match result {
+        Ok(response) => response,
+        Err(e) if e.is_cancelled() => Err(APIError::Cancelled(id)),
+        Err(e) => Err(APIError::General(e.to_string())),
     }
 }
 
+/// Aborts an in-flight request, returns false when no request with that id is running.
+pub async fn cancel_request(id: &str) -> bool {
+    match IN_FLIGHT_REQUESTS.lock().await.remove(id) {
+        Some(handle) => {
+            handle.abort();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Sends `http_request`, timing it with a [`HTTPTrace`] of its own.
 pub async fn request(
     http_request: HTTPRequest,
     timeout: Option<RequestTimeout>,
+    on_chunk: Option<BodyListener>,
+) -> Result<HTTPResponse, APIError> {
+    request_on(None, http_request, timeout, on_chunk).await
+}
+
+/// Like [`request`], sent on `client` when given so its pooled connections are reused.
+/// Placeholders must already be resolved.
+pub async fn request_on(
+    client: Option<HTTPClient>,
+    http_request: HTTPRequest,
+    timeout: Option<RequestTimeout>,
+    on_chunk: Option<BodyListener>,
 ) -> Result<HTTPResponse, APIError> {
-    let trace = get_http_trace();
-    trace.reset();
-    let mut client_builder = Client::builder();
+    let timeout = timeout.unwrap_or_default();
+    let total = RequestTimeout::limit(timeout.total);
+    let trace = Arc::new(HTTPTrace::new());
+    let sending = CURRENT_HTTP_TRACE.scope(
+        trace.clone(),
+        send(client, http_request, timeout, on_chunk, trace),
+    );
+    match total {
+        Some(total) => tokio::time::timeout(total, sending).await.map_err(|_| {
+            APIError::Timeout(
+                TimeoutPhase::Total,
+                format!("request did not complete within {}s", total.as_secs()),
+            )
+        })?,
+        None => sending.await,
+    }
+}
+
+type TlsSession = (String, Option<String>, Vec<HTTPCertificate>);
+
+/// A client whose connections are pooled across the requests sent on it, see [`request_on`].
+#[derive(Clone)]
+pub struct HTTPClient {
+    client: Client,
+    /// The proxy settings requests are routed with, for [`HTTPStats::proxy`].
+    proxy: HTTPProxySettings,
+    /// Cipher, ALPN protocol and certificates of the TLS connections opened, by origin,
+    /// reported again for the requests that reuse them.
+    sessions: Arc<Mutex<HashMap<String, TlsSession>>>,
+}
+
+impl HTTPClient {
+    /// Builds the client `http_request` is sent with. Its TLS options, HTTP version, proxy,
+    /// cookie jar and connect and read timeouts apply to every request sent on the client.
+    pub fn new(http_request: &HTTPRequest, timeout: &RequestTimeout) -> Result<Self, APIError> {
+        Self::build(
+            http_request,
+            timeout,
+            Arc::new(|chain| {
+                if let Some(trace) = HTTPTrace::current() {
+                    trace.set_certificates(chain);
+                }
+            }),
+        )
+    }
+
+    /// The settings a client is built from, requests with the same profile can share one.
+    fn profile(http_request: &HTTPRequest, timeout: &RequestTimeout) -> Result<String, APIError> {
+        let proxy = match &http_request.proxy {
+            Some(proxy) => proxy.clone(),
+            None => HTTPProxySettings::global()?,
+        };
+        let profile = serde_json::json!({
+            "tls": http_request.tls,
+            "httpVersion": http_request.http_version,
+            "proxy": proxy,
+            "cookieJar": http_request.cookie_jar,
+            "connect": timeout.connect,
+            "read": timeout.read,
+        });
+        Ok(profile.to_string())
+    }
+
+    /// Remembers the TLS details of a new connection and fills them in for a reused one,
+    /// whose handshake happened during an earlier request.
+    fn tls_session(&self, origin: &str, stats: &mut HTTPStats) {
+        let mut sessions = match self.sessions.lock() {
+            Ok(sessions) => sessions,
+            Err(_) => return,
+        };
+        if !stats.reused {
+            if !stats.certificates.is_empty() {
+                let session = (
+                    stats.cipher.clone(),
+                    stats.alpn.clone(),
+                    stats.certificates.clone(),
+                );
+                sessions.insert(origin.to_string(), session);
+            }
+        } else if let Some((cipher, alpn, certificates)) = sessions.get(origin) {
+            stats.cipher = cipher.clone();
+            stats.alpn = alpn.clone();
+            stats.certificates = certificates.clone();
+        }
+    }
+
+    fn build(
+        http_request: &HTTPRequest,
+        timeout: &RequestTimeout,
+        on_chain: PeerChainListener,
+    ) -> Result<Self, APIError> {
+        let mut client_builder = Client::builder()
+            .dns_resolver(Arc::new(TracedResolver))
+            .connector_layer(TracedConnectorLayer);
+        let mut tls = http_request
+            .tls
+            .client_config_with(TRACED_PROVIDER.clone(), on_chain)?;
+        tls.alpn_protocols = http_request.http_version.alpn();
+        let sessions = TracedSessions(ClientSessionMemoryCache::new(256));
+        tls.resumption = Resumption::store(Arc::new(sessions));
+        client_builder = client_builder.use_preconfigured_tls(tls).tls_info(true);
+        client_builder = match http_request.http_version {
+            HTTPVersion::Negotiate => client_builder,
+            HTTPVersion::Http1 => client_builder.http1_only(),
+            HTTPVersion::Http2 => client_builder.http2_prior_knowledge(),
+        };
+        let proxy = match &http_request.proxy {
+            Some(proxy) => proxy.clone(),
+            None => HTTPProxySettings::global()?,
+        };
+        client_builder = match proxy.clone() {
+            HTTPProxySettings::Direct => client_builder.no_proxy(),
+            proxy => client_builder.proxy(Proxy::custom(move |url| proxy.resolve(url))),
+        };
+        if let Some(jar) = &http_request.cookie_jar {
+            client_builder = client_builder.cookie_provider(CookieJar::get(jar)?);
+        }
+        if let Some(connect) = RequestTimeout::limit(timeout.connect) {
+            client_builder = client_builder.connect_timeout(connect);
+        }
+        if let Some(read) = RequestTimeout::limit(timeout.read) {
+            client_builder = client_builder.read_timeout(read);
+        }
+        // redirects are followed by hand to record every hop, and bodies decoded in
+        // `send` so the response keeps its Content-Encoding
+        let client = client_builder
+            .no_gzip()
+            .no_brotli()
+            .redirect(Policy::none())
+            .build()
+            .context("build client")?;
+        Ok(HTTPClient {
+            client,
+            proxy,
+            sessions: Arc::default(),
+        })
+    }
+}
+
+/// The clients requests from the app are sent on, one per settings profile, so
+/// connections are kept alive between requests. Managed as Tauri state.
+#[derive(Default)]
+pub struct HTTPClients(Mutex<HashMap<String, HTTPClient>>);
+
+impl HTTPClients {
+    /// The client for the settings of `http_request`, built on first use. None when the
+    /// request asks for a [`HTTPRequest::fresh_connection`].
+    pub fn get(
+        &self,
+        http_request: &HTTPRequest,
+        timeout: &RequestTimeout,
+    ) -> Result<Option<HTTPClient>, APIError> {
+        if http_request.fresh_connection {
+            return Ok(None);
+        }
+        let profile = HTTPClient::profile(http_request, timeout)?;
+        let mut clients = self
+            .0
+            .lock()
+            .map_err(|e| APIError::General(e.to_string()))?;
+        if let Some(client) = clients.get(&profile) {
+            return Ok(Some(client.clone()));
+        }
+        let client = HTTPClient::new(http_request, timeout)?;
+        clients.insert(profile, client.clone());
+        Ok(Some(client))
+    }
+
+    /// Drops every client along with its pooled connections.
+    pub fn clear(&self) -> Result<(), APIError> {
+        self.0
+            .lock()
+            .map_err(|e| APIError::General(e.to_string()))?
+            .clear();
+        Ok(())
+    }
+}
+
+async fn send(
+    client: Option<HTTPClient>,
+    http_request: HTTPRequest,
+    timeout: RequestTimeout,
+    on_chunk: Option<BodyListener>,
+    trace: Arc<HTTPTrace>,
+) -> Result<HTTPResponse, APIError> {
+    let id = http_request
+        .id
+        .clone()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let stream = http_request.stream;
+    let skip_body_file = http_request.skip_body_file;
+    trace.start();
     // let original_req = http_request.clone();
     // .http_stats(HTTPStats::default())
+    let http_client = match client {
+        Some(client) => client,
+        None => {
+            let chain_trace = trace.clone();
+            HTTPClient::build(
+                &http_request,
+                &timeout,
+                Arc::new(move |chain| chain_trace.set_certificates(chain)),
+            )?
+        }
+    };
+
+    let request_url = Url::parse(&http_request.url)?;
+    let effective_proxy = http_client.proxy.resolve(&request_url);
+    if request_url.scheme() == "https" {
+        trace.tls();
+    }
+    let (mut res, redirects) = exchange(&http_client.client, http_request, &timeout).await?;
 
+    // let content_encoding_key = "content-encoding";
+    let headers = response_headers(res.headers())?;
+    debug!("response: {:#?}", res);
+    let url = res.url().to_string();
+    let origin = res.url().origin().ascii_serialization();
+    let content_length = res.content_length();
+    let status = res.status().as_u16();
+    let remote_addr: String = res.remote_addr().map_or("".into(), |a| a.to_string());
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let version = version_name(res.version());
+    let content_encoding = res
+        .headers()
+        .get(CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let mut decoder = BodyDecoder::new(&content_encoding);
+    let compressed = decoder.is_some();
+    let mut body_writer = match skip_body_file {
+        true => BodyWriter::discarding(),
+        false => BodyWriter::create(&id).await?,
+    };
+    let mut body_bytes = Vec::new();
+    let mut seq = 0;
+    let mut received = 0;
+    let mut length = 0;
+    loop {
+        let chunk = match res.chunk().await.map_err(timeout_error)? {
+            Some(chunk) => {
+                received += chunk.len() as u64;
+                match decoder.as_mut() {
+                    Some(decoder) => decoder.decode(&chunk)?,
+                    None => chunk.to_vec(),
+                }
+            }
+            // HEAD, 204 and 304 responses keep the Content-Encoding of a body they lack
+            None => match decoder.take() {
+                Some(decoder) if received > 0 => decoder.finish()?,
+                _ => break,
+            },
+        };
+        if chunk.is_empty() {
+            continue;
+        }
+        length += chunk.len() as u64;
+        body_writer.write(&chunk).await?;
+        if !stream {
+            body_bytes.extend_from_slice(&chunk);
+            continue;
+        }
+        if let Some(listener) = on_chunk.as_ref() {
+            listener(HTTPBodyChunk {
+                id: id.clone(),
+                seq,
+                data: general_purpose::STANDARD.encode(&chunk),
+                received,
+                total: content_length,
+            });
+        }
+        seq += 1;
+    }
+    // a HEAD, 204 or 304 response keeps a Content-Encoding nothing was decoded from
+    let compression = (compressed && received > 0).then(|| content_encoding.trim().to_lowercase());
+    let body_kind = HTTPBodyKind::detect(&content_type, body_writer.head());
+    let body_file = body_writer
+        .finish()
+        .await?
+        .map_or(String::new(), |path| path.to_string_lossy().to_string());
+    let (body, body_base64) = match (stream, body_kind.is_text()) {
+        (true, _) => (String::new(), None),
+        (false, true) => (decode_text(&content_type, &body_bytes), None),
+        (false, false) => (
+            String::new(),
+            Some(general_purpose::STANDARD.encode(&body_bytes)),
+        ),
+    };
+    trace.done();
+    let mut stats: HTTPStats = trace.as_ref().into();
+    stats.remote_addr = remote_addr;
+    if stats.is_https {
+        http_client.tls_session(&origin, &mut stats);
+    }
+    stats.proxy = effective_proxy.map_or("".into(), |p| http_proxy::display(&p));
+
+    let response = HTTPResponse {
+        id,
+        url,
+        // req: original_req,
+        length,
+        latency: stats.total,
+        status,
+        headers,
+        body,
+        body_base64,
+        body_kind,
+        body_file,
+        stats,
+        redirects,
+        tests: None,
+        graphql: None,
+        version,
+        compression,
+    };
+    Ok(response)
+}
+
+/// Builds the request described by `http_request` and sends it on `client`, answering
+/// an authentication challenge once. Returns as soon as the response head arrives.
+async fn exchange(
+    client: &Client,
+    http_request: HTTPRequest,
+    timeout: &RequestTimeout,
+) -> Result<(Response, Vec<HTTPRedirectHop>), APIError> {
     let mut current_url: Url = Url::parse(http_request.url.as_str())?;
     for q in http_request.query {
         if !q.enabled {
@@ -106,12 +843,6 @@ pub async fn request(
         }
         current_url.query_pairs_mut().append_pair(&q.key, &q.value);
     }
-
-    if current_url.scheme() == "https" {
-        trace.tls();
-        client_builder = client_builder.use_rustls_tls().tls_info(true);
-    }
-    let client = client_builder.build().context("build client")?;
     // let mut req = Request::new(http_request.get_method(), http_request.uri.parse()?);
     let method = match http_request.method.to_uppercase().as_str() {
         "POST" => Method::POST,
@@ -125,56 +856,182 @@ pub async fn request(
         _ => Method::GET,
     };
 
-    let connect_timeout = Duration::from_secs(timeout.map_or(10, |t| t.connect));
-    // let write_timeout = Duration::from_secs(timeout.write);
-    // let read_timeout = Duration::from_secs(timeout.read);
+    // form bodies carry their own content type, multipart with a generated boundary
+    let form_body = matches!(
+        http_request.body_type,
+        HTTPBodyType::UrlEncoded { .. } | HTTPBodyType::Multipart { .. }
+    );
     let mut req_headers = HeaderMap::new();
-    // let mut set_content_type = false;
     for h in http_request.headers {
         if !h.enabled {
             continue;
         }
-        // if h.key.to_lowercase() == "content-type" {
-        //     set_content_type = true;
-        // }
-        req_headers.insert(
-            h.key.parse::<HeaderName>()?,
-            HeaderValue::from_str(h.value.as_str())?,
-        );
+        let name = h.key.parse::<HeaderName>()?;
+        if form_body && name == CONTENT_TYPE {
+            continue;
+        }
+        req_headers.insert(name, HeaderValue::from_str(h.value.as_str())?);
+    }
+    if http_request.compression == HTTPCompression::Auto
+        && !req_headers.contains_key(ACCEPT_ENCODING)
+    {
+        req_headers.insert(ACCEPT_ENCODING, HeaderValue::from_static("gzip, br"));
+    }
+    let has_content_type = req_headers.contains_key(CONTENT_TYPE);
+
+    let request_builder = client.request(method, current_url).headers(req_headers);
+    let request_builder = with_body(
+        request_builder,
+        http_request.body_type,
+        http_request.body,
+        &http_request.content_type,
+        has_content_type,
+    )
+    .await?;
+    let mut request = request_builder.build()?;
+    if let Some(auth) = &http_request.auth {
+        auth.authorize(client, &mut request).await?;
+    }
+    let retry = request.try_clone();
+    let response_timeout = RequestTimeout::limit(timeout.response);
+    let (res, redirects) =
+        execute(client, request, &http_request.redirect, response_timeout).await?;
+    if let (Some(auth), Some(retry)) = (&http_request.auth, retry) {
+        if res.status() == StatusCode::UNAUTHORIZED {
+            if let Some(request) = auth.answer(client, &res, retry).await? {
+                return execute(client, request, &http_request.redirect, response_timeout).await;
+            }
+        }
     }
-    req_headers.insert("Accept-Encoding", HeaderValue::from_str("gzip, br")?);
+    Ok((res, redirects))
+}
 
-    let mut request_builder = client
-        .request(method, current_url)
-        .headers(req_headers)
-        .timeout(connect_timeout);
+/// Sends `http_request` on `client` and hands back the response as soon as its head
+/// arrives, for bodies read while they stream such as server-sent events. Placeholders
+/// must already be resolved.
+pub async fn open(
+    client: &HTTPClient,
+    http_request: HTTPRequest,
+    timeout: &RequestTimeout,
+) -> Result<Response, APIError> {
+    let (res, _) = exchange(&client.client, http_request, timeout).await?;
+    Ok(res)
+}
 
-    request_builder = if !http_request.body.is_empty() {
-        let body = if http_request.content_type.starts_with("multipart/form-data") {
-            // let buf = general_purpose::STANDARD.decode(http_request.body.as_bytes())?;
-            // Body::from(buf)
-            Body::from(http_request.body)
-        } else {
-            Body::from(http_request.body)
+/// Sends `request`, following redirects as allowed by `policy`.
+async fn execute(
+    client: &Client,
+    mut request: Request,
+    policy: &HTTPRedirectPolicy,
+    response_timeout: Option<Duration>,
+) -> Result<(Response, Vec<HTTPRedirectHop>), APIError> {
+    let trace = HTTPTrace::current();
+    let mut redirects = Vec::new();
+    loop {
+        let method = request.method().clone();
+        let req_headers = request.headers().clone();
+        let retry = request.try_clone();
+        if let Some(trace) = &trace {
+            trace.sending();
+        }
+        let sending = client.execute(request);
+        let res = match response_timeout {
+            Some(limit) => tokio::time::timeout(limit, sending).await.map_err(|_| {
+                APIError::Timeout(
+                    TimeoutPhase::Response,
+                    format!("no response received within {}s", limit.as_secs()),
+                )
+            })?,
+            None => sending.await,
+        }
+        .map_err(timeout_error)?;
+        if let Some(trace) = &trace {
+            trace.got_first_response_byte();
+        }
+
+        let location = match res.headers().get(LOCATION) {
+            Some(location) if res.status().is_redirection() => location.to_str()?.to_string(),
+            _ => return Ok((res, redirects)),
+        };
+        if redirects.len() >= policy.max_redirects() {
+            if let HTTPRedirectPolicy::None = policy {
+                return Ok((res, redirects));
+            }
+            return Err(APIError::Http(format!(
+                "too many redirects, stopped after {}",
+                redirects.len()
+            )));
+        }
+        let next_url = res.url().join(&location)?;
+        let status = res.status();
+        // 301, 302 and 303 turn into a body-less GET the way browsers do it
+        let keep_body = status == StatusCode::TEMPORARY_REDIRECT
+            || status == StatusCode::PERMANENT_REDIRECT
+            || method == Method::GET
+            || method == Method::HEAD;
+        request = match retry {
+            Some(next) if keep_body => next,
+            None if keep_body => {
+                return Err(APIError::Http(format!(
+                    "cannot resend a streamed body to {}",
+                    next_url
+                )));
+            }
+            _ => {
+                let mut next = Request::new(Method::GET, next_url.clone());
+                *next.headers_mut() = req_headers;
+                next.headers_mut().remove(CONTENT_TYPE);
+                next.headers_mut().remove(CONTENT_LENGTH);
+                next
+            }
         };
-        request_builder.body(body)
+        *request.url_mut() = next_url.clone();
+        if res.url().host_str() != next_url.host_str() {
+            for name in [AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION] {
+                request.headers_mut().remove(name);
+            }
+        }
+        redirects.push(HTTPRedirectHop {
+            url: res.url().to_string(),
+            status: status.as_u16(),
+            location: next_url.to_string(),
+            headers: response_headers(res.headers())?,
+        });
+    }
+}
+
+fn version_name(version: Version) -> String {
+    match version {
+        Version::HTTP_09 => "HTTP/0.9".to_string(),
+        Version::HTTP_10 => "HTTP/1.0".to_string(),
+        Version::HTTP_11 => "HTTP/1.1".to_string(),
+        Version::HTTP_2 => "HTTP/2".to_string(),
+        Version::HTTP_3 => "HTTP/3".to_string(),
+        version => format!("{:?}", version),
+    }
+}
+
+/// Reports the connect and read timeouts reqwest enforces with their [`TimeoutPhase`].
+fn timeout_error(error: reqwest::Error) -> APIError {
+    if !error.is_timeout() {
+        return error.into();
+    }
+    let phase = if error.is_connect() {
+        TimeoutPhase::Connect
     } else {
-        request_builder
+        TimeoutPhase::Read
     };
-    let res = request_builder.send().await?;
+    APIError::Timeout(phase, error.to_string())
+}
 
-    // let content_encoding_key = "content-encoding";
+pub fn response_headers(res_headers: &HeaderMap) -> Result<HashMap<String, Vec<String>>, APIError> {
     let mut headers = HashMap::new();
-    let mut set_cookies = Vec::new();
 
-    for (name, value) in res.headers() {
+    for (name, value) in res_headers {
         let mut key = name.to_string();
         key = key.to_lowercase();
 
         let value = value.to_str()?.to_string();
-        if key == "set-cookie" {
-            set_cookies.push(value.clone());
-        }
         let values: Option<&Vec<String>> = headers.get(&key);
         match values {
             Some(values) => {
@@ -187,27 +1044,81 @@ pub async fn request(
             }
         }
     }
-    debug!("response: {:#?}", res);
-    let url = res.url().to_string();
-    let length = res.content_length().unwrap_or(0);
-    let status = res.status().as_u16();
-    let remote_addr: String = res.remote_addr().map_or("".into(), |a| a.to_string());
-    let body = res.text().await?;
-    trace.done();
-    let mut stats: HTTPStats = trace.into();
-    stats.remote_addr = remote_addr;
+    Ok(headers)
+}
 
-    let response = HTTPResponse {
-        url,
-        // req: original_req,
-        length,
-        latency: stats.total,
-        status,
-        headers,
-        body,
-        stats,
+/// Attaches the body described by `body_type`, setting its content type unless a header already does.
+async fn with_body(
+    request_builder: RequestBuilder,
+    body_type: HTTPBodyType,
+    body: String,
+    content_type: &str,
+    has_content_type: bool,
+) -> Result<RequestBuilder, APIError> {
+    let request_builder = match body_type {
+        HTTPBodyType::Raw if body.is_empty() => request_builder,
+        HTTPBodyType::Raw => {
+            if !has_content_type && !content_type.is_empty() {
+                request_builder
+                    .header(CONTENT_TYPE, content_type)
+                    .body(body)
+            } else {
+                request_builder.body(body)
+            }
+        }
+        HTTPBodyType::Json => {
+            serde_json::from_str::<serde_json::Value>(&body)?;
+            if has_content_type {
+                request_builder.body(body)
+            } else {
+                request_builder
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body)
+            }
+        }
+        HTTPBodyType::UrlEncoded { params } => {
+            let params: Vec<(String, String)> = params
+                .into_iter()
+                .filter(|p| p.enabled)
+                .map(|p| (p.key, p.value))
+                .collect();
+            request_builder.form(&params)
+        }
+        HTTPBodyType::Multipart { parts } => {
+            let mut form = Form::new();
+            for p in parts.into_iter().filter(|p| p.enabled) {
+                let mut part = match &p.file {
+                    Some(file) => Part::file(file)
+                        .await
+                        .map_err(|e| APIError::Io(format!("{}: {}", file, e)))?,
+                    None => Part::text(p.value),
+                };
+                if let Some(file_name) = p.file_name {
+                    part = part.file_name(file_name);
+                }
+                if let Some(content_type) = p.content_type.filter(|c| !c.is_empty()) {
+                    part = part.mime_str(&content_type)?;
+                }
+                form = form.part(p.name, part);
+            }
+            request_builder.multipart(form)
+        }
+        HTTPBodyType::GraphQL {
+            query,
+            variables,
+            operation_name,
+        } => {
+            let body = http_graphql::body(&query, &variables, operation_name.as_deref())?;
+            if has_content_type {
+                request_builder.body(body)
+            } else {
+                request_builder
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(body)
+            }
+        }
     };
-    Ok(response)
+    Ok(request_builder)
 }
 
 impl From<&HTTPTrace> for HTTPStats {
@@ -215,11 +1126,14 @@ impl From<&HTTPTrace> for HTTPStats {
         let mut stats = HTTPStats::new();
         stats.is_https = trace.is_tls();
         stats.cipher = trace.get_cipher();
+        stats.alpn = trace.get_alpn();
+        stats.certificates = trace.get_certificates();
         stats.dns_lookup = trace.dns_consuming();
+        stats.connect = trace.connect_consuming();
+        stats.reused = trace.reused();
         stats.tcp = trace.tcp_consuming();
         stats.tls = trace.tls_consuming();
         stats.server_processing = trace.server_processing_consuming();
-        stats.send = trace.send_consuming();
         stats.content_transfer = trace.content_transfer_consuming();
         stats.total = trace.consuming();
         stats
@@ -230,6 +1144,8 @@ impl From<&HTTPTrace> for HTTPStats {
 struct HTTPTrace {
     is_tls_value: AtomicBool,
     cipher_value: Mutex<String>,
+    alpn_value: Mutex<Option<String>>,
+    certificates_value: Mutex<Vec<HTTPCertificate>>,
     start_value: AtomicU64,
     get_conn_value: AtomicU64,
     dns_start_value: AtomicU64,
@@ -239,7 +1155,7 @@ struct HTTPTrace {
     tls_start_value: AtomicU64,
     tls_done_value: AtomicU64,
     http_start_value: AtomicU64,
-    written_value: AtomicU64,
+    sending_value: AtomicU64,
     got_first_response_byte_value: AtomicU64,
     done_value: AtomicU64,
 }
@@ -253,22 +1169,9 @@ impl HTTPTrace {
             ..Default::default()
         }
     }
-    fn reset(&self) {
-        self.set_cipher("".to_string());
-        self.is_tls_value.store(false, Ordering::Relaxed);
-        self.start_value.store(0, Ordering::Relaxed);
-        self.get_conn_value.store(0, Ordering::Relaxed);
-        self.dns_start_value.store(0, Ordering::Relaxed);
-        self.dns_done_value.store(0, Ordering::Relaxed);
-        self.tcp_start_value.store(0, Ordering::Relaxed);
-        self.tcp_done_value.store(0, Ordering::Relaxed);
-        self.tls_start_value.store(0, Ordering::Relaxed);
-        self.tls_done_value.store(0, Ordering::Relaxed);
-        self.http_start_value.store(0, Ordering::Relaxed);
-        self.written_value.store(0, Ordering::Relaxed);
-        self.got_first_response_byte_value
-            .store(0, Ordering::Relaxed);
-        self.done_value.store(0, Ordering::Relaxed);
+    /// The trace of the request being driven by the current task, if any.
+    fn current() -> Option<Arc<HTTPTrace>> {
+        CURRENT_HTTP_TRACE.try_with(|trace| trace.clone()).ok()
     }
     fn set_cipher(&self, value: String) {
         if let Ok(mut cipher) = self.cipher_value.lock() {
@@ -281,18 +1184,41 @@ impl HTTPTrace {
         }
         "".to_string()
     }
+    fn set_alpn(&self, value: Option<String>) {
+        if let Ok(mut alpn) = self.alpn_value.lock() {
+            *alpn = value;
+        }
+    }
+    fn get_alpn(&self) -> Option<String> {
+        self.alpn_value.lock().ok().and_then(|alpn| alpn.clone())
+    }
+    fn set_certificates(&self, value: Vec<HTTPCertificate>) {
+        if let Ok(mut certificates) = self.certificates_value.lock() {
+            *certificates = value;
+        }
+    }
+    fn get_certificates(&self) -> Vec<HTTPCertificate> {
+        if let Ok(certificates) = self.certificates_value.lock() {
+            return certificates.clone();
+        }
+        vec![]
+    }
     fn is_tls(&self) -> bool {
         self.is_tls_value.load(Ordering::Relaxed)
     }
     fn tls(&self) {
         self.is_tls_value.store(true, Ordering::Relaxed);
     }
-    fn get_conn_from_pool(&self) {
+    fn start(&self) {
         self.start_value.store(self.now(), Ordering::Relaxed)
     }
     fn get_conn(&self) {
         self.get_conn_value.store(self.now(), Ordering::Relaxed)
     }
+    /// No new connection was opened, the request went over a pooled one.
+    fn reused(&self) -> bool {
+        self.get_conn_value.load(Ordering::Relaxed) == 0
+    }
     fn dns_start(&self) {
         self.dns_start_value.store(self.now(), Ordering::Relaxed)
     }
@@ -314,29 +1240,26 @@ impl HTTPTrace {
     fn http_start(&self) {
         self.http_start_value.store(self.now(), Ordering::Relaxed);
     }
-
-    fn got_first_response_byte(&self) {
-        if self.got_first_response_byte_value.load(Ordering::Relaxed) == 0 {
-            self.got_first_response_byte_value
-                .store(self.now(), Ordering::Relaxed);
+    fn connected(&self) {
+        if self.is_tls() {
+            self.tls_done();
+        } else {
+            self.tcp_done();
         }
+        self.http_start();
     }
-    fn written(&self) {
-        if self.written_value.load(Ordering::Relaxed) == 0 {
-            self.written_value.store(self.now(), Ordering::Relaxed);
-        }
+
+    /// The request, or the next redirect hop, was handed to the client.
+    fn sending(&self) {
+        self.sending_value.store(self.now(), Ordering::Relaxed);
+    }
+    fn got_first_response_byte(&self) {
+        self.got_first_response_byte_value
+            .store(self.now(), Ordering::Relaxed);
     }
     fn done(&self) {
         self.done_value.store(self.now(), Ordering::Relaxed);
     }
-    fn send_consuming(&self) -> u32 {
-        let http_start_value = self.http_start_value.load(Ordering::Relaxed);
-        let written_value = self.written_value.load(Ordering::Relaxed);
-        if http_start_value == 0 || written_value == 0 {
-            return 0;
-        }
-        (written_value - http_start_value) as u32
-    }
     fn dns_consuming(&self) -> u32 {
         let dns_start_value = self.dns_start_value.load(Ordering::Relaxed);
         let dns_done_value = self.dns_done_value.load(Ordering::Relaxed);
@@ -345,6 +1268,14 @@ impl HTTPTrace {
         }
         (dns_done_value - dns_start_value) as u32
     }
+    fn connect_consuming(&self) -> u32 {
+        let get_conn_value = self.get_conn_value.load(Ordering::Relaxed);
+        let http_start_value = self.http_start_value.load(Ordering::Relaxed);
+        if get_conn_value == 0 || http_start_value == 0 {
+            return 0;
+        }
+        (http_start_value - get_conn_value) as u32
+    }
     fn tcp_consuming(&self) -> u32 {
         let tcp_start_value = self.tcp_start_value.load(Ordering::Relaxed);
         let tcp_done_value = self.tcp_done_value.load(Ordering::Relaxed);
@@ -363,14 +1294,18 @@ impl HTTPTrace {
     }
 
     fn server_processing_consuming(&self) -> u32 {
-        let written_value = self.written_value.load(Ordering::Relaxed);
+        // a new connection becomes ready after the request was handed over
+        let sending_value = self
+            .sending_value
+            .load(Ordering::Relaxed)
+            .max(self.http_start_value.load(Ordering::Relaxed));
         let got_first_response_byte_value =
             self.got_first_response_byte_value.load(Ordering::Relaxed);
-        if written_value == 0 || got_first_response_byte_value == 0 {
+        if sending_value == 0 || got_first_response_byte_value < sending_value {
             return 0;
         }
 
-        (got_first_response_byte_value - written_value) as u32
+        (got_first_response_byte_value - sending_value) as u32
     }
     fn content_transfer_consuming(&self) -> u32 {
         let got_first_response_byte_value =
@@ -390,92 +1325,733 @@ impl HTTPTrace {
         (done_value - start_value) as u32
     }
 }
-static HTTP_TRACE: OnceCell<HTTPTrace> = OnceCell::new();
-
-fn get_http_trace() -> &'static HTTPTrace {
-    HTTP_TRACE.get_or_init(HTTPTrace::new)
+tokio::task_local! {
+    /// Trace of the request whose future is being polled, see [`request`].
+    static CURRENT_HTTP_TRACE: Arc<HTTPTrace>;
 }
-pub struct HTTPTraceLayer;
-impl<S> Layer<S> for HTTPTraceLayer
-where
-    S: tracing::Subscriber,
-    // Scary! But there's no need to even understand it. We just need it.
-    S: for<'lookup> tracing_subscriber::registry::LookupSpan<'lookup>,
-{
-    fn on_event(&self, event: &tracing::Event<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
-        let trace = get_http_trace();
-        // dbg!(event.metadata().target());
-        let target = event.metadata().target();
-        // if !target.starts_with("hyper") && !trace.is_tls() {
-        //     return;
-        // }
-
-        let mut fields = BTreeMap::new();
-        let mut visitor = JsonVisitor(&mut fields);
-
-        event.record(&mut visitor);
-        let message = fields.get("message");
-        if message.is_none() {
-            return;
-        }
-        let message = message.unwrap();
-        if trace.is_tls() {
-            if message.starts_with("Sending ClientHello Message") {
-                trace.tls_start();
-            } else if message.starts_with("Server cert is") {
-                trace.tls_done();
-            } else if message.starts_with("Using ciphersuite ") {
-                let cipher = message.replace("Using ciphersuite ", "");
-                trace.set_cipher(cipher);
-                // let p = AtomicPtr::new(&mut cipher);
-                // trace.cipher.store(&mut cipher, Ordering::Relaxed);
-            }
-        }
-        if target.contains("pool") {
-            if message.contains("checkout waiting for idle connection") {
-                trace.get_conn_from_pool();
+
+static DNS_RESOLVER: OnceCell<hickory_resolver::TokioResolver> = OnceCell::new();
+
+/// Hickory based resolver that records lookup timings into the current request trace.
+struct TracedResolver;
+
+impl Resolve for TracedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let trace = HTTPTrace::current();
+        Box::pin(async move {
+            let resolver = DNS_RESOLVER.get_or_try_init(|| {
+                let mut builder = hickory_resolver::TokioResolver::builder_tokio()?;
+                builder.options_mut().ip_strategy =
+                    hickory_resolver::config::LookupIpStrategy::Ipv4AndIpv6;
+                Ok::<_, hickory_resolver::ResolveError>(builder.build())
+            })?;
+            if let Some(trace) = &trace {
+                trace.dns_start();
             }
-        } else if target.contains("::connect::http") {
-            if message.starts_with("Http::connect;") {
-                trace.get_conn();
-            } else if message.starts_with("connecting to") {
+            let lookup = resolver.lookup_ip(name.as_str()).await?;
+            if let Some(trace) = &trace {
                 trace.dns_done();
                 trace.tcp_start();
-            } else if message.starts_with("connected to") {
-                trace.tcp_done();
-                trace.http_start();
-            }
-        } else if target.starts_with("hickory_") {
-            if message.starts_with("querying: ") {
-                trace.dns_start();
             }
-        } else if target.contains("::framed_write") {
-            if message.contains("send") {
-                trace.written();
-            }
-        } else if target.contains("::framed_read") {
-            if message.contains("received") {
-                trace.got_first_response_byte();
-            }
-        } else if target.contains("::client") {
-            if message.contains("handshake complete") {
-                trace.written();
+            let addrs: Addrs = Box::new(lookup.into_iter().map(|ip| SocketAddr::new(ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+/// Wraps the client connector to time how long opening a new connection takes and to
+/// note the protocol picked through ALPN. Requests served from a pooled connection never
+/// reach it.
+#[derive(Clone)]
+struct TracedConnectorLayer;
+
+impl<S> tower::Layer<S> for TracedConnectorLayer {
+    type Service = TracedConnector<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TracedConnector(inner)
+    }
+}
+
+#[derive(Clone)]
+struct TracedConnector<S>(S);
+
+impl<S, R> tower::Service<R> for TracedConnector<S>
+where
+    S: tower::Service<R>,
+    S::Response: Connection,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: R) -> Self::Future {
+        let trace = HTTPTrace::current();
+        if let Some(trace) = &trace {
+            trace.get_conn();
+            // moved past the lookup by TracedResolver, IP literals are connected at once
+            trace.tcp_start();
+        }
+        let connecting = self.0.call(req);
+        Box::pin(async move {
+            let trace = match trace {
+                Some(trace) => trace,
+                None => return connecting.await,
+            };
+            // the handshake may be driven off the request task, keep the trace in reach of
+            // TracedSessions and the TracedProvider suites
+            let conn = CURRENT_HTTP_TRACE.scope(trace.clone(), connecting).await;
+            if let Ok(conn) = &conn {
+                trace.connected();
+                let h2 = conn.connected().is_negotiated_h2();
+                trace.set_alpn(h2.then(|| "h2".to_string()));
             }
+            conn
+        })
+    }
+}
+
+/// Session cache of [`HTTPClient`] connections. rustls asks it for a key exchange hint
+/// while writing the ClientHello, right after the socket connected, which is where the
+/// TCP connect ends and the TLS handshake starts.
+#[derive(Debug)]
+struct TracedSessions(ClientSessionMemoryCache);
+
+impl ClientSessionStore for TracedSessions {
+    fn set_kx_hint(&self, server_name: ServerName<'static>, group: NamedGroup) {
+        self.0.set_kx_hint(server_name, group)
+    }
+
+    fn kx_hint(&self, server_name: &ServerName<'_>) -> Option<NamedGroup> {
+        if let Some(trace) = HTTPTrace::current() {
+            trace.tcp_done();
+            trace.tls_start();
         }
+        self.0.kx_hint(server_name)
+    }
+
+    fn set_tls12_session(&self, server_name: ServerName<'static>, value: Tls12ClientSessionValue) {
+        self.0.set_tls12_session(server_name, value)
+    }
+
+    fn tls12_session(&self, server_name: &ServerName<'_>) -> Option<Tls12ClientSessionValue> {
+        self.0.tls12_session(server_name)
+    }
+
+    fn remove_tls12_session(&self, server_name: &ServerName<'static>) {
+        self.0.remove_tls12_session(server_name)
+    }
+
+    fn insert_tls13_ticket(
+        &self,
+        server_name: ServerName<'static>,
+        value: Tls13ClientSessionValue,
+    ) {
+        self.0.insert_tls13_ticket(server_name, value)
+    }
+
+    fn take_tls13_ticket(
+        &self,
+        server_name: &ServerName<'static>,
+    ) -> Option<Tls13ClientSessionValue> {
+        self.0.take_tls13_ticket(server_name)
+    }
+}
+
+/// The ring provider with each cipher suite wrapped to note itself as the cipher of the
+/// current trace when its keys are derived, which only happens for the suite negotiated.
+static TRACED_PROVIDER: Lazy<Arc<CryptoProvider>> = Lazy::new(|| {
+    let mut provider = rustls::crypto::ring::default_provider();
+    provider.cipher_suites = provider
+        .cipher_suites
+        .into_iter()
+        .map(traced_suite)
+        .collect();
+    Arc::new(provider)
+});
+
+fn traced_suite(suite: SupportedCipherSuite) -> SupportedCipherSuite {
+    // suites are referenced for the lifetime of the process, built once by TRACED_PROVIDER
+    fn common(common: &CipherSuiteCommon) -> CipherSuiteCommon {
+        CipherSuiteCommon {
+            suite: common.suite,
+            hash_provider: common.hash_provider,
+            confidentiality_limit: common.confidentiality_limit,
+        }
+    }
+    match suite {
+        SupportedCipherSuite::Tls13(inner) => {
+            let hkdf = TracedHkdf(inner.common.suite, inner.hkdf_provider);
+            SupportedCipherSuite::Tls13(Box::leak(Box::new(Tls13CipherSuite {
+                common: common(&inner.common),
+                hkdf_provider: Box::leak(Box::new(hkdf)),
+                aead_alg: inner.aead_alg,
+                quic: inner.quic,
+            })))
+        }
+        SupportedCipherSuite::Tls12(inner) => {
+            let prf = TracedPrf(inner.common.suite, inner.prf_provider);
+            SupportedCipherSuite::Tls12(Box::leak(Box::new(Tls12CipherSuite {
+                common: common(&inner.common),
+                prf_provider: Box::leak(Box::new(prf)),
+                kx: inner.kx,
+                sign: inner.sign,
+                aead_alg: inner.aead_alg,
+            })))
+        }
+    }
+}
+
+fn note_cipher(suite: CipherSuite) {
+    if let Some(trace) = HTTPTrace::current() {
+        trace.set_cipher(format!("{:?}", suite));
+    }
+}
+
+/// TLS 1.3 key schedule of a suite, see [`TRACED_PROVIDER`].
+struct TracedHkdf(CipherSuite, &'static dyn Hkdf);
+
+impl TracedHkdf {
+    fn inner(&self) -> &'static dyn Hkdf {
+        note_cipher(self.0);
+        self.1
+    }
+}
+
+impl Hkdf for TracedHkdf {
+    fn extract_from_zero_ikm(&self, salt: Option<&[u8]>) -> Box<dyn HkdfExpander> {
+        self.inner().extract_from_zero_ikm(salt)
+    }
+
+    fn extract_from_secret(&self, salt: Option<&[u8]>, secret: &[u8]) -> Box<dyn HkdfExpander> {
+        self.inner().extract_from_secret(salt, secret)
+    }
+
+    fn extract_from_kx_shared_secret(
+        &self,
+        salt: Option<&[u8]>,
+        kx: Box<dyn ActiveKeyExchange>,
+        peer_pub_key: &[u8],
+    ) -> Result<Box<dyn HkdfExpander>, rustls::Error> {
+        self.inner()
+            .extract_from_kx_shared_secret(salt, kx, peer_pub_key)
+    }
+
+    fn expander_for_okm(&self, okm: &OkmBlock) -> Box<dyn HkdfExpander> {
+        self.inner().expander_for_okm(okm)
+    }
+
+    fn hmac_sign(&self, key: &OkmBlock, message: &[u8]) -> hmac::Tag {
+        self.inner().hmac_sign(key, message)
+    }
+
+    fn fips(&self) -> bool {
+        self.1.fips()
+    }
+}
+
+/// TLS 1.2 key derivation of a suite, see [`TRACED_PROVIDER`].
+struct TracedPrf(CipherSuite, &'static dyn Prf);
+
+impl TracedPrf {
+    fn inner(&self) -> &'static dyn Prf {
+        note_cipher(self.0);
+        self.1
+    }
+}
+
+impl Prf for TracedPrf {
+    fn for_key_exchange(
+        &self,
+        output: &mut [u8; 48],
+        kx: Box<dyn ActiveKeyExchange>,
+        peer_pub_key: &[u8],
+        label: &[u8],
+        seed: &[u8],
+    ) -> Result<(), rustls::Error> {
+        self.inner()
+            .for_key_exchange(output, kx, peer_pub_key, label, seed)
+    }
+
+    fn for_secret(&self, output: &mut [u8], secret: &[u8], label: &[u8], seed: &[u8]) {
+        self.inner().for_secret(output, secret, label, seed)
+    }
+
+    fn fips(&self) -> bool {
+        self.1.fips()
     }
 }
 
 pub mod commands {
     use super::*;
     use crate::errors::ApiResult;
-    use tauri::command;
+    use crate::services::{http_assert, http_history};
+    use std::time::Instant;
+    use tauri::{command, AppHandle, Emitter, Manager, State};
 
     #[command(async)]
     pub async fn http_send_request(
+        app: AppHandle,
+        req: HTTPRequest,
+        timeout: Option<RequestTimeout>,
+    ) -> ApiResult<HTTPResponse> {
+        send_recorded(app, req, timeout).await
+    }
+
+    /// Sends `req` on the app's pooled [`HTTPClients`], streaming its body to the UI,
+    /// and adds it to the history resolved but for its secrets, see
+    /// [`HTTPRequest::resolve_keeping_secrets`].
+    pub async fn send_recorded(
+        app: AppHandle,
         req: HTTPRequest,
         timeout: Option<RequestTimeout>,
     ) -> ApiResult<HTTPResponse> {
-        dbg!(req.clone());
-        Ok(request(req, timeout).await?)
+        let mut sent = req;
+        let recorded = sent.resolve_keeping_secrets()?;
+        let client = app
+            .state::<HTTPClients>()
+            .get(&sent, &timeout.clone().unwrap_or_default())?;
+        let on_chunk: BodyListener = Box::new(move |chunk| {
+            if let Err(e) = app.emit(format!("http_body_{}", chunk.id).as_str(), &chunk) {
+                log::error!("Failed to emit body chunk: {}", e);
+            }
+        });
+        let started = Instant::now();
+        let result = request_cancellable(client, sent, timeout, Some(on_chunk))
+            .await
+            .map(|mut response| {
+                response.tests = http_assert::run(&recorded.assertions, &response);
+                if let HTTPBodyType::GraphQL { .. } = recorded.body_type {
+                    response.graphql = http_graphql::split(&response);
+                }
+                response
+            });
+        if let Err(e) = http_history::record(&recorded, &result, started.elapsed()) {
+            log::error!("Failed to record request history: {}", e);
+        }
+        result
+    }
+
+    #[command(async)]
+    pub async fn http_cancel_request(id: String) -> ApiResult<bool> {
+        log::info!("Cancelling request {}", id);
+        Ok(cancel_request(&id).await)
+    }
+
+    /// Closes the kept-alive connections so the next requests open new ones.
+    #[command(async)]
+    pub async fn http_clear_connections(clients: State<'_, HTTPClients>) -> ApiResult<()> {
+        clients.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use http_body_util::Full;
+    use hyper::body::Bytes;
+    use hyper::service::service_fn;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+    use std::convert::Infallible;
+    use std::io::Write;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Self-signed P-256 certificate for `localhost` and `127.0.0.1`, base64 DER.
+    const TLS_CERT: &str = concat!(
+        "MIIBmzCCAUGgAwIBAgIUARzejfrMPporfquSHcLQD55s09UwCgYIKoZIzj0EAwIwFDESMBAGA1UEAwwJ",
+        "bG9jYWxob3N0MCAXDTI2MTAxNzAwMzczOFoYDzIxMjYwOTIzMDAzNzM4WjAUMRIwEAYDVQQDDAlsb2Nh",
+        "bGhvc3QwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAARVatHJwPWl77YeiA17BLSVAZ5haL7GhZWSlszf",
+        "/hC5r3dlSIt2nQP109EpwfKX5xZY72luYve+cUr/yhatH1cgo28wbTAdBgNVHQ4EFgQUxiNy2saHd5eN",
+        "q7y2VoBH03bZepwwHwYDVR0jBBgwFoAUxiNy2saHd5eNq7y2VoBH03bZepwwDwYDVR0TAQH/BAUwAwEB",
+        "/zAaBgNVHREEEzARgglsb2NhbGhvc3SHBH8AAAEwCgYIKoZIzj0EAwIDSAAwRQIgJFDOZGr91nT/yCK7",
+        "tVNlUmVCREHSac1KghblqFyAwY0CIQDtcLsnGctIrviPjZ73BkIOTDGi8RzaTXAma0PzhzVSkg==",
+    );
+    /// PKCS#8 key of [`TLS_CERT`], base64 DER.
+    const TLS_KEY: &str = concat!(
+        "MIGHAgEAMBMGByqGSM49AgEGCCqGSM49AwEHBG0wawIBAQQg0lVM4xv3SLpHSaG7pNEdfRUqqHNhgRmO",
+        "jk3XGdakM7KhRANCAARVatHJwPWl77YeiA17BLSVAZ5haL7GhZWSlszf/hC5r3dlSIt2nQP109EpwfKX",
+        "5xZY72luYve+cUr/yhatH1cg",
+    );
+
+    /// Serves canned responses on a local port, `/slow` answers after 300ms,
+    /// `/stall` stops sending halfway through the body and `/redirect` points to `/fast`.
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = match listener.accept().await {
+                    Ok(conn) => conn,
+                    Err(_) => return,
+                };
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let head = String::from_utf8_lossy(&buf[..n]).to_string();
+                    if head.starts_with("GET /redirect") {
+                        let _ = socket
+                            .write_all(b"HTTP/1.1 302 Found\r\nLocation: /fast\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                            .await;
+                        return;
+                    } else if head.starts_with("GET /slow") {
+                        tokio::time::sleep(Duration::from_millis(300)).await;
+                    } else if head.starts_with("GET /stall") {
+                        let _ = socket
+                            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nok")
+                            .await;
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        return;
+                    }
+                    let _ = socket
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok")
+                        .await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    /// Answers `ok` over TLS, with HTTP/2 when the client offers it through ALPN.
+    async fn serve_tls() -> String {
+        let cert = general_purpose::STANDARD.decode(TLS_CERT).unwrap();
+        let key = general_purpose::STANDARD.decode(TLS_KEY).unwrap();
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from(cert)],
+                PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key)),
+            )
+            .unwrap();
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let tls = match acceptor.accept(socket).await {
+                        Ok(tls) => tls,
+                        Err(_) => return,
+                    };
+                    let h2 = tls.get_ref().1.alpn_protocol() == Some(b"h2");
+                    let io = TokioIo::new(tls);
+                    let service = service_fn(|_| async {
+                        let body = Full::new(Bytes::from_static(b"ok"));
+                        Ok::<_, Infallible>(hyper::Response::new(body))
+                    });
+                    let _ = if h2 {
+                        hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                            .serve_connection(io, service)
+                            .await
+                    } else {
+                        hyper::server::conn::http1::Builder::new()
+                            .serve_connection(io, service)
+                            .await
+                    };
+                });
+            }
+        });
+        format!("https://{}/", addr)
+    }
+
+    fn get(url: String) -> HTTPRequest {
+        HTTPRequest {
+            id: None,
+            method: "GET".to_string(),
+            url,
+            body: String::new(),
+            content_type: String::new(),
+            headers: vec![],
+            query: vec![],
+            stream: false,
+            cookie_jar: None,
+            body_type: HTTPBodyType::Raw,
+            redirect: HTTPRedirectPolicy::Follow,
+            proxy: Some(HTTPProxySettings::Direct),
+            tls: HTTPTlsOptions::default(),
+            auth: None,
+            environment: None,
+            assertions: vec![],
+            fresh_connection: false,
+            http_version: HTTPVersion::Negotiate,
+            compression: HTTPCompression::Auto,
+            skip_body_file: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_keep_their_own_timings() {
+        let base = serve().await;
+        let (slow, fast) = tokio::join!(
+            request(get(format!("{}/slow", base)), None, None),
+            request(get(format!("{}/fast", base)), None, None),
+        );
+        let (slow, fast) = (slow.expect("slow request"), fast.expect("fast request"));
+
+        assert_eq!(slow.body, "ok");
+        assert!(slow.stats.server_processing >= 250, "{:?}", slow.stats);
+        assert!(fast.stats.server_processing < 250, "{:?}", fast.stats);
+        assert!(fast.stats.total < slow.stats.total);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_id_can_be_reused() {
+        let base = serve().await;
+        let slow = |id: &str| {
+            let mut req = get(format!("{}/slow", base));
+            req.id = Some(id.to_string());
+            tokio::spawn(request_cancellable(None, req, None, None))
+        };
+        let first = slow("reused");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(cancel_request("reused").await);
+        let second = slow("reused");
+        assert!(matches!(
+            first.await.unwrap(),
+            Err(APIError::Cancelled(id)) if id == "reused"
+        ));
+        // the first request's cleanup leaves the second one cancellable
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(cancel_request("reused").await);
+        assert!(matches!(second.await.unwrap(), Err(APIError::Cancelled(_))));
+    }
+
+    #[tokio::test]
+    async fn test_idle_body_reports_read_timeout() {
+        let base = serve().await;
+        let timeout = RequestTimeout {
+            read: 1,
+            ..Default::default()
+        };
+        let res = request(get(format!("{}/stall", base)), Some(timeout), None).await;
+        assert!(
+            matches!(res, Err(APIError::Timeout(TimeoutPhase::Read, _))),
+            "{:?}",
+            res.map(|r| r.status)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_url_encoded_body_replaces_content_type() {
+        let body_type = HTTPBodyType::UrlEncoded {
+            params: vec![
+                HTTPRequestKVParam {
+                    key: "q".to_string(),
+                    value: "a b&c".to_string(),
+                    enabled: true,
+                },
+                HTTPRequestKVParam {
+                    key: "off".to_string(),
+                    value: "1".to_string(),
+                    enabled: false,
+                },
+            ],
+        };
+        let builder = Client::new().post("http://localhost/");
+        let req = with_body(builder, body_type, String::new(), "", false)
+            .await
+            .expect("body")
+            .build()
+            .expect("request");
+        assert_eq!(
+            req.headers()[CONTENT_TYPE],
+            "application/x-www-form-urlencoded"
+        );
+        assert_eq!(
+            req.body().and_then(|b| b.as_bytes()),
+            Some(&b"q=a+b%26c"[..])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_json_body_is_validated() {
+        let builder = Client::new().post("http://localhost/");
+        let res = with_body(
+            builder,
+            HTTPBodyType::Json,
+            "{\"a\":".to_string(),
+            "",
+            false,
+        )
+        .await;
+        assert!(matches!(res, Err(APIError::Parser(_))));
+    }
+
+    #[tokio::test]
+    async fn test_redirects_are_recorded() {
+        let base = serve().await;
+        let res = request(get(format!("{}/redirect", base)), None, None)
+            .await
+            .expect("followed request");
+        assert_eq!(res.status, 200);
+        assert_eq!(res.url, format!("{}/fast", base));
+        assert_eq!(res.redirects.len(), 1);
+        assert_eq!(res.redirects[0].status, 302);
+        assert_eq!(res.redirects[0].headers["location"], vec!["/fast"]);
+
+        let mut req = get(format!("{}/redirect", base));
+        req.redirect = HTTPRedirectPolicy::None;
+        let res = request(req, None, None).await.expect("unfollowed request");
+        assert_eq!(res.status, 302);
+        assert!(res.redirects.is_empty());
+
+        let mut req = get(format!("{}/redirect", base));
+        req.redirect = HTTPRedirectPolicy::Limit { max: 0 };
+        assert!(request(req, None, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pooled_connections_are_reused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicU64::new(0));
+        let counter = accepted.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::Relaxed);
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    while let Ok(n) = socket.read(&mut buf).await {
+                        let ok = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+                        if n == 0 || socket.write_all(ok).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        let clients = HTTPClients::default();
+        let timeout = RequestTimeout::default();
+        let url = format!("http://{}/", addr);
+        let mut reused = vec![];
+        for fresh_connection in [false, false, true] {
+            let mut req = get(url.clone());
+            req.fresh_connection = fresh_connection;
+            let client = clients.get(&req, &timeout).unwrap();
+            assert_eq!(client.is_none(), fresh_connection);
+            let res = request_on(client, req, None, None).await.unwrap();
+            reused.push(res.stats.reused);
+            // let the finished connection go back to the pool
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(reused, [false, true, false]);
+        assert_eq!(accepted.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn test_version_and_compression() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (received, mut heads) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(&b"zipped ".repeat(50)).unwrap();
+            let gzipped = encoder.finish().unwrap();
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let head = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let _ = received.send(head.clone());
+                let response = if head.starts_with("get /empty") {
+                    b"HTTP/1.1 204 No Content\r\nContent-Encoding: gzip\r\nConnection: close\r\n\r\n"
+                        .to_vec()
+                } else if head.contains("accept-encoding: gzip") {
+                    let mut response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Encoding: gzip\r\n\
+                        Content-Length: {}\r\nConnection: close\r\n\r\n",
+                        gzipped.len()
+                    )
+                    .into_bytes();
+                    // the answer to HEAD has the headers of a GET but no body
+                    if !head.starts_with("head ") {
+                        response.extend(&gzipped);
+                    }
+                    response
+                } else {
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok".to_vec()
+                };
+                let _ = socket.write_all(&response).await;
+            }
+        });
+
+        let url = format!("http://{}/", addr);
+        let mut req = get(url.clone());
+        req.http_version = HTTPVersion::Http1;
+        let res = request(req, None, None).await.unwrap();
+        assert_eq!(res.version, "HTTP/1.1");
+        assert_eq!(res.compression.as_deref(), Some("gzip"));
+        assert_eq!(res.body, "zipped ".repeat(50));
+        assert_eq!(res.length, 350);
+        assert_eq!(res.headers["content-encoding"], ["gzip"]);
+        assert_eq!(res.stats.alpn, None);
+        assert!(heads
+            .recv()
+            .await
+            .unwrap()
+            .contains("accept-encoding: gzip, br"));
+
+        let mut req = get(url.clone());
+        req.compression = HTTPCompression::None;
+        let res = request(req, None, None).await.unwrap();
+        assert_eq!((res.body.as_str(), res.compression), ("ok", None));
+        assert!(!heads.recv().await.unwrap().contains("accept-encoding"));
+
+        let mut req = get(url.clone());
+        req.compression = HTTPCompression::None;
+        req.headers.push(HTTPRequestKVParam {
+            key: "Accept-Encoding".to_string(),
+            value: "gzip".to_string(),
+            enabled: true,
+        });
+        let res = request(req, None, None).await.unwrap();
+        assert_eq!(res.compression.as_deref(), Some("gzip"));
+        assert!(heads
+            .recv()
+            .await
+            .unwrap()
+            .contains("accept-encoding: gzip\r\n"));
+
+        let mut req = get(url.clone());
+        req.method = "HEAD".to_string();
+        let res = request(req, None, None).await.unwrap();
+        assert_eq!((res.status, res.body.as_str(), res.length), (200, "", 0));
+        assert_eq!(res.headers["content-encoding"], ["gzip"]);
+        assert_eq!(res.compression, None);
+        let res = request(get(format!("{}empty", url)), None, None)
+            .await
+            .unwrap();
+        assert_eq!((res.status, res.length, res.compression), (204, 0, None));
+    }
+
+    #[tokio::test]
+    async fn test_tls_alpn() {
+        let mut req = get(serve_tls().await);
+        req.tls.accept_invalid_certs = true;
+        let res = request(req.clone(), None, None).await.unwrap();
+        assert_eq!(res.body, "ok");
+        assert_eq!(res.version, "HTTP/2");
+        assert_eq!(res.stats.alpn.as_deref(), Some("h2"));
+        assert!(res.stats.is_https);
+        assert!(res.stats.cipher.starts_with("TLS13_"));
+        assert_eq!(res.stats.certificates.len(), 1);
+
+        req.http_version = HTTPVersion::Http1;
+        let res = request(req, None, None).await.unwrap();
+        assert_eq!(res.version, "HTTP/1.1");
+        assert_eq!(res.stats.alpn, None);
     }
 }
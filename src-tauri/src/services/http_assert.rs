@@ -0,0 +1,520 @@
+use crate::services::http::HTTPResponse;
+use regex::Regex;
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use std::cell::OnceCell;
+use std::fmt;
+use std::fs;
+
+/// A check run on the response of a request.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HTTPAssertion {
+    Status {
+        equals: u16,
+    },
+    /// Passes when the header is present and, with `matches`, one of its values matches.
+    Header {
+        name: String,
+        #[serde(default)]
+        matches: Option<String>,
+    },
+    /// Checks the values an RFC 9535 JSONPath selects, strings compare without quotes.
+    /// Passes when the path selects anything if neither `equals` nor `matches` is set.
+    JsonPath {
+        path: String,
+        #[serde(default)]
+        equals: Option<String>,
+        #[serde(default)]
+        matches: Option<String>,
+    },
+    /// Like [`HTTPAssertion::JsonPath`] with an XPath, supporting child and descendant
+    /// steps, `*`, `@attr`, `text()` and `[n]`, `[@attr]`, `[@attr='v']`, `[child='v']`
+    /// predicates.
+    XPath {
+        path: String,
+        #[serde(default)]
+        equals: Option<String>,
+        #[serde(default)]
+        matches: Option<String>,
+    },
+    /// Passes when a regex matches somewhere in the body.
+    Body {
+        matches: String,
+    },
+    /// Passes when [`crate::services::http::HTTPStats::total`] is under `max` ms.
+    Latency {
+        max: u32,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPAssertionResult {
+    pub assertion: HTTPAssertion,
+    pub passed: bool,
+    /// What the response had, shown next to the expectation.
+    pub actual: Option<String>,
+    /// Why the assertion failed, empty when it passed.
+    pub message: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPTestReport {
+    pub passed: usize,
+    pub failed: usize,
+    pub results: Vec<HTTPAssertionResult>,
+}
+
+/// Runs `assertions` on `response`, `None` when there are none.
+pub fn run(assertions: &[HTTPAssertion], response: &HTTPResponse) -> Option<HTTPTestReport> {
+    if assertions.is_empty() {
+        return None;
+    }
+    let text = OnceCell::new();
+    let results: Vec<HTTPAssertionResult> = assertions
+        .iter()
+        .map(|assertion| {
+            let (actual, failure) = match assertion.check(response, &text) {
+                Ok(actual) => (actual, None),
+                Err((actual, message)) => (actual, Some(message)),
+            };
+            HTTPAssertionResult {
+                assertion: assertion.clone(),
+                passed: failure.is_none(),
+                actual,
+                message: failure.unwrap_or_default(),
+            }
+        })
+        .collect();
+    let passed = results.iter().filter(|r| r.passed).count();
+    Some(HTTPTestReport {
+        passed,
+        failed: results.len() - passed,
+        results,
+    })
+}
+
+/// The actual value on success, the actual value and the reason on failure.
+type Outcome = Result<Option<String>, (Option<String>, String)>;
+
+impl HTTPAssertion {
+    fn check(&self, response: &HTTPResponse, text: &OnceCell<String>) -> Outcome {
+        let body = || text.get_or_init(|| body_text(response)).as_str();
+        match self {
+            HTTPAssertion::Status { equals } => {
+                let actual = Some(response.status.to_string());
+                if response.status == *equals {
+                    return Ok(actual);
+                }
+                Err((actual, format!("Expected status {}", equals)))
+            }
+            HTTPAssertion::Header { name, matches } => {
+                let values = response
+                    .headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, values)| values.as_slice())
+                    .unwrap_or_default();
+                if values.is_empty() {
+                    return Err((None, format!("Missing header {}", name)));
+                }
+                compare(values.to_vec(), None, matches.as_deref())
+            }
+            HTTPAssertion::JsonPath {
+                path,
+                equals,
+                matches,
+            } => {
+                let json: Value = serde_json::from_str(body())
+                    .map_err(|e| (None, format!("Body is not JSON: {}", e)))?;
+                let path = JsonPath::parse(path)
+                    .map_err(|e| (None, format!("Invalid JSONPath: {}", e)))?;
+                let values = path.query(&json).all().into_iter().map(json_text).collect();
+                compare(values, equals.as_deref(), matches.as_deref())
+            }
+            HTTPAssertion::XPath {
+                path,
+                equals,
+                matches,
+            } => {
+                let document = Document::parse(body())
+                    .map_err(|e| (None, format!("Body is not XML: {}", e)))?;
+                let values =
+                    xpath(&document, path).map_err(|e| (None, format!("Invalid XPath: {}", e)))?;
+                compare(values, equals.as_deref(), matches.as_deref())
+            }
+            HTTPAssertion::Body { matches } => {
+                let regex = regex(matches)?;
+                if regex.is_match(body()) {
+                    return Ok(None);
+                }
+                Err((None, format!("Body does not match {}", matches)))
+            }
+            HTTPAssertion::Latency { max } => {
+                let actual = Some(format!("{} ms", response.stats.total));
+                if response.stats.total < *max {
+                    return Ok(actual);
+                }
+                Err((actual, format!("Expected a response in under {} ms", max)))
+            }
+        }
+    }
+}
+
+/// The expectation in words, e.g. `header X-Id matches ^a`.
+impl fmt::Display for HTTPAssertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (subject, equals, matches) = match self {
+            HTTPAssertion::Status { equals } => return write!(f, "status {}", equals),
+            HTTPAssertion::Header { name, matches } => (format!("header {}", name), None, matches),
+            HTTPAssertion::JsonPath {
+                path,
+                equals,
+                matches,
+            } => (format!("JSONPath {}", path), equals.as_ref(), matches),
+            HTTPAssertion::XPath {
+                path,
+                equals,
+                matches,
+            } => (format!("XPath {}", path), equals.as_ref(), matches),
+            HTTPAssertion::Body { matches } => return write!(f, "body matches {}", matches),
+            HTTPAssertion::Latency { max } => return write!(f, "response in under {} ms", max),
+        };
+        write!(f, "{}", subject)?;
+        if let Some(equals) = equals {
+            write!(f, " equals {}", equals)?;
+        }
+        if let Some(matches) = matches {
+            write!(f, " matches {}", matches)?;
+        }
+        Ok(())
+    }
+}
+
+/// The body as text, read back from the copy on disk when it was streamed.
+pub fn body_text(response: &HTTPResponse) -> String {
+    if !response.body.is_empty() || response.body_file.is_empty() {
+        return response.body.clone();
+    }
+    match fs::read(&response.body_file) {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+        Err(e) => {
+            log::error!("Failed to read body {}: {}", response.body_file, e);
+            String::new()
+        }
+    }
+}
+
+fn regex(pattern: &str) -> Result<Regex, (Option<String>, String)> {
+    Regex::new(pattern).map_err(|e| (None, format!("Invalid pattern: {}", e)))
+}
+
+/// Passes when one of `values` equals `equals` and matches `matches`, or when there are
+/// values at all if neither is set.
+fn compare(values: Vec<String>, equals: Option<&str>, matches: Option<&str>) -> Outcome {
+    let actual = (!values.is_empty()).then(|| values.join(", "));
+    if values.is_empty() {
+        return Err((actual, "Nothing selected".to_string()));
+    }
+    let regex = matches.map(regex).transpose()?;
+    let passes = |value: &String| {
+        equals.is_none_or(|equals| value == equals)
+            && regex.as_ref().is_none_or(|regex| regex.is_match(value))
+    };
+    if values.iter().any(passes) {
+        return Ok(actual);
+    }
+    let message = match (equals, matches) {
+        (Some(equals), Some(matches)) => format!("Expected {} matching {}", equals, matches),
+        (Some(equals), None) => format!("Expected {}", equals),
+        (None, Some(matches)) => format!("Expected a value matching {}", matches),
+        (None, None) => unreachable!("values are not empty"),
+    };
+    Err((actual, message))
+}
+
+pub fn json_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// Selects the text of elements, attribute values or text nodes `path` points to.
+fn xpath(document: &Document, path: &str) -> Result<Vec<String>, String> {
+    let steps = split_steps(path)?;
+    let mut nodes = vec![document.root()];
+    for (i, (descendant, step)) in steps.iter().enumerate() {
+        let (test, predicates) = split_predicates(step)?;
+        let last = i + 1 == steps.len();
+        if let Some(name) = test.strip_prefix('@') {
+            if !last || !predicates.is_empty() {
+                return Err(format!("Attributes can only be selected last: {}", step));
+            }
+            let elements = nodes.iter().flat_map(|node| scope(*node, *descendant));
+            return Ok(elements
+                .flat_map(|node| node.attributes())
+                .filter(|a| name == "*" || a.name() == name)
+                .map(|a| a.value().to_string())
+                .collect());
+        }
+        let mut selected = Vec::new();
+        for node in &nodes {
+            let groups: Vec<Node> = match (test, *descendant) {
+                (".", _) => {
+                    selected.push(*node);
+                    continue;
+                }
+                ("..", _) => {
+                    selected.extend(node.parent());
+                    continue;
+                }
+                (_, true) => scope(*node, true).collect(),
+                (_, false) => vec![*node],
+            };
+            for parent in groups {
+                let children: Vec<Node> = parent
+                    .children()
+                    .filter(|c| matches_test(c, test))
+                    .collect();
+                selected.extend(filter(children, &predicates)?);
+            }
+        }
+        selected.sort();
+        selected.dedup();
+        nodes = selected;
+        if test == "text()" && !last {
+            return Err(format!("text() can only be selected last: {}", step));
+        }
+    }
+    Ok(nodes.iter().map(node_text).collect())
+}
+
+/// `node` with its element descendants when `descendant`, `node` alone otherwise.
+fn scope<'a, 'input>(
+    node: Node<'a, 'input>,
+    descendant: bool,
+) -> Box<dyn Iterator<Item = Node<'a, 'input>> + 'a> {
+    if descendant {
+        Box::new(node.descendants().filter(|n| n.is_element() || n.is_root()))
+    } else {
+        Box::new(std::iter::once(node))
+    }
+}
+
+fn matches_test(node: &Node, test: &str) -> bool {
+    match test {
+        "text()" => node.is_text(),
+        "*" => node.is_element(),
+        "node()" => node.is_element() || node.is_text(),
+        name => {
+            let local = name.rsplit(':').next().unwrap_or(name);
+            node.is_element() && node.tag_name().name() == local
+        }
+    }
+}
+
+fn filter<'a, 'input>(
+    mut nodes: Vec<Node<'a, 'input>>,
+    predicates: &[&str],
+) -> Result<Vec<Node<'a, 'input>>, String> {
+    for predicate in predicates {
+        let predicate = predicate.trim();
+        if let Ok(position) = predicate.parse::<usize>() {
+            nodes = nodes
+                .into_iter()
+                .skip(position.max(1) - 1)
+                .take(1)
+                .collect();
+            if position == 0 {
+                nodes.clear();
+            }
+            continue;
+        }
+        if predicate == "last()" {
+            nodes = nodes.pop().into_iter().collect();
+            continue;
+        }
+        let (left, right) = match predicate.split_once('=') {
+            Some((left, right)) => (left.trim(), Some(unquote(right.trim())?)),
+            None => (predicate, None),
+        };
+        nodes.retain(|node| {
+            let values: Vec<String> = match left.strip_prefix('@') {
+                Some(name) => node
+                    .attribute(name)
+                    .map(str::to_string)
+                    .into_iter()
+                    .collect(),
+                None if left == "text()" || left == "." => vec![node_text(node)],
+                None => node
+                    .children()
+                    .filter(|c| matches_test(c, left))
+                    .map(|c| node_text(&c))
+                    .collect(),
+            };
+            match &right {
+                Some(right) => values.iter().any(|v| v == right),
+                None => !values.is_empty(),
+            }
+        });
+    }
+    Ok(nodes)
+}
+
+fn unquote(value: &str) -> Result<&str, String> {
+    for quote in ['\'', '"'] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|v| v.strip_suffix(quote))
+        {
+            return Ok(inner);
+        }
+    }
+    value
+        .parse::<f64>()
+        .map(|_| value)
+        .map_err(|_| format!("Expected a quoted string or number: {}", value))
+}
+
+fn node_text(node: &Node) -> String {
+    if node.is_text() {
+        return node.text().unwrap_or_default().to_string();
+    }
+    node.descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect()
+}
+
+/// Splits `path` on the `/` outside predicates, flagging steps after `//`.
+fn split_steps(path: &str) -> Result<Vec<(bool, &str)>, String> {
+    let mut steps = Vec::new();
+    let (mut depth, mut quote, mut start, mut descendant) = (0, None, 0, false);
+    let path = path.trim();
+    for (i, c) in path.char_indices() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {}
+            ('[', None) => depth += 1,
+            (']', None) => depth -= 1,
+            ('/', None) if depth == 0 => {
+                if i > start {
+                    steps.push((descendant, &path[start..i]));
+                    descendant = false;
+                } else if i > 0 {
+                    descendant = true;
+                }
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if depth != 0 || quote.is_some() {
+        return Err(format!("Unbalanced brackets or quotes in {}", path));
+    }
+    if start == path.len() {
+        return Err(format!("Missing step at the end of {}", path));
+    }
+    steps.push((descendant, &path[start..]));
+    Ok(steps)
+}
+
+/// Splits `step` into its node test and the contents of its `[...]` predicates.
+fn split_predicates(step: &str) -> Result<(&str, Vec<&str>), String> {
+    let test_end = step.find('[').unwrap_or(step.len());
+    let mut predicates = Vec::new();
+    let (mut depth, mut quote, mut start) = (0, None, test_end);
+    for (i, c) in step[test_end..]
+        .char_indices()
+        .map(|(i, c)| (i + test_end, c))
+    {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => {}
+            ('[', None) => {
+                if depth == 0 {
+                    start = i + 1;
+                }
+                depth += 1;
+            }
+            (']', None) => {
+                depth -= 1;
+                if depth == 0 {
+                    predicates.push(&step[start..i]);
+                }
+            }
+            (_, None) if depth == 0 => return Err(format!("Unexpected {} in {}", c, step)),
+            _ => {}
+        }
+    }
+    Ok((step[..test_end].trim(), predicates))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_assertions() {
+        let mut response =
+            HTTPResponse::json(r#"{"items":[{"id":7,"name":"pen"},{"id":8,"name":"ink"}]}"#);
+        response.stats.total = 120;
+        let assertions = vec![
+            HTTPAssertion::Status { equals: 200 },
+            HTTPAssertion::Header {
+                name: "Content-Type".to_string(),
+                matches: Some("json".to_string()),
+            },
+            HTTPAssertion::JsonPath {
+                path: "$.items[*].name".to_string(),
+                equals: Some("ink".to_string()),
+                matches: None,
+            },
+            HTTPAssertion::JsonPath {
+                path: "$.items[0].id".to_string(),
+                equals: Some("8".to_string()),
+                matches: None,
+            },
+            HTTPAssertion::Body {
+                matches: r#""id":\d+"#.to_string(),
+            },
+            HTTPAssertion::Latency { max: 100 },
+        ];
+        let report = run(&assertions, &response).expect("report");
+        let passed: Vec<bool> = report.results.iter().map(|r| r.passed).collect();
+        assert_eq!(passed, vec![true, true, true, false, true, false]);
+        assert_eq!((report.passed, report.failed), (4, 2));
+        assert_eq!(report.results[3].actual.as_deref(), Some("7"));
+        assert_eq!(report.results[5].actual.as_deref(), Some("120 ms"));
+        assert_eq!(
+            report.results[3].assertion.to_string(),
+            "JSONPath $.items[0].id equals 8"
+        );
+        assert!(run(&[], &response).is_none());
+    }
+
+    #[test]
+    fn test_xpath() {
+        let document = Document::parse(
+            r#"<feed><entry id="1"><title>First</title></entry>
+               <entry id="2"><title>Second</title><tag>a</tag></entry></feed>"#,
+        )
+        .unwrap();
+        let select = |path| xpath(&document, path).unwrap();
+        assert_eq!(select("/feed/entry/title"), vec!["First", "Second"]);
+        assert_eq!(select("//entry[2]/@id"), vec!["2"]);
+        assert_eq!(select("//entry[@id='1']/title/text()"), vec!["First"]);
+        assert_eq!(select("//entry[tag]/title"), vec!["Second"]);
+        assert_eq!(select("//title[last()]"), vec!["First", "Second"]);
+        assert_eq!(select("/feed/*[title='Second']/@*"), vec!["2"]);
+        assert!(xpath(&document, "/feed/@id/title").is_err());
+        assert!(xpath(&document, "//entry[@id='1'").is_err());
+    }
+}
@@ -0,0 +1,562 @@
+use crate::errors::{APIError, ApiResult, AwsError};
+use aws_config::SdkConfig;
+use aws_credential_types::provider::ProvideCredentials;
+use aws_sigv4::http_request::{
+    sign, SignableBody, SignableRequest, SigningParams, SigningSettings,
+};
+use aws_sigv4::sign::v4;
+use base64::{engine::general_purpose, Engine as _};
+use md5::Md5;
+use once_cell::sync::Lazy;
+use reqwest::header::{HeaderName, HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use reqwest::{Client, Request, Response};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::Mutex;
+
+/// Tokens are renewed this long before the server says they expire.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// OAuth2 tokens keyed by token url, client id and scope. Each has its own lock, held
+/// while the token is requested, so a slow token endpoint only holds up its own requests.
+static OAUTH2_TOKENS: Lazy<Mutex<HashMap<String, Arc<Mutex<Option<OAuth2Token>>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// AWS configs by profile, their credentials providers cache the credentials they load.
+static AWS_CONFIGS: Lazy<Mutex<HashMap<String, SdkConfig>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HTTPAuth {
+    Basic {
+        username: String,
+        password: String,
+    },
+    Bearer {
+        token: String,
+    },
+    /// Answers the `WWW-Authenticate: Digest` challenge of a first unauthenticated attempt.
+    Digest {
+        username: String,
+        password: String,
+    },
+    /// Signs with the credentials of an AWS profile, `region` defaults to the profile's.
+    AwsSigV4 {
+        profile: String,
+        #[serde(default)]
+        region: Option<String>,
+        service: String,
+    },
+    /// Client credentials grant, the token is cached until it expires.
+    OAuth2 {
+        token_url: String,
+        client_id: String,
+        client_secret: String,
+        #[serde(default)]
+        scope: String,
+    },
+}
+
+impl HTTPAuth {
+    /// Adds the credentials known before sending, must be the last change made to `request`.
+    /// OAuth2 tokens are requested on `client`, with the proxy and TLS options of the request.
+    pub async fn authorize(&self, client: &Client, request: &mut Request) -> ApiResult<()> {
+        match self {
+            HTTPAuth::Basic { username, password } => {
+                let credentials =
+                    general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+                set_authorization(request, &format!("Basic {}", credentials))
+            }
+            HTTPAuth::Bearer { token } => set_authorization(request, &format!("Bearer {}", token)),
+            HTTPAuth::Digest { .. } => Ok(()),
+            HTTPAuth::AwsSigV4 {
+                profile,
+                region,
+                service,
+            } => sign_v4(request, profile, region.as_deref(), service).await,
+            HTTPAuth::OAuth2 { .. } => {
+                let token = self.oauth2_token(client, false).await?;
+                set_authorization(request, &format!("Bearer {}", token))
+            }
+        }
+    }
+
+    /// Answers the 401 `challenged` got, returning `request` ready to be sent again,
+    /// or `None` when sending it again would not help.
+    pub async fn answer(
+        &self,
+        client: &Client,
+        challenged: &Response,
+        mut request: Request,
+    ) -> ApiResult<Option<Request>> {
+        match self {
+            HTTPAuth::Digest { username, password } => {
+                let challenge = challenged
+                    .headers()
+                    .get_all(WWW_AUTHENTICATE)
+                    .iter()
+                    .filter_map(|v| v.to_str().ok())
+                    .find_map(DigestChallenge::parse);
+                let challenge = match challenge {
+                    Some(challenge) => challenge,
+                    None => return Ok(None),
+                };
+                let cnonce = uuid::Uuid::new_v4().simple().to_string();
+                let authorization =
+                    challenge.authorization(&request, username, password, &cnonce)?;
+                set_authorization(&mut request, &authorization)?;
+                Ok(Some(request))
+            }
+            HTTPAuth::OAuth2 { .. } => {
+                // the token may have been revoked before it expired
+                let token = self.oauth2_token(client, true).await?;
+                set_authorization(&mut request, &format!("Bearer {}", token))?;
+                Ok(Some(request))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The fields that may hold `{{name}}` placeholders.
+    pub fn values_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            HTTPAuth::Basic { username, password } | HTTPAuth::Digest { username, password } => {
+                vec![username, password]
+            }
+            HTTPAuth::Bearer { token } => vec![token],
+            HTTPAuth::AwsSigV4 {
+                profile,
+                region,
+                service,
+            } => {
+                let mut values = vec![profile, service];
+                values.extend(region.as_mut());
+                values
+            }
+            HTTPAuth::OAuth2 {
+                token_url,
+                client_id,
+                client_secret,
+                scope,
+            } => vec![token_url, client_id, client_secret, scope],
+        }
+    }
+
+    async fn oauth2_token(&self, client: &Client, renew: bool) -> ApiResult<String> {
+        let (token_url, client_id, scope) = match self {
+            HTTPAuth::OAuth2 {
+                token_url,
+                client_id,
+                scope,
+                ..
+            } => (token_url, client_id, scope),
+            _ => return Err(APIError::Auth("not an OAuth2 auth".to_string())),
+        };
+        let key = format!("{}|{}|{}", token_url, client_id, scope);
+        let slot = OAUTH2_TOKENS.lock().await.entry(key).or_default().clone();
+        let mut cached = slot.lock().await;
+        if let Some(token) = cached.as_ref() {
+            if !renew && !token.is_expiring() {
+                return Ok(token.access_token.clone());
+            }
+        }
+
+        let refresh_token = cached.take().and_then(|token| token.refresh_token);
+        let token = match refresh_token {
+            Some(refresh_token) => match self.request_token(client, Some(&refresh_token)).await {
+                Ok(token) => token,
+                Err(e) => {
+                    // refresh tokens expire or get revoked, the client can still log in
+                    log::warn!("OAuth2 token refresh failed, requesting a new token: {}", e);
+                    self.request_token(client, None).await?
+                }
+            },
+            None => self.request_token(client, None).await?,
+        };
+        let access_token = token.access_token.clone();
+        *cached = Some(token);
+        Ok(access_token)
+    }
+
+    /// Requests a token with the refresh token grant when given one, the client
+    /// credentials grant otherwise.
+    async fn request_token(
+        &self,
+        client: &Client,
+        refresh_token: Option<&str>,
+    ) -> ApiResult<OAuth2Token> {
+        let (token_url, client_id, client_secret, scope) = match self {
+            HTTPAuth::OAuth2 {
+                token_url,
+                client_id,
+                client_secret,
+                scope,
+            } => (token_url, client_id, client_secret, scope),
+            _ => return Err(APIError::Auth("not an OAuth2 auth".to_string())),
+        };
+        let mut params = match refresh_token {
+            Some(refresh_token) => vec![
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ],
+            None => vec![("grant_type", "client_credentials")],
+        };
+        if !scope.is_empty() {
+            params.push(("scope", scope));
+        }
+        log::info!("Requesting OAuth2 token from {}", token_url);
+        let res = client
+            .post(token_url)
+            .basic_auth(client_id, Some(client_secret))
+            .form(&params)
+            .send()
+            .await?;
+        let status = res.status();
+        let body = res.text().await?;
+        if !status.is_success() {
+            return Err(APIError::Auth(format!(
+                "token request failed with {}: {}",
+                status, body
+            )));
+        }
+        let token: OAuth2TokenResponse = serde_json::from_str(&body)?;
+        Ok(OAuth2Token::from(token))
+    }
+}
+
+fn set_authorization(request: &mut Request, value: &str) -> ApiResult<()> {
+    let mut value = HeaderValue::from_str(value)?;
+    value.set_sensitive(true);
+    request.headers_mut().insert(AUTHORIZATION, value);
+    Ok(())
+}
+
+async fn sign_v4(
+    request: &mut Request,
+    profile: &str,
+    region: Option<&str>,
+    service: &str,
+) -> ApiResult<()> {
+    let config = {
+        let mut configs = AWS_CONFIGS.lock().await;
+        match configs.get(profile) {
+            Some(config) => config.clone(),
+            None => {
+                let config = aws_config::from_env().profile_name(profile).load().await;
+                configs.insert(profile.to_string(), config.clone());
+                config
+            }
+        }
+    };
+    let provider = config
+        .credentials_provider()
+        .ok_or_else(|| AwsError::Config(format!("no credentials for profile {}", profile)))?;
+    let credentials = provider
+        .provide_credentials()
+        .await
+        .map_err(|e| AwsError::AwsProfile(profile.to_string(), e.to_string()))?;
+    let region = match region.filter(|r| !r.is_empty()) {
+        Some(region) => region.to_string(),
+        None => config
+            .region()
+            .map(|r| r.to_string())
+            .ok_or_else(|| AwsError::Config(format!("no region for profile {}", profile)))?,
+    };
+
+    let identity = credentials.into();
+    let params: SigningParams = v4::SigningParams::builder()
+        .identity(&identity)
+        .region(&region)
+        .name(service)
+        .time(SystemTime::now())
+        .settings(SigningSettings::default())
+        .build()
+        .map_err(|e| APIError::Auth(e.to_string()))?
+        .into();
+    let headers = request
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+        .collect::<Vec<_>>();
+    let body = match request.body() {
+        Some(body) => match body.as_bytes() {
+            Some(bytes) => SignableBody::Bytes(bytes),
+            // streamed multipart bodies can't be hashed up front
+            None => SignableBody::UnsignedPayload,
+        },
+        None => SignableBody::Bytes(&[]),
+    };
+    let signable = SignableRequest::new(
+        request.method().as_str(),
+        request.url().as_str(),
+        headers.into_iter(),
+        body,
+    )
+    .map_err(|e| APIError::Auth(e.to_string()))?;
+    let (instructions, _) = sign(signable, &params)
+        .map_err(|e| APIError::Auth(e.to_string()))?
+        .into_parts();
+
+    let (headers, _) = instructions.into_parts();
+    for header in headers {
+        let mut value = HeaderValue::from_str(header.value())?;
+        value.set_sensitive(header.sensitive());
+        request
+            .headers_mut()
+            .insert(HeaderName::from_static(header.name()), value);
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+struct OAuth2TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct OAuth2Token {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_at: Option<Instant>,
+}
+
+impl From<OAuth2TokenResponse> for OAuth2Token {
+    fn from(res: OAuth2TokenResponse) -> Self {
+        OAuth2Token {
+            access_token: res.access_token,
+            refresh_token: res.refresh_token,
+            expires_at: res
+                .expires_in
+                .map(|secs| Instant::now() + Duration::from_secs(secs)),
+        }
+    }
+}
+
+impl OAuth2Token {
+    fn is_expiring(&self) -> bool {
+        self.expires_at
+            .is_some_and(|at| at <= Instant::now() + TOKEN_EXPIRY_MARGIN)
+    }
+}
+
+/// A `WWW-Authenticate: Digest` challenge, see RFC 7616.
+#[derive(Debug)]
+struct DigestChallenge {
+    realm: String,
+    nonce: String,
+    opaque: Option<String>,
+    algorithm: String,
+    /// Only `auth` is supported, `auth-int` would need the body hashed.
+    qop: Option<String>,
+}
+
+impl DigestChallenge {
+    fn parse(header: &str) -> Option<DigestChallenge> {
+        let (scheme, params) = header.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return None;
+        }
+        let params = parse_auth_params(params);
+        let qop = params.get("qop").map(|qop| {
+            qop.split(',')
+                .map(str::trim)
+                .find(|q| q.eq_ignore_ascii_case("auth"))
+                .map(str::to_string)
+        });
+        Some(DigestChallenge {
+            realm: params.get("realm").cloned().unwrap_or_default(),
+            nonce: params.get("nonce")?.clone(),
+            opaque: params.get("opaque").cloned(),
+            algorithm: params
+                .get("algorithm")
+                .cloned()
+                .unwrap_or_else(|| "MD5".to_string()),
+            qop: match qop {
+                // the server only offers auth-int
+                Some(None) => return None,
+                Some(qop) => qop,
+                None => None,
+            },
+        })
+    }
+
+    fn authorization(
+        &self,
+        request: &Request,
+        username: &str,
+        password: &str,
+        cnonce: &str,
+    ) -> ApiResult<String> {
+        let (algorithm, session) = match self.algorithm.strip_suffix("-sess") {
+            Some(algorithm) => (algorithm, true),
+            None => (self.algorithm.as_str(), false),
+        };
+        let hash: fn(String) -> String = match algorithm.to_uppercase().as_str() {
+            "MD5" => |data| format!("{:x}", Md5::digest(data)),
+            "SHA-256" => |data| format!("{:x}", Sha256::digest(data)),
+            _ => {
+                return Err(APIError::Auth(format!(
+                    "unsupported digest algorithm {}",
+                    self.algorithm
+                )))
+            }
+        };
+        let uri = match request.url().query() {
+            Some(query) => format!("{}?{}", request.url().path(), query),
+            None => request.url().path().to_string(),
+        };
+        let nc = "00000001";
+
+        let mut ha1 = hash(format!("{}:{}:{}", username, self.realm, password));
+        if session {
+            ha1 = hash(format!("{}:{}:{}", ha1, self.nonce, cnonce));
+        }
+        let ha2 = hash(format!("{}:{}", request.method(), uri));
+        let response = match &self.qop {
+            Some(qop) => hash(format!(
+                "{}:{}:{}:{}:{}:{}",
+                ha1, self.nonce, nc, cnonce, qop, ha2
+            )),
+            None => hash(format!("{}:{}:{}", ha1, self.nonce, ha2)),
+        };
+
+        let mut authorization = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+            username, self.realm, self.nonce, uri, self.algorithm, response
+        );
+        if let Some(qop) = &self.qop {
+            authorization.push_str(&format!(", qop={}, nc={}, cnonce=\"{}\"", qop, nc, cnonce));
+        }
+        if let Some(opaque) = &self.opaque {
+            authorization.push_str(&format!(", opaque=\"{}\"", opaque));
+        }
+        Ok(authorization)
+    }
+}
+
+/// Parses `key=value, key="quoted, value"` pairs, keys lowercased.
+fn parse_auth_params(params: &str) -> HashMap<String, String> {
+    let mut parsed = HashMap::new();
+    let mut rest = params.trim();
+    while let Some((key, after)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_lowercase();
+        let after = after.trim_start();
+        let (value, next) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+            }
+            None => match after.find(',') {
+                Some(end) => (&after[..end], &after[end..]),
+                None => (after, ""),
+            },
+        };
+        parsed.insert(key, value.trim().to_string());
+        rest = next.trim_start().trim_start_matches(',');
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::{BodyExt, Full};
+    use hyper::body::{Bytes, Incoming};
+    use hyper::service::service_fn;
+    use hyper_util::rt::TokioIo;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::TcpListener;
+
+    /// Token endpoint issuing `token-<n>` that expire at once, along with a refresh token
+    /// it then rejects.
+    async fn serve_tokens() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().unwrap();
+        let issued = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                let issued = issued.clone();
+                let service = service_fn(move |req: hyper::Request<Incoming>| {
+                    let issued = issued.clone();
+                    async move {
+                        let form = req.into_body().collect().await?.to_bytes();
+                        let res = if form.starts_with(b"grant_type=refresh_token") {
+                            hyper::Response::builder()
+                                .status(400)
+                                .body(Full::new(Bytes::from_static(b"invalid_grant")))
+                        } else {
+                            let n = issued.fetch_add(1, Ordering::SeqCst) + 1;
+                            let body = format!(
+                                r#"{{"access_token":"token-{}","refresh_token":"r","expires_in":0}}"#,
+                                n
+                            );
+                            hyper::Response::builder().body(Full::new(Bytes::from(body)))
+                        };
+                        Ok::<_, hyper::Error>(res.unwrap())
+                    }
+                });
+                tokio::spawn(async move {
+                    let _ = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(socket), service)
+                        .await;
+                });
+            }
+        });
+        format!("http://{}/token", addr)
+    }
+
+    #[tokio::test]
+    async fn test_oauth2_rejected_refresh_falls_back_to_client_credentials() {
+        let auth = HTTPAuth::OAuth2 {
+            token_url: serve_tokens().await,
+            client_id: "id".to_string(),
+            client_secret: "secret".to_string(),
+            scope: String::new(),
+        };
+        let client = Client::new();
+        assert_eq!(auth.oauth2_token(&client, false).await.unwrap(), "token-1");
+        assert_eq!(auth.oauth2_token(&client, false).await.unwrap(), "token-2");
+    }
+
+    #[test]
+    fn test_digest_authorization() {
+        // example from RFC 2617 section 3.5
+        let challenge = DigestChallenge::parse(
+            "Digest realm=\"testrealm@host.com\", qop=\"auth,auth-int\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"",
+        )
+        .expect("challenge");
+        assert_eq!(challenge.realm, "testrealm@host.com");
+        assert_eq!(challenge.qop.as_deref(), Some("auth"));
+
+        let request = Request::new(
+            reqwest::Method::GET,
+            "http://www.nowhere.org/dir/index.html".parse().unwrap(),
+        );
+        let authorization = challenge
+            .authorization(&request, "Mufasa", "Circle Of Life", "0a4f113b")
+            .expect("authorization");
+        assert_eq!(
+            authorization,
+            "Digest username=\"Mufasa\", realm=\"testrealm@host.com\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", uri=\"/dir/index.html\", \
+             algorithm=MD5, response=\"6629fae49393a05397450978507c4ef1\", qop=auth, \
+             nc=00000001, cnonce=\"0a4f113b\", opaque=\"5ccc069c403ebaf9f0171e9517f40e41\""
+        );
+    }
+
+    #[test]
+    fn test_digest_challenge_requires_nonce() {
+        assert!(DigestChallenge::parse("Digest realm=\"r\"").is_none());
+        assert!(DigestChallenge::parse("Basic realm=\"r\"").is_none());
+        assert!(DigestChallenge::parse("Digest nonce=\"n\", qop=\"auth-int\"").is_none());
+    }
+}
@@ -0,0 +1,388 @@
+use crate::errors::ApiResult;
+use crate::store;
+use crate::utils::expand_tilde;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+
+const BODY_DIR: &str = "msms-dev-tools-http";
+/// Bodies kept on disk, older ones are deleted as new responses arrive.
+const KEPT_BODIES: usize = 20;
+/// Bytes of the body looked at to tell its kind.
+const SNIFF_LEN: usize = 512;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum HTTPBodyKind {
+    Text,
+    Json,
+    Xml,
+    Image,
+    Binary,
+}
+
+impl HTTPBodyKind {
+    /// Tells the kind from the magic bytes at the start of `head`, then from `content_type`,
+    /// then from what the content looks like when the server did not say.
+    pub fn detect(content_type: &str, head: &[u8]) -> Self {
+        const IMAGES: [&[u8]; 5] = [
+            b"\x89PNG\r\n\x1a\n",
+            b"\xff\xd8\xff",
+            b"GIF87a",
+            b"GIF89a",
+            b"\x00\x00\x01\x00",
+        ];
+        const BINARIES: [&[u8]; 5] = [
+            b"%PDF-",
+            b"PK\x03\x04",
+            b"\x1f\x8b",
+            b"\x7fELF",
+            b"7z\xbc\xaf",
+        ];
+        if IMAGES.iter().any(|magic| head.starts_with(magic))
+            || (head.starts_with(b"RIFF") && head.get(8..12) == Some(&b"WEBP"[..]))
+        {
+            return HTTPBodyKind::Image;
+        }
+        if BINARIES.iter().any(|magic| head.starts_with(magic)) {
+            return HTTPBodyKind::Binary;
+        }
+
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        if mime.starts_with("image/") {
+            return HTTPBodyKind::Image;
+        }
+        if mime.ends_with("/json") || mime.ends_with("+json") {
+            return HTTPBodyKind::Json;
+        }
+        if mime.ends_with("/xml") || mime.ends_with("+xml") {
+            return HTTPBodyKind::Xml;
+        }
+        if mime.starts_with("text/")
+            || mime.ends_with("/javascript")
+            || mime == "application/x-www-form-urlencoded"
+        {
+            return HTTPBodyKind::Text;
+        }
+        if !mime.is_empty() && mime != "application/octet-stream" {
+            return HTTPBodyKind::Binary;
+        }
+
+        // a multi-byte character may be cut at the end of the sniffed bytes
+        let text = match std::str::from_utf8(head) {
+            Ok(text) => text,
+            Err(e) if e.error_len().is_none() => {
+                std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default()
+            }
+            Err(_) => return HTTPBodyKind::Binary,
+        };
+        if text.contains('\0') {
+            return HTTPBodyKind::Binary;
+        }
+        match text.trim_start().chars().next() {
+            Some('{') | Some('[') => HTTPBodyKind::Json,
+            Some('<') => HTTPBodyKind::Xml,
+            _ => HTTPBodyKind::Text,
+        }
+    }
+
+    pub fn is_text(&self) -> bool {
+        !matches!(self, HTTPBodyKind::Image | HTTPBodyKind::Binary)
+    }
+}
+
+/// Decodes a textual body with the charset declared in `content_type`, like reqwest's
+/// `Response::text` does. Falls back to UTF-8 when none or an unknown one is declared.
+pub fn decode_text(content_type: &str, body: &[u8]) -> String {
+    let encoding = content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("charset"))
+        .and_then(|(_, charset)| {
+            encoding_rs::Encoding::for_label(charset.trim().trim_matches('"').as_bytes())
+        })
+        .unwrap_or(encoding_rs::UTF_8);
+    let (text, _, _) = encoding.decode(body);
+    text.into_owned()
+}
+
+/// Copy of a response body kept in the temp dir so it can be saved later without re-requesting.
+/// Only the last [`KEPT_BODIES`] are kept, see also [`discard_body`].
+pub struct BodyWriter {
+    file: Option<(PathBuf, File)>,
+    head: Vec<u8>,
+}
+
+impl BodyWriter {
+    pub async fn create(id: &str) -> ApiResult<BodyWriter> {
+        let path = body_path(id).await?;
+        if let Err(e) = prune(&path).await {
+            log::warn!("Failed to delete old response bodies: {}", e);
+        }
+        Ok(BodyWriter {
+            file: Some((path.clone(), File::create(&path).await?)),
+            head: Vec::new(),
+        })
+    }
+
+    /// A writer that keeps nothing on disk, only the [`BodyWriter::head`].
+    pub fn discarding() -> BodyWriter {
+        BodyWriter {
+            file: None,
+            head: Vec::new(),
+        }
+    }
+
+    pub async fn write(&mut self, chunk: &[u8]) -> ApiResult<()> {
+        if self.head.len() < SNIFF_LEN {
+            let n = chunk.len().min(SNIFF_LEN - self.head.len());
+            self.head.extend_from_slice(&chunk[..n]);
+        }
+        if let Some((_, file)) = &mut self.file {
+            file.write_all(chunk).await?;
+        }
+        Ok(())
+    }
+
+    /// The first bytes written, enough for [`HTTPBodyKind::detect`].
+    pub fn head(&self) -> &[u8] {
+        &self.head
+    }
+
+    /// The path of the copy, None for a [`BodyWriter::discarding`] one.
+    pub async fn finish(self) -> ApiResult<Option<PathBuf>> {
+        match self.file {
+            Some((path, mut file)) => {
+                file.flush().await?;
+                Ok(Some(path))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Decodes a compressed response body chunk by chunk as it downloads.
+pub enum BodyDecoder {
+    Gzip(flate2::write::GzDecoder<Vec<u8>>),
+    Deflate(flate2::write::ZlibDecoder<Vec<u8>>),
+    Brotli(Box<brotli_decompressor::DecompressorWriter<Vec<u8>>>),
+}
+
+impl BodyDecoder {
+    /// The decoder for a `Content-Encoding`, None for identity and for codings it does
+    /// not know, including several stacked ones.
+    pub fn new(content_encoding: &str) -> Option<BodyDecoder> {
+        match content_encoding.trim().to_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(BodyDecoder::Gzip(flate2::write::GzDecoder::new(vec![]))),
+            "deflate" => Some(BodyDecoder::Deflate(flate2::write::ZlibDecoder::new(
+                vec![],
+            ))),
+            "br" => Some(BodyDecoder::Brotli(Box::new(
+                brotli_decompressor::DecompressorWriter::new(vec![], 4096),
+            ))),
+            _ => None,
+        }
+    }
+
+    /// Feeds the next compressed `chunk`, returns what could be decoded so far.
+    pub fn decode(&mut self, chunk: &[u8]) -> ApiResult<Vec<u8>> {
+        let decoded = match self {
+            BodyDecoder::Gzip(decoder) => {
+                decoder.write_all(chunk)?;
+                decoder.get_mut()
+            }
+            BodyDecoder::Deflate(decoder) => {
+                decoder.write_all(chunk)?;
+                decoder.get_mut()
+            }
+            BodyDecoder::Brotli(decoder) => {
+                decoder.write_all(chunk)?;
+                decoder.get_mut()
+            }
+        };
+        Ok(std::mem::take(decoded))
+    }
+
+    /// The rest of the body, fails when a gzip or brotli stream was cut short.
+    pub fn finish(self) -> ApiResult<Vec<u8>> {
+        Ok(match self {
+            BodyDecoder::Gzip(decoder) => decoder.finish()?,
+            BodyDecoder::Deflate(decoder) => decoder.finish()?,
+            BodyDecoder::Brotli(mut decoder) => {
+                decoder.close()?;
+                std::mem::take(decoder.get_mut())
+            }
+        })
+    }
+}
+
+async fn body_path(id: &str) -> ApiResult<PathBuf> {
+    let dir = std::env::temp_dir().join(BODY_DIR);
+    fs::create_dir_all(&dir).await?;
+    Ok(dir.join(store::file_name(id, "body")?))
+}
+
+/// Deletes the oldest body files so at most [`KEPT_BODIES`] remain with `path` added.
+/// Files another request already deleted are skipped.
+async fn prune(path: &Path) -> ApiResult<()> {
+    let dir = path.parent().unwrap_or(path);
+    let mut bodies = Vec::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file = entry.path();
+        if file == path || !file.extension().is_some_and(|ext| ext == "body") {
+            continue;
+        }
+        match entry.metadata().await.and_then(|meta| meta.modified()) {
+            Ok(modified) => bodies.push((modified, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    bodies.sort();
+    let excess = (bodies.len() + 1).saturating_sub(KEPT_BODIES);
+    for (_, file) in bodies.into_iter().take(excess) {
+        match fs::remove_file(file).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Deletes the copy of the body of response `id`, once the response is no longer shown.
+pub async fn discard_body(id: &str) -> ApiResult<()> {
+    match fs::remove_file(body_path(id).await?).await {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Copies the body of response `id` to `path`, returns the number of bytes written.
+pub async fn save_body(id: &str, path: &str) -> ApiResult<u64> {
+    Ok(fs::copy(body_path(id).await?, expand_tilde(path)).await?)
+}
+
+pub mod commands {
+    use super::*;
+    use tauri::command;
+
+    #[command(async)]
+    pub async fn http_save_response_body(id: String, path: String) -> ApiResult<u64> {
+        log::info!("Saving body of response {} to {}", id, path);
+        save_body(&id, &path).await
+    }
+
+    #[command(async)]
+    pub async fn http_discard_response_body(id: String) -> ApiResult<()> {
+        discard_body(&id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    #[test]
+    fn test_detect_body_kind() {
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+        assert_eq!(
+            HTTPBodyKind::detect("application/octet-stream", png),
+            HTTPBodyKind::Image
+        );
+        assert_eq!(
+            HTTPBodyKind::detect("text/plain", b"%PDF-1.7"),
+            HTTPBodyKind::Binary
+        );
+        assert_eq!(
+            HTTPBodyKind::detect("application/problem+json; charset=utf-8", b"{}"),
+            HTTPBodyKind::Json
+        );
+        assert_eq!(
+            HTTPBodyKind::detect("application/soap+xml", b"<a/>"),
+            HTTPBodyKind::Xml
+        );
+        assert_eq!(
+            HTTPBodyKind::detect("application/x-protobuf", b"\x08\x96\x01"),
+            HTTPBodyKind::Binary
+        );
+        assert_eq!(HTTPBodyKind::detect("", b" [1, 2]"), HTTPBodyKind::Json);
+        assert_eq!(
+            HTTPBodyKind::detect("", "caf\u{e9}".as_bytes()),
+            HTTPBodyKind::Text
+        );
+        assert_eq!(
+            HTTPBodyKind::detect("", b"\x00\x01\x02"),
+            HTTPBodyKind::Binary
+        );
+    }
+
+    #[tokio::test]
+    async fn test_discard_body() {
+        let id = uuid::Uuid::new_v4().to_string();
+        let mut writer = BodyWriter::create(&id).await.unwrap();
+        writer.write(b"kept").await.unwrap();
+        let path = writer.finish().await.unwrap().expect("path");
+        let dir = tempfile::tempdir().unwrap();
+        let saved = dir.path().join("saved");
+        assert_eq!(save_body(&id, &saved.to_string_lossy()).await.unwrap(), 4);
+
+        discard_body(&id).await.unwrap();
+        assert!(!path.exists());
+        discard_body(&id).await.unwrap();
+
+        let mut writer = BodyWriter::discarding();
+        writer.write(b"skipped").await.unwrap();
+        assert_eq!(writer.head(), b"skipped");
+        assert_eq!(writer.finish().await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_decode_text_with_charset() {
+        assert_eq!(
+            decode_text("text/plain; charset=ISO-8859-1", b"caf\xe9"),
+            "caf\u{e9}"
+        );
+        assert_eq!(
+            decode_text("text/html;charset=\"Shift_JIS\"", b"\x82\xa0"),
+            "\u{3042}"
+        );
+        assert_eq!(
+            decode_text("application/json", "caf\u{e9}".as_bytes()),
+            "caf\u{e9}"
+        );
+        assert_eq!(decode_text("text/plain; charset=bogus", b"ok"), "ok");
+    }
+
+    #[test]
+    fn test_decode_gzip_body() {
+        let text = "compressed ".repeat(100);
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let mut decoder = BodyDecoder::new("GZIP").expect("gzip decoder");
+        let mut decoded = vec![];
+        for chunk in gzipped.chunks(7) {
+            decoded.extend(decoder.decode(chunk).unwrap());
+        }
+        decoded.extend(decoder.finish().unwrap());
+        assert_eq!(String::from_utf8(decoded).unwrap(), text);
+
+        let mut truncated = BodyDecoder::new("gzip").unwrap();
+        truncated.decode(&gzipped[..gzipped.len() / 2]).unwrap();
+        assert!(truncated.finish().is_err());
+        assert!(BodyDecoder::new("identity").is_none());
+        assert!(BodyDecoder::new("gzip, br").is_none());
+    }
+}
@@ -0,0 +1,582 @@
+use crate::errors::{APIError, ApiResult};
+use crate::services::http::{HTTPRequest, HTTPRequestKVParam};
+use crate::services::http_auth::HTTPAuth;
+use crate::services::http_runner::HTTPExtraction;
+use crate::store;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const COLLECTION_DIR: &str = "collections";
+
+/// Held while a collection file is read, changed and written back.
+static COLLECTION_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// A folder of saved requests. A collection is the root folder, saved as
+/// `collections/<name>.yaml` or `.json`, see [`HTTPCollectionFormat`].
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPFolder {
+    #[serde(default = "new_id")]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Sent by every request below this folder that does not set them itself.
+    #[serde(default)]
+    pub headers: Vec<HTTPRequestKVParam>,
+    /// Used by requests below this folder that have no auth of their own.
+    #[serde(default)]
+    pub auth: Option<HTTPAuth>,
+    #[serde(default)]
+    pub items: Vec<HTTPCollectionItem>,
+}
+
+/// File format of a saved collection.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum HTTPCollectionFormat {
+    #[default]
+    Yaml,
+    Json,
+}
+
+impl HTTPCollectionFormat {
+    fn of(path: &Path) -> Self {
+        match path.extension() {
+            Some(ext) if ext == "json" => HTTPCollectionFormat::Json,
+            _ => HTTPCollectionFormat::Yaml,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            HTTPCollectionFormat::Yaml => "yaml",
+            HTTPCollectionFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HTTPCollectionItem {
+    Folder(HTTPFolder),
+    Request(HTTPSavedRequest),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPSavedRequest {
+    #[serde(default = "new_id")]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub request: HTTPRequest,
+    /// Values taken from the response for the requests run after it by the runner.
+    #[serde(default)]
+    pub extract: Vec<HTTPExtraction>,
+}
+
+/// A request found by [`search`].
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPCollectionMatch {
+    pub collection: String,
+    /// Names of the folders leading to the request.
+    pub path: Vec<String>,
+    pub id: String,
+    pub name: String,
+    pub method: String,
+    pub url: String,
+}
+
+fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+impl HTTPCollectionItem {
+    pub fn id(&self) -> &str {
+        match self {
+            HTTPCollectionItem::Folder(folder) => &folder.id,
+            HTTPCollectionItem::Request(saved) => &saved.id,
+        }
+    }
+
+    /// Deep copy with fresh ids, named as a copy.
+    fn duplicate(&self) -> HTTPCollectionItem {
+        let mut copy = self.clone();
+        copy.renew_ids();
+        match &mut copy {
+            HTTPCollectionItem::Folder(folder) => folder.name.push_str(" Copy"),
+            HTTPCollectionItem::Request(saved) => saved.name.push_str(" Copy"),
+        }
+        copy
+    }
+
+    fn renew_ids(&mut self) {
+        match self {
+            HTTPCollectionItem::Folder(folder) => {
+                folder.id = new_id();
+                folder.items.iter_mut().for_each(Self::renew_ids);
+            }
+            HTTPCollectionItem::Request(saved) => saved.id = new_id(),
+        }
+    }
+}
+
+impl HTTPFolder {
+    pub fn load(name: &str) -> ApiResult<HTTPFolder> {
+        let mut collection = Self::read(&Self::path(name)?)?;
+        collection.name = name.to_string();
+        Ok(collection)
+    }
+
+    /// Reads a collection file from anywhere, named after the file.
+    pub fn read(path: &Path) -> ApiResult<HTTPFolder> {
+        let data = fs::read_to_string(path)?;
+        let mut collection: HTTPFolder = match HTTPCollectionFormat::of(path) {
+            HTTPCollectionFormat::Json => serde_json::from_str(&data)?,
+            HTTPCollectionFormat::Yaml => serde_yaml::from_str(&data)?,
+        };
+        if let Some(stem) = path.file_stem() {
+            collection.name = stem.to_string_lossy().to_string();
+        }
+        Ok(collection)
+    }
+
+    /// Saves in the format the collection already has, YAML for a new one.
+    pub fn save(&self) -> ApiResult<()> {
+        self.save_as(None)
+    }
+
+    /// Saves in `format`, converting the file when it was saved in the other one.
+    pub fn save_as(&self, format: Option<HTTPCollectionFormat>) -> ApiResult<()> {
+        let existing = Self::path(&self.name)?;
+        let format = format.unwrap_or_else(|| HTTPCollectionFormat::of(&existing));
+        let path = existing.with_extension(format.extension());
+        let data = match format {
+            HTTPCollectionFormat::Json => serde_json::to_string_pretty(self)?,
+            HTTPCollectionFormat::Yaml => serde_yaml::to_string(self)?,
+        };
+        fs::write(&path, data)?;
+        if path != existing && existing.exists() {
+            fs::remove_file(existing)?;
+        }
+        Ok(())
+    }
+
+    pub fn delete(name: &str) -> ApiResult<()> {
+        fs::remove_file(Self::path(name)?)?;
+        Ok(())
+    }
+
+    /// Names of all collections saved on disk.
+    pub fn names() -> ApiResult<Vec<String>> {
+        let mut names = store::names(COLLECTION_DIR, "yaml")?;
+        names.extend(store::names(COLLECTION_DIR, "json")?);
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    fn path(name: &str) -> ApiResult<PathBuf> {
+        let dir = store::dir(COLLECTION_DIR)?;
+        let json = dir.join(store::file_name(name, "json")?);
+        if json.exists() {
+            return Ok(json);
+        }
+        Ok(dir.join(store::file_name(name, "yaml")?))
+    }
+
+    /// Loads collection `name`, applies `change` and saves it when `change` succeeds.
+    fn update<T>(name: &str, change: impl FnOnce(&mut HTTPFolder) -> ApiResult<T>) -> ApiResult<T> {
+        let _lock = COLLECTION_LOCK
+            .lock()
+            .map_err(|e| APIError::General(e.to_string()))?;
+        let mut collection = HTTPFolder::load(name)?;
+        let result = change(&mut collection)?;
+        collection.save()?;
+        Ok(result)
+    }
+
+    fn folder_mut(&mut self, id: Option<&str>) -> ApiResult<&mut HTTPFolder> {
+        let id = match id {
+            Some(id) if id != self.id => id,
+            _ => return Ok(self),
+        };
+        let mut pending = vec![self];
+        while let Some(folder) = pending.pop() {
+            for item in folder.items.iter_mut() {
+                if let HTTPCollectionItem::Folder(folder) = item {
+                    if folder.id == id {
+                        return Ok(folder);
+                    }
+                    pending.push(folder);
+                }
+            }
+        }
+        Err(not_found(id))
+    }
+
+    pub fn find(&self, id: &str) -> Option<&HTTPCollectionItem> {
+        self.items.iter().find_map(|item| match item {
+            _ if item.id() == id => Some(item),
+            HTTPCollectionItem::Folder(folder) => folder.find(id),
+            HTTPCollectionItem::Request(_) => None,
+        })
+    }
+
+    /// Adds `item` to folder `parent`, or replaces the item with the same id wherever it is.
+    fn upsert(&mut self, parent: Option<&str>, item: HTTPCollectionItem) -> ApiResult<()> {
+        if let Some(existing) = self.find_mut(item.id()) {
+            *existing = item;
+            return Ok(());
+        }
+        self.folder_mut(parent)?.items.push(item);
+        Ok(())
+    }
+
+    fn find_mut(&mut self, id: &str) -> Option<&mut HTTPCollectionItem> {
+        for item in self.items.iter_mut() {
+            if item.id() == id {
+                return Some(item);
+            }
+            if let HTTPCollectionItem::Folder(folder) = item {
+                if let Some(found) = folder.find_mut(id) {
+                    return Some(found);
+                }
+            }
+        }
+        None
+    }
+
+    fn remove(&mut self, id: &str) -> Option<HTTPCollectionItem> {
+        if let Some(index) = self.items.iter().position(|item| item.id() == id) {
+            return Some(self.items.remove(index));
+        }
+        self.items.iter_mut().find_map(|item| match item {
+            HTTPCollectionItem::Folder(folder) => folder.remove(id),
+            HTTPCollectionItem::Request(_) => None,
+        })
+    }
+
+    /// Copies item `id` right after itself, returns the copy.
+    fn duplicate(&mut self, id: &str) -> Option<HTTPCollectionItem> {
+        if let Some(index) = self.items.iter().position(|item| item.id() == id) {
+            let copy = self.items[index].duplicate();
+            self.items.insert(index + 1, copy.clone());
+            return Some(copy);
+        }
+        self.items.iter_mut().find_map(|item| match item {
+            HTTPCollectionItem::Folder(folder) => folder.duplicate(id),
+            HTTPCollectionItem::Request(_) => None,
+        })
+    }
+
+    /// The saved request `id` with the headers and auth inherited from its folders applied.
+    pub fn request(&self, id: &str) -> Option<HTTPRequest> {
+        let mut request = self.items.iter().find_map(|item| match item {
+            HTTPCollectionItem::Request(saved) if saved.id == id => Some(saved.request.clone()),
+            HTTPCollectionItem::Folder(folder) => folder.request(id),
+            HTTPCollectionItem::Request(_) => None,
+        })?;
+        for header in self.headers.iter().filter(|h| h.enabled) {
+            let overridden = request
+                .headers
+                .iter()
+                .any(|h| h.enabled && h.key.eq_ignore_ascii_case(&header.key));
+            if !overridden {
+                request.headers.push(header.clone());
+            }
+        }
+        if request.auth.is_none() {
+            request.auth = self.auth.clone();
+        }
+        Some(request)
+    }
+
+    fn search(&self, query: &str, path: &mut Vec<String>, found: &mut Vec<HTTPCollectionMatch>) {
+        for item in &self.items {
+            match item {
+                HTTPCollectionItem::Folder(folder) => {
+                    path.push(folder.name.clone());
+                    folder.search(query, path, found);
+                    path.pop();
+                }
+                HTTPCollectionItem::Request(saved) => {
+                    let matches = [&saved.name, &saved.description, &saved.request.url]
+                        .iter()
+                        .any(|text| text.to_lowercase().contains(query));
+                    if matches {
+                        found.push(HTTPCollectionMatch {
+                            collection: path.first().cloned().unwrap_or_default(),
+                            path: path[1..].to_vec(),
+                            id: saved.id.clone(),
+                            name: saved.name.clone(),
+                            method: saved.request.method.clone(),
+                            url: saved.request.url.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn not_found(id: &str) -> APIError {
+    APIError::General(format!("Collection item {} not found", id))
+}
+
+/// Moves item `id` of collection `from` into folder `parent` of collection `to`.
+pub fn move_item(from: &str, id: &str, to: &str, parent: Option<&str>) -> ApiResult<()> {
+    if from == to {
+        return HTTPFolder::update(from, |collection| {
+            let item = collection.remove(id).ok_or_else(|| not_found(id))?;
+            if let HTTPCollectionItem::Folder(folder) = &item {
+                if parent.is_some_and(|parent| parent == folder.id || folder.find(parent).is_some())
+                {
+                    return Err(APIError::General(format!(
+                        "Cannot move folder {} into itself",
+                        folder.name
+                    )));
+                }
+            }
+            collection.folder_mut(parent)?.items.push(item);
+            Ok(())
+        });
+    }
+    let _lock = COLLECTION_LOCK
+        .lock()
+        .map_err(|e| APIError::General(e.to_string()))?;
+    let original = HTTPFolder::load(from)?;
+    let mut source = original.clone();
+    let item = source.remove(id).ok_or_else(|| not_found(id))?;
+    let mut target = HTTPFolder::load(to)?;
+    target.folder_mut(parent)?.items.push(item);
+    // nothing is written until both sides are ready, and the source goes first so a
+    // failed save never leaves the item in both collections
+    source.save()?;
+    if let Err(e) = target.save() {
+        // put the item back rather than lose it
+        original.save()?;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Saved requests whose name, description or url contain `query`, ignoring case.
+pub fn search(query: &str) -> ApiResult<Vec<HTTPCollectionMatch>> {
+    let query = query.trim().to_lowercase();
+    let mut found = Vec::new();
+    for name in HTTPFolder::names()? {
+        let collection = HTTPFolder::load(&name)?;
+        collection.search(&query, &mut vec![name], &mut found);
+    }
+    Ok(found)
+}
+
+pub mod commands {
+    use super::*;
+    use tauri::command;
+
+    #[command(async)]
+    pub async fn http_collections() -> ApiResult<Vec<String>> {
+        HTTPFolder::names()
+    }
+
+    #[command(async)]
+    pub async fn http_collection(name: String) -> ApiResult<HTTPFolder> {
+        HTTPFolder::load(&name)
+    }
+
+    /// Saves `collection` in `format`, or in the format it already has when left out.
+    #[command(async)]
+    pub async fn http_save_collection(
+        collection: HTTPFolder,
+        format: Option<HTTPCollectionFormat>,
+    ) -> ApiResult<()> {
+        log::info!("Saving collection {}", collection.name);
+        collection.save_as(format)
+    }
+
+    #[command(async)]
+    pub async fn http_delete_collection(name: String) -> ApiResult<()> {
+        log::info!("Deleting collection {}", name);
+        HTTPFolder::delete(&name)
+    }
+
+    /// Request `id` of `collection` as it is sent, with folder defaults applied.
+    #[command(async)]
+    pub async fn http_collection_request(collection: String, id: String) -> ApiResult<HTTPRequest> {
+        HTTPFolder::load(&collection)?
+            .request(&id)
+            .ok_or_else(|| not_found(&id))
+    }
+
+    #[command(async)]
+    pub async fn http_save_collection_item(
+        collection: String,
+        parent: Option<String>,
+        item: HTTPCollectionItem,
+    ) -> ApiResult<()> {
+        log::info!("Saving item {} in collection {}", item.id(), collection);
+        HTTPFolder::update(&collection, |c| c.upsert(parent.as_deref(), item))
+    }
+
+    #[command(async)]
+    pub async fn http_delete_collection_item(collection: String, id: String) -> ApiResult<()> {
+        log::info!("Deleting item {} of collection {}", id, collection);
+        HTTPFolder::update(&collection, |c| {
+            c.remove(&id).map(|_| ()).ok_or_else(|| not_found(&id))
+        })
+    }
+
+    #[command(async)]
+    pub async fn http_move_collection_item(
+        collection: String,
+        id: String,
+        to_collection: String,
+        parent: Option<String>,
+    ) -> ApiResult<()> {
+        log::info!("Moving item {} of {} to {}", id, collection, to_collection);
+        move_item(&collection, &id, &to_collection, parent.as_deref())
+    }
+
+    #[command(async)]
+    pub async fn http_duplicate_collection_item(
+        collection: String,
+        id: String,
+    ) -> ApiResult<HTTPCollectionItem> {
+        log::info!("Duplicating item {} of collection {}", id, collection);
+        HTTPFolder::update(&collection, |c| {
+            c.duplicate(&id).ok_or_else(|| not_found(&id))
+        })
+    }
+
+    #[command(async)]
+    pub async fn http_search_collections(query: String) -> ApiResult<Vec<HTTPCollectionMatch>> {
+        search(&query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved(name: &str, headers: Vec<HTTPRequestKVParam>) -> HTTPCollectionItem {
+        let request = serde_json::from_value(serde_json::json!({
+            "method": "GET",
+            "url": format!("https://example.com/{}", name),
+            "body": "",
+            "contentType": "",
+            "headers": headers,
+            "query": [],
+        }))
+        .expect("request");
+        HTTPCollectionItem::Request(HTTPSavedRequest {
+            id: name.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            request,
+            extract: vec![],
+        })
+    }
+
+    fn header(key: &str, value: &str) -> HTTPRequestKVParam {
+        HTTPRequestKVParam {
+            key: key.to_string(),
+            value: value.to_string(),
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_collection_items() {
+        store::init_for_tests();
+        let folder = HTTPFolder {
+            id: "users".to_string(),
+            name: "Users".to_string(),
+            headers: vec![header("X-Api-Key", "folder"), header("Accept", "*/*")],
+            items: vec![saved("list", vec![header("accept", "application/json")])],
+            ..Default::default()
+        };
+        HTTPFolder {
+            name: "test".to_string(),
+            items: vec![HTTPCollectionItem::Folder(folder), saved("health", vec![])],
+            ..Default::default()
+        }
+        .save()
+        .expect("save");
+
+        let collection = HTTPFolder::load("test").expect("load");
+        let request = collection.request("list").expect("request");
+        let headers: Vec<_> = request
+            .headers
+            .iter()
+            .map(|h| (h.key.as_str(), h.value.as_str()))
+            .collect();
+        assert_eq!(
+            headers,
+            vec![("accept", "application/json"), ("X-Api-Key", "folder")]
+        );
+
+        move_item("test", "health", "test", Some("users")).expect("move");
+        let copy = HTTPFolder::update("test", |c| Ok(c.duplicate("users")))
+            .expect("duplicate")
+            .expect("copy");
+        assert_ne!(copy.id(), "users");
+
+        let found = search("HEALTH").expect("search");
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].collection, "test");
+        assert_eq!(found[0].path, vec!["Users".to_string()]);
+        assert!(move_item("test", "users", "test", Some("users")).is_err());
+        assert!(move_item("test", "users", "missing", None).is_err());
+        assert!(HTTPFolder::load("test").unwrap().find("users").is_some());
+
+        HTTPFolder {
+            name: "other".to_string(),
+            ..Default::default()
+        }
+        .save()
+        .expect("save other");
+        assert!(move_item("test", "users", "other", Some("nowhere")).is_err());
+        assert!(HTTPFolder::load("test").unwrap().find("users").is_some());
+        move_item("test", "users", "other", None).expect("move across");
+        assert!(HTTPFolder::load("test").unwrap().find("users").is_none());
+        assert!(HTTPFolder::load("other").unwrap().find("users").is_some());
+    }
+
+    #[test]
+    fn test_collection_format() {
+        store::init_for_tests();
+        let collection = HTTPFolder {
+            name: "formats".to_string(),
+            items: vec![saved("health", vec![])],
+            ..Default::default()
+        };
+        collection
+            .save_as(Some(HTTPCollectionFormat::Json))
+            .expect("save");
+        let path = HTTPFolder::path("formats").unwrap();
+        assert!(path.ends_with("formats.json"));
+        collection.save().expect("save again");
+        assert!(HTTPFolder::path("formats")
+            .unwrap()
+            .ends_with("formats.json"));
+
+        collection
+            .save_as(Some(HTTPCollectionFormat::Yaml))
+            .expect("convert");
+        assert!(!path.exists());
+        let names = HTTPFolder::names().unwrap();
+        assert_eq!(names.iter().filter(|n| *n == "formats").count(), 1);
+        assert!(HTTPFolder::load("formats")
+            .unwrap()
+            .find("health")
+            .is_some());
+    }
+}
@@ -0,0 +1,261 @@
+use crate::errors::{APIError, ApiResult};
+use crate::store;
+use cookie_store::{CookieDomain, CookieExpiration, CookieStore};
+use once_cell::sync::Lazy;
+use reqwest::header::HeaderValue;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use url::Url;
+
+const COOKIE_DIR: &str = "cookies";
+
+/// Cookie jars loaded so far, keyed by name. Every jar is backed by `cookies/<name>.json`.
+static COOKIE_JARS: Lazy<Mutex<HashMap<String, Arc<CookieJar>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPCookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    /// Only sent to `domain` itself, not to its subdomains.
+    pub host_only: bool,
+    pub path: String,
+    pub secure: bool,
+    pub http_only: bool,
+    pub same_site: Option<String>,
+    /// Unix timestamp in seconds, `None` for session cookies.
+    pub expires: Option<i64>,
+}
+
+impl From<&cookie_store::Cookie<'static>> for HTTPCookie {
+    fn from(c: &cookie_store::Cookie<'static>) -> Self {
+        let (domain, host_only) = match &c.domain {
+            CookieDomain::HostOnly(d) => (d.clone(), true),
+            CookieDomain::Suffix(d) => (d.clone(), false),
+            _ => (String::new(), false),
+        };
+        HTTPCookie {
+            name: c.name().to_string(),
+            value: c.value().to_string(),
+            domain,
+            host_only,
+            path: String::from(&c.path),
+            secure: c.secure().unwrap_or(false),
+            http_only: c.http_only().unwrap_or(false),
+            same_site: c.same_site().map(|s| s.to_string()),
+            expires: match &c.expires {
+                CookieExpiration::AtUtc(at) => Some(at.unix_timestamp()),
+                CookieExpiration::SessionEnd => None,
+            },
+        }
+    }
+}
+
+impl HTTPCookie {
+    /// Renders the cookie as the `Set-Cookie` value a server at [`HTTPCookie::origin`] would send.
+    fn to_set_cookie(&self) -> String {
+        let mut set_cookie = format!("{}={}; Path={}", self.name, self.value, self.path);
+        if !self.host_only {
+            set_cookie.push_str(&format!("; Domain={}", self.domain));
+        }
+        if self.secure {
+            set_cookie.push_str("; Secure");
+        }
+        if self.http_only {
+            set_cookie.push_str("; HttpOnly");
+        }
+        if let Some(same_site) = &self.same_site {
+            set_cookie.push_str(&format!("; SameSite={}", same_site));
+        }
+        if let Some(expires) = self.expires {
+            let max_age = expires - chrono::Utc::now().timestamp();
+            set_cookie.push_str(&format!("; Max-Age={}", max_age.max(0)));
+        }
+        set_cookie
+    }
+
+    fn origin(&self) -> ApiResult<Url> {
+        let scheme = if self.secure { "https" } else { "http" };
+        let domain = self.domain.trim_start_matches('.');
+        Ok(Url::parse(&format!("{}://{}{}", scheme, domain, self.path))?)
+    }
+}
+
+/// A named cookie store shared by every request that selects it, saved after each change.
+pub struct CookieJar {
+    name: String,
+    store: RwLock<CookieStore>,
+}
+
+impl CookieJar {
+    pub fn get(name: &str) -> ApiResult<Arc<CookieJar>> {
+        let mut jars = COOKIE_JARS
+            .lock()
+            .map_err(|e| APIError::Cookie(e.to_string()))?;
+        if let Some(jar) = jars.get(name) {
+            return Ok(jar.clone());
+        }
+
+        let path = Self::path(name)?;
+        let store = if path.exists() {
+            cookie_store::serde::json::load(BufReader::new(File::open(&path)?))?
+        } else {
+            CookieStore::default()
+        };
+        let jar = Arc::new(CookieJar {
+            name: name.to_string(),
+            store: RwLock::new(store),
+        });
+        jars.insert(name.to_string(), jar.clone());
+        Ok(jar)
+    }
+
+    /// Names of all jars saved on disk.
+    pub fn names() -> ApiResult<Vec<String>> {
+        store::names(COOKIE_DIR, "json")
+    }
+
+    fn path(name: &str) -> ApiResult<PathBuf> {
+        Ok(store::dir(COOKIE_DIR)?.join(store::file_name(name, "json")?))
+    }
+
+    pub fn cookies(&self) -> ApiResult<Vec<HTTPCookie>> {
+        let store = self.store.read().map_err(|e| APIError::Cookie(e.to_string()))?;
+        Ok(store.iter_unexpired().map(HTTPCookie::from).collect())
+    }
+
+    /// Adds `cookie`, replacing the one with the same domain, path and name.
+    pub fn set(&self, cookie: &HTTPCookie) -> ApiResult<()> {
+        self.update(|store| {
+            store.parse(&cookie.to_set_cookie(), &cookie.origin()?)?;
+            Ok(())
+        })
+    }
+
+    pub fn remove(&self, domain: &str, path: &str, name: &str) -> ApiResult<bool> {
+        self.update(|store| Ok(store.remove(domain, path, name).is_some()))
+    }
+
+    pub fn clear(&self) -> ApiResult<()> {
+        self.update(|store| {
+            store.clear();
+            Ok(())
+        })
+    }
+
+    fn update<T, F>(&self, update_fn: F) -> ApiResult<T>
+    where
+        F: FnOnce(&mut CookieStore) -> ApiResult<T>,
+    {
+        let mut store = self.store.write().map_err(|e| APIError::Cookie(e.to_string()))?;
+        let result = update_fn(&mut store)?;
+        let mut writer = BufWriter::new(File::create(Self::path(&self.name)?)?);
+        cookie_store::serde::json::save_incl_expired_and_nonpersistent(&store, &mut writer)?;
+        Ok(result)
+    }
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let cookies: Vec<_> = cookie_headers
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| cookie::Cookie::parse(value.to_string()).ok())
+            .collect();
+        let result = self.update(|store| {
+            store.store_response_cookies(cookies.into_iter(), url);
+            Ok(())
+        });
+        if let Err(e) = result {
+            log::error!("Failed to store cookies in jar {}: {}", self.name, e);
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let store = self.store.read().ok()?;
+        let cookies = store
+            .get_request_values(url)
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if cookies.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&cookies).ok()
+    }
+}
+
+pub mod commands {
+    use super::*;
+    use tauri::command;
+
+    #[command(async)]
+    pub async fn http_cookie_jars() -> ApiResult<Vec<String>> {
+        CookieJar::names()
+    }
+
+    #[command(async)]
+    pub async fn http_cookies(jar: String) -> ApiResult<Vec<HTTPCookie>> {
+        CookieJar::get(&jar)?.cookies()
+    }
+
+    #[command(async)]
+    pub async fn http_set_cookie(jar: String, cookie: HTTPCookie) -> ApiResult<()> {
+        log::info!("Setting cookie {} in jar {}", cookie.name, jar);
+        CookieJar::get(&jar)?.set(&cookie)
+    }
+
+    #[command(async)]
+    pub async fn http_delete_cookie(
+        jar: String,
+        domain: String,
+        path: String,
+        name: String,
+    ) -> ApiResult<bool> {
+        log::info!("Deleting cookie {} from jar {}", name, jar);
+        CookieJar::get(&jar)?.remove(&domain, &path, &name)
+    }
+
+    #[command(async)]
+    pub async fn http_clear_cookies(jar: String) -> ApiResult<()> {
+        log::info!("Clearing cookie jar {}", jar);
+        CookieJar::get(&jar)?.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_remove_cookie() {
+        store::init_for_tests();
+        let jar = CookieJar::get("test").expect("jar");
+        let cookie = HTTPCookie {
+            name: "session".to_string(),
+            value: "abc".to_string(),
+            domain: "example.com".to_string(),
+            host_only: true,
+            path: "/".to_string(),
+            secure: false,
+            http_only: true,
+            same_site: None,
+            expires: None,
+        };
+        jar.set(&cookie).expect("set cookie");
+
+        let cookies = jar.cookies().expect("cookies");
+        assert_eq!(cookies.len(), 1);
+        assert_eq!(cookies[0].value, "abc");
+        assert!(cookies[0].host_only);
+        assert!(CookieJar::names().expect("names").contains(&"test".to_string()));
+
+        assert!(jar.remove("example.com", "/", "session").expect("remove"));
+        assert!(jar.cookies().expect("cookies").is_empty());
+    }
+}
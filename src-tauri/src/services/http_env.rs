@@ -0,0 +1,291 @@
+use crate::errors::ApiResult;
+use crate::store;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+const ENVIRONMENT_DIR: &str = "environments";
+/// Shorter values are left as they are by [`Variables::mask`], they would match all over
+/// unrelated text.
+const MIN_MASKED_LEN: usize = 4;
+
+/// A named set of variables, saved as `environments/<name>.json`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPEnvironment {
+    pub name: String,
+    pub variables: Vec<HTTPVariable>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPVariable {
+    pub key: String,
+    pub value: String,
+    /// Hidden by the UI, left out of logs, history and exports. Saved in plaintext in the
+    /// environment file like the other values.
+    #[serde(default)]
+    pub secret: bool,
+    pub enabled: bool,
+}
+
+impl fmt::Debug for HTTPVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = if self.secret { "***" } else { &self.value };
+        f.debug_struct("HTTPVariable")
+            .field("key", &self.key)
+            .field("value", &value)
+            .field("enabled", &self.enabled)
+            .finish()
+    }
+}
+
+impl HTTPEnvironment {
+    pub fn load(name: &str) -> ApiResult<HTTPEnvironment> {
+        let json = fs::read_to_string(Self::path(name)?)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub fn save(&self) -> ApiResult<()> {
+        fs::write(Self::path(&self.name)?, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn delete(name: &str) -> ApiResult<()> {
+        fs::remove_file(Self::path(name)?)?;
+        Ok(())
+    }
+
+    /// Names of all environments saved on disk.
+    pub fn names() -> ApiResult<Vec<String>> {
+        store::names(ENVIRONMENT_DIR, "json")
+    }
+
+    fn path(name: &str) -> ApiResult<PathBuf> {
+        Ok(store::dir(ENVIRONMENT_DIR)?.join(store::file_name(name, "json")?))
+    }
+}
+
+/// Values `{{name}}` placeholders are replaced with.
+#[derive(Debug, Clone, Default)]
+pub struct Variables(HashMap<String, String>);
+
+impl From<&HTTPEnvironment> for Variables {
+    fn from(environment: &HTTPEnvironment) -> Self {
+        Variables(
+            environment
+                .variables
+                .iter()
+                .filter(|v| v.enabled)
+                .map(|v| (v.key.clone(), v.value.clone()))
+                .collect(),
+        )
+    }
+}
+
+impl Variables {
+    /// The enabled [`HTTPVariable::secret`] variables of `environment`.
+    pub fn secrets(environment: &HTTPEnvironment) -> Variables {
+        Variables(
+            environment
+                .variables
+                .iter()
+                .filter(|v| v.enabled && v.secret && !v.value.is_empty())
+                .map(|v| (v.key.clone(), v.value.clone()))
+                .collect(),
+        )
+    }
+
+    /// The enabled variables of `environment`, secret ones rendering as their own
+    /// `{{name}}` placeholder.
+    pub fn without_secrets(environment: &HTTPEnvironment) -> Variables {
+        let mut variables = Variables::from(environment);
+        for name in Variables::secrets(environment).0.into_keys() {
+            variables.0.insert(name.clone(), placeholder(&name));
+        }
+        variables
+    }
+
+    /// Puts the `{{name}}` placeholder back wherever a value of at least [`MIN_MASKED_LEN`]
+    /// bytes shows up in `text`. Longer values win where one contains another.
+    pub fn mask(&self, text: &str) -> String {
+        let mut values: Vec<_> = self
+            .0
+            .iter()
+            .filter(|(_, value)| value.len() >= MIN_MASKED_LEN)
+            .collect();
+        values.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(b.0)));
+        let mut masked = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            let found = values
+                .iter()
+                .find(|(_, value)| rest.starts_with(value.as_str()));
+            match found {
+                Some((name, value)) => {
+                    masked.push_str(&placeholder(name));
+                    rest = &rest[value.len()..];
+                }
+                None => {
+                    masked.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        masked
+    }
+
+    pub fn insert(&mut self, key: &str, value: &str) {
+        self.0.insert(key.to_string(), value.to_string());
+    }
+
+    /// Adds the values of `other`, replacing those with the same names.
+    pub fn extend(&mut self, other: &Variables) {
+        self.0
+            .extend(other.0.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+
+    /// Replaces the placeholders in `template`. Names neither set nor dynamic are added
+    /// to `missing` and their placeholder is kept.
+    pub fn render(&self, template: &str, missing: &mut BTreeSet<String>) -> String {
+        let mut rendered = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            let end = match rest[start + 2..].find("}}") {
+                Some(end) => start + 2 + end,
+                None => break,
+            };
+            rendered.push_str(&rest[..start]);
+            let name = rest[start + 2..end].trim();
+            match self.0.get(name).cloned().or_else(|| dynamic(name)) {
+                Some(value) => rendered.push_str(&value),
+                None => {
+                    missing.insert(name.to_string());
+                    rendered.push_str(&rest[start..end + 2]);
+                }
+            }
+            rest = &rest[end + 2..];
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+fn placeholder(name: &str) -> String {
+    format!("{{{{{}}}}}", name)
+}
+
+/// Values generated anew for every placeholder, like `{{$uuid}}`.
+fn dynamic(name: &str) -> Option<String> {
+    let value = match name {
+        "$uuid" => uuid::Uuid::new_v4().to_string(),
+        "$timestamp" => chrono::Utc::now().timestamp().to_string(),
+        "$isoTimestamp" => chrono::Utc::now().to_rfc3339(),
+        "$randomInt" => rand::random_range(0..=1000).to_string(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+pub mod commands {
+    use super::*;
+    use tauri::command;
+
+    #[command(async)]
+    pub async fn http_environments() -> ApiResult<Vec<String>> {
+        HTTPEnvironment::names()
+    }
+
+    #[command(async)]
+    pub async fn http_environment(name: String) -> ApiResult<HTTPEnvironment> {
+        HTTPEnvironment::load(&name)
+    }
+
+    #[command(async)]
+    pub async fn http_save_environment(environment: HTTPEnvironment) -> ApiResult<()> {
+        log::info!("Saving environment {}", environment.name);
+        environment.save()
+    }
+
+    #[command(async)]
+    pub async fn http_delete_environment(name: String) -> ApiResult<()> {
+        log::info!("Deleting environment {}", name);
+        HTTPEnvironment::delete(&name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut variables = Variables::default();
+        variables.insert("baseUrl", "https://uat.example.com");
+        variables.insert("token", "abc");
+        let mut missing = BTreeSet::new();
+
+        let rendered = variables.render("{{baseUrl}}/users?token={{ token }}", &mut missing);
+        assert_eq!(rendered, "https://uat.example.com/users?token=abc");
+        assert!(missing.is_empty());
+
+        let id = variables.render("{{$uuid}}", &mut missing);
+        assert!(uuid::Uuid::parse_str(&id).is_ok());
+        let n: u32 = variables
+            .render("{{$randomInt}}", &mut missing)
+            .parse()
+            .unwrap();
+        assert!(n <= 1000);
+
+        let rendered = variables.render("{{host}}/{{id}} {{unclosed", &mut missing);
+        assert_eq!(rendered, "{{host}}/{{id}} {{unclosed");
+        assert_eq!(
+            missing.into_iter().collect::<Vec<_>>(),
+            vec!["host".to_string(), "id".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_mask_secrets() {
+        let variable = |key: &str, value: &str, secret| HTTPVariable {
+            key: key.to_string(),
+            value: value.to_string(),
+            secret,
+            enabled: true,
+        };
+        let environment = HTTPEnvironment {
+            name: "uat".to_string(),
+            variables: vec![
+                variable("host", "uat.example.com", false),
+                variable("token", "s3cr3t", true),
+            ],
+        };
+        let secrets = Variables::secrets(&environment);
+        assert_eq!(
+            secrets.mask("https://uat.example.com/?token=s3cr3t"),
+            "https://uat.example.com/?token={{token}}"
+        );
+
+        let environment = HTTPEnvironment {
+            name: "uat".to_string(),
+            variables: vec![
+                variable("pin", "1", true),
+                variable("password", "s3cr3t", true),
+                variable("secret", "s3cr3t-s3cr3t", true),
+            ],
+        };
+        let secrets = Variables::secrets(&environment);
+        assert_eq!(
+            secrets.mask("id=12 s3cr3t-s3cr3t s3cr3t"),
+            "id=12 {{secret}} {{password}}"
+        );
+
+        let mut missing = BTreeSet::new();
+        let rendered = Variables::without_secrets(&environment)
+            .render("https://{{host}}/?token={{token}}", &mut missing);
+        assert_eq!(rendered, "https://uat.example.com/?token={{token}}");
+        assert!(missing.is_empty());
+    }
+}
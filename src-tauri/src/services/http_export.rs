@@ -0,0 +1,973 @@
+use crate::errors::ApiResult;
+use crate::services::http::{
+    HTTPBodyType, HTTPMultipartPart, HTTPRedirectPolicy, HTTPRequest, HTTPVersion,
+};
+use crate::services::http_auth::HTTPAuth;
+use crate::services::http_env::{HTTPEnvironment, Variables};
+use crate::services::http_graphql;
+use crate::services::http_proxy::HTTPProxySettings;
+use crate::services::http_tls::{HTTPClientIdentity, HTTPTlsOptions, HTTPTlsVersion};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use url::Url;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum HTTPExportFormat {
+    Curl,
+    Httpie,
+    /// Rust with reqwest and tokio.
+    Rust,
+    /// Python with requests.
+    Python,
+    /// JavaScript `fetch`, reading multipart files with Node.js.
+    JavaScript,
+    /// Go with net/http.
+    Go,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPExport {
+    pub code: String,
+    /// Settings of the request the generated code leaves out.
+    pub warnings: Vec<String>,
+}
+
+/// What the request sends, with query params added to the url and auth split out.
+struct Outline {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Body,
+    basic: Option<(String, String)>,
+    digest: Option<(String, String)>,
+    redirect: HTTPRedirectPolicy,
+    http_version: HTTPVersion,
+    tls: HTTPTlsOptions,
+    proxy: Option<HTTPProxySettings>,
+}
+
+enum Body {
+    None,
+    Text(String),
+    Form(Vec<(String, String)>),
+    Multipart(Vec<HTTPMultipartPart>),
+}
+
+impl Outline {
+    fn new(request: &HTTPRequest, warnings: &mut Vec<String>) -> ApiResult<Outline> {
+        let mut url = Url::parse(&request.url)?;
+        for param in request.query.iter().filter(|q| q.enabled) {
+            url.query_pairs_mut().append_pair(&param.key, &param.value);
+        }
+        let mut headers: Vec<(String, String)> = request
+            .headers
+            .iter()
+            .filter(|h| h.enabled)
+            .map(|h| (h.key.clone(), h.value.clone()))
+            .collect();
+        let has_content_type = headers
+            .iter()
+            .any(|(k, _)| k.eq_ignore_ascii_case("content-type"));
+        let body = match &request.body_type {
+            HTTPBodyType::Raw | HTTPBodyType::Json if request.body.is_empty() => Body::None,
+            HTTPBodyType::Raw | HTTPBodyType::Json => {
+                let content_type = match &request.body_type {
+                    HTTPBodyType::Json if request.content_type.is_empty() => "application/json",
+                    _ => request.content_type.as_str(),
+                };
+                if !has_content_type && !content_type.is_empty() {
+                    headers.push(("Content-Type".to_string(), content_type.to_string()));
+                }
+                Body::Text(request.body.clone())
+            }
+            HTTPBodyType::UrlEncoded { params } => Body::Form(
+                params
+                    .iter()
+                    .filter(|p| p.enabled)
+                    .map(|p| (p.key.clone(), p.value.clone()))
+                    .collect(),
+            ),
+            HTTPBodyType::Multipart { parts } => {
+                Body::Multipart(parts.iter().filter(|p| p.enabled).cloned().collect())
+            }
+            HTTPBodyType::GraphQL {
+                query,
+                variables,
+                operation_name,
+            } => {
+                if !has_content_type {
+                    headers.push(("Content-Type".to_string(), "application/json".to_string()));
+                }
+                Body::Text(http_graphql::body(
+                    query,
+                    variables,
+                    operation_name.as_deref(),
+                )?)
+            }
+        };
+        if matches!(body, Body::Form(_) | Body::Multipart(_)) {
+            headers.retain(|(k, _)| !k.eq_ignore_ascii_case("content-type"));
+        }
+
+        let (mut basic, mut digest) = (None, None);
+        match &request.auth {
+            Some(HTTPAuth::Basic { username, password }) => {
+                basic = Some((username.clone(), password.clone()))
+            }
+            Some(HTTPAuth::Digest { username, password }) => {
+                digest = Some((username.clone(), password.clone()))
+            }
+            Some(HTTPAuth::Bearer { token }) => {
+                headers.push(("Authorization".to_string(), format!("Bearer {}", token)))
+            }
+            Some(HTTPAuth::AwsSigV4 { .. }) => {
+                warnings.push("AWS SigV4 signing is not exported".to_string())
+            }
+            Some(HTTPAuth::OAuth2 { .. }) => {
+                warnings.push("OAuth2 tokens are not exported, add the token header".to_string())
+            }
+            None => {}
+        }
+        if let Some(jar) = &request.cookie_jar {
+            warnings.push(format!("Cookies from jar {} are not exported", jar));
+        }
+        Ok(Outline {
+            method: request.method.to_uppercase(),
+            url: url.to_string(),
+            headers,
+            body,
+            basic,
+            digest,
+            redirect: request.redirect.clone(),
+            http_version: request.http_version,
+            tls: request.tls.clone(),
+            proxy: request.proxy.clone(),
+        })
+    }
+
+    /// Turns basic auth into a header for languages without a helper for it.
+    fn basic_header(&mut self) {
+        if let Some((username, password)) = self.basic.take() {
+            let credentials =
+                general_purpose::STANDARD.encode(format!("{}:{}", username, password));
+            self.headers.push((
+                "Authorization".to_string(),
+                format!("Basic {}", credentials),
+            ));
+        }
+    }
+
+    /// Warns about the settings snippets in `language` leave out.
+    fn warn_unsupported(&self, language: &str, warnings: &mut Vec<String>) {
+        let tls = &self.tls;
+        if tls.accept_invalid_certs
+            || !tls.ca_files.is_empty()
+            || tls.identity.is_some()
+            || tls.min_version.is_some()
+        {
+            warnings.push(format!("TLS options are not included in {} code", language));
+        }
+        if matches!(self.proxy, Some(HTTPProxySettings::Manual(_))) {
+            warnings.push(format!("The proxy is not included in {} code", language));
+        }
+        if self.http_version != HTTPVersion::Negotiate && language != "Rust" {
+            warnings.push(format!(
+                "The HTTP version is not included in {} code",
+                language
+            ));
+        }
+        if matches!(self.redirect, HTTPRedirectPolicy::Limit { .. }) && language == "JavaScript" {
+            warnings.push("fetch cannot limit redirects, they are all followed".to_string());
+        }
+        if self.digest.is_some() && language != "Python" {
+            warnings.push(format!("Digest auth is not included in {} code", language));
+        }
+        if let Body::Multipart(parts) = &self.body {
+            if language != "Python" && parts.iter().any(|p| p.content_type.is_some()) {
+                warnings.push(format!(
+                    "Content types of multipart parts are not included in {} code",
+                    language
+                ));
+            }
+        }
+    }
+}
+
+/// Generates code sending `request` as it is, placeholders resolved beforehand.
+pub fn export(request: &HTTPRequest, format: HTTPExportFormat) -> ApiResult<HTTPExport> {
+    let mut warnings = Vec::new();
+    let mut outline = Outline::new(request, &mut warnings)?;
+    let code = match format {
+        HTTPExportFormat::Curl => curl(&outline),
+        HTTPExportFormat::Httpie => httpie(&outline, &mut warnings),
+        HTTPExportFormat::Rust => {
+            outline.warn_unsupported("Rust", &mut warnings);
+            rust(&outline)
+        }
+        HTTPExportFormat::Python => {
+            outline.warn_unsupported("Python", &mut warnings);
+            python(&outline)
+        }
+        HTTPExportFormat::JavaScript => {
+            outline.warn_unsupported("JavaScript", &mut warnings);
+            outline.basic_header();
+            javascript(&outline)
+        }
+        HTTPExportFormat::Go => {
+            outline.warn_unsupported("Go", &mut warnings);
+            go(&outline)
+        }
+    };
+    Ok(HTTPExport { code, warnings })
+}
+
+/// Quotes `value` for a POSIX shell when it holds anything but safe characters.
+fn shell(value: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@,%+".contains(c);
+    if !value.is_empty() && value.chars().all(safe) {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// A double quoted string literal, valid in Python, JavaScript and Go.
+fn quoted(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+fn rust_quoted(value: &str) -> String {
+    format!("{:?}", value)
+}
+
+fn curl(outline: &Outline) -> String {
+    let mut first = vec!["curl".to_string()];
+    let has_body = !matches!(outline.body, Body::None);
+    match (outline.method.as_str(), has_body) {
+        ("HEAD", _) => first.push("--head".to_string()),
+        ("GET", false) | ("POST", true) => {}
+        (method, _) => first.push(format!("-X {}", shell(method))),
+    }
+    first.push(shell(&outline.url));
+
+    let mut args = vec![first.join(" ")];
+    for (key, value) in &outline.headers {
+        args.push(format!("-H {}", shell(&format!("{}: {}", key, value))));
+    }
+    if let Some((username, password)) = outline.basic.as_ref().or(outline.digest.as_ref()) {
+        args.push(format!(
+            "-u {}",
+            shell(&format!("{}:{}", username, password))
+        ));
+        if outline.digest.is_some() {
+            args.push("--digest".to_string());
+        }
+    }
+    match &outline.body {
+        Body::None => {}
+        Body::Text(body) => args.push(format!("--data-raw {}", shell(body))),
+        Body::Form(params) => {
+            for (key, value) in params {
+                let key: String = url::form_urlencoded::byte_serialize(key.as_bytes()).collect();
+                args.push(format!(
+                    "--data-urlencode {}",
+                    shell(&format!("{}={}", key, value))
+                ));
+            }
+        }
+        Body::Multipart(parts) => {
+            for part in parts {
+                let field = match &part.file {
+                    Some(file) => {
+                        let mut field = format!("{}=@{}", part.name, file);
+                        if let Some(content_type) = &part.content_type {
+                            field.push_str(&format!(";type={}", content_type));
+                        }
+                        if let Some(file_name) = &part.file_name {
+                            field.push_str(&format!(";filename={}", file_name));
+                        }
+                        format!("-F {}", shell(&field))
+                    }
+                    None => format!(
+                        "--form-string {}",
+                        shell(&format!("{}={}", part.name, part.value))
+                    ),
+                };
+                args.push(field);
+            }
+        }
+    }
+    match outline.redirect {
+        HTTPRedirectPolicy::None => {}
+        HTTPRedirectPolicy::Follow => args.push("-L".to_string()),
+        HTTPRedirectPolicy::Limit { max } => args.push(format!("-L --max-redirs {}", max)),
+    }
+    match outline.http_version {
+        HTTPVersion::Negotiate => {}
+        HTTPVersion::Http1 => args.push("--http1.1".to_string()),
+        HTTPVersion::Http2 => args.push("--http2-prior-knowledge".to_string()),
+    }
+    let tls = &outline.tls;
+    if tls.accept_invalid_certs {
+        args.push("-k".to_string());
+    }
+    for ca_file in &tls.ca_files {
+        args.push(format!("--cacert {}", shell(ca_file)));
+    }
+    match &tls.identity {
+        Some(HTTPClientIdentity::Pem { cert, key }) => {
+            args.push(format!("--cert {}", shell(cert)));
+            if let Some(key) = key.as_deref().filter(|k| !k.is_empty()) {
+                args.push(format!("--key {}", shell(key)));
+            }
+        }
+        Some(HTTPClientIdentity::Pkcs12 { file, password }) => args.push(format!(
+            "--cert-type P12 --cert {}",
+            shell(&format!("{}:{}", file, password))
+        )),
+        None => {}
+    }
+    match tls.min_version {
+        Some(HTTPTlsVersion::Tls12) => args.push("--tlsv1.2".to_string()),
+        Some(HTTPTlsVersion::Tls13) => args.push("--tlsv1.3".to_string()),
+        None => {}
+    }
+    match &outline.proxy {
+        Some(HTTPProxySettings::Manual(proxy)) => {
+            args.push(format!("-x {}", shell(&proxy.url)));
+            if let Some(username) = proxy.username.as_deref().filter(|u| !u.is_empty()) {
+                let password = proxy.password.as_deref().unwrap_or_default();
+                args.push(format!(
+                    "-U {}",
+                    shell(&format!("{}:{}", username, password))
+                ));
+            }
+            if !proxy.no_proxy.is_empty() {
+                args.push(format!("--noproxy {}", shell(&proxy.no_proxy)));
+            }
+        }
+        Some(HTTPProxySettings::Direct) => args.push("--noproxy '*'".to_string()),
+        Some(HTTPProxySettings::Environment) | None => {}
+    }
+    args.join(" \\\n  ")
+}
+
+fn httpie(outline: &Outline, warnings: &mut Vec<String>) -> String {
+    let mut args = vec!["http".to_string()];
+    match &outline.body {
+        Body::Form(_) => args.push("--form".to_string()),
+        Body::Multipart(_) => args.push("--multipart".to_string()),
+        Body::None | Body::Text(_) => {}
+    }
+    match outline.redirect {
+        HTTPRedirectPolicy::None => {}
+        HTTPRedirectPolicy::Follow => args.push("--follow".to_string()),
+        HTTPRedirectPolicy::Limit { max } => args.push(format!("--follow --max-redirects {}", max)),
+    }
+    if let Some((username, password)) = outline.basic.as_ref().or(outline.digest.as_ref()) {
+        args.push(format!(
+            "--auth {}",
+            shell(&format!("{}:{}", username, password))
+        ));
+        if outline.digest.is_some() {
+            args.push("--auth-type digest".to_string());
+        }
+    }
+    let tls = &outline.tls;
+    if tls.accept_invalid_certs {
+        args.push("--verify no".to_string());
+    } else if let Some(ca_file) = tls.ca_files.first() {
+        args.push(format!("--verify {}", shell(ca_file)));
+        if tls.ca_files.len() > 1 {
+            warnings.push("HTTPie takes a single CA bundle, the first one is used".to_string());
+        }
+    }
+    match &tls.identity {
+        Some(HTTPClientIdentity::Pem { cert, key }) => {
+            args.push(format!("--cert {}", shell(cert)));
+            if let Some(key) = key.as_deref().filter(|k| !k.is_empty()) {
+                args.push(format!("--cert-key {}", shell(key)));
+            }
+        }
+        Some(HTTPClientIdentity::Pkcs12 { .. }) => {
+            warnings.push("HTTPie does not support PKCS#12 client certificates".to_string())
+        }
+        None => {}
+    }
+    if tls.min_version.is_some() {
+        warnings.push("The minimum TLS version is not exported to HTTPie".to_string());
+    }
+    if let Some(HTTPProxySettings::Manual(proxy)) = &outline.proxy {
+        let mut proxy_url = proxy.url.clone();
+        if let (Some(username), Ok(mut url)) = (&proxy.username, Url::parse(&proxy.url)) {
+            let _ = url.set_username(username);
+            let _ = url.set_password(proxy.password.as_deref());
+            proxy_url = url.to_string();
+        }
+        for scheme in ["http", "https"] {
+            args.push(format!(
+                "--proxy {}",
+                shell(&format!("{}:{}", scheme, proxy_url))
+            ));
+        }
+    }
+    args.push(shell(&outline.method));
+    args.push(shell(&outline.url));
+    for (key, value) in &outline.headers {
+        args.push(shell(&format!("{}:{}", key, value)));
+    }
+    match &outline.body {
+        Body::None => {}
+        Body::Text(body) => args.push(format!("--raw {}", shell(body))),
+        Body::Form(params) => {
+            for (key, value) in params {
+                args.push(shell(&format!("{}={}", key, value)));
+            }
+        }
+        Body::Multipart(parts) => {
+            for part in parts {
+                args.push(match &part.file {
+                    Some(file) => {
+                        let mut field = format!("{}@{}", part.name, file);
+                        if let Some(content_type) = &part.content_type {
+                            field.push_str(&format!(";type={}", content_type));
+                        }
+                        shell(&field)
+                    }
+                    None => shell(&format!("{}={}", part.name, part.value)),
+                });
+            }
+        }
+    }
+    args.join(" \\\n  ")
+}
+
+fn python(outline: &Outline) -> String {
+    let mut imports = vec!["import requests".to_string()];
+    let mut args = vec![quoted(&outline.method), quoted(&outline.url)];
+    if !outline.headers.is_empty() {
+        let headers: Vec<String> = outline
+            .headers
+            .iter()
+            .map(|(k, v)| format!("        {}: {},", quoted(k), quoted(v)))
+            .collect();
+        args.push(format!("headers={{\n{}\n    }}", headers.join("\n")));
+    }
+    match &outline.body {
+        Body::None => {}
+        Body::Text(body) => args.push(format!("data={}", quoted(body))),
+        Body::Form(params) => {
+            let params: Vec<String> = params
+                .iter()
+                .map(|(k, v)| format!("        ({}, {}),", quoted(k), quoted(v)))
+                .collect();
+            args.push(format!("data=[\n{}\n    ]", params.join("\n")));
+        }
+        Body::Multipart(parts) => {
+            let files: Vec<String> = parts
+                .iter()
+                .map(|part| {
+                    let mut value = match &part.file {
+                        Some(file) => {
+                            let file_name = part.file_name.clone().unwrap_or_else(|| {
+                                file.rsplit(['/', '\\']).next().unwrap_or(file).to_string()
+                            });
+                            format!("{}, open({}, \"rb\")", quoted(&file_name), quoted(file))
+                        }
+                        None => format!("None, {}", quoted(&part.value)),
+                    };
+                    if let Some(content_type) = &part.content_type {
+                        value.push_str(&format!(", {}", quoted(content_type)));
+                    }
+                    format!("        ({}, ({})),", quoted(&part.name), value)
+                })
+                .collect();
+            args.push(format!("files=[\n{}\n    ]", files.join("\n")));
+        }
+    }
+    if let Some((username, password)) = &outline.basic {
+        args.push(format!("auth=({}, {})", quoted(username), quoted(password)));
+    }
+    if let Some((username, password)) = &outline.digest {
+        imports.push("from requests.auth import HTTPDigestAuth".to_string());
+        args.push(format!(
+            "auth=HTTPDigestAuth({}, {})",
+            quoted(username),
+            quoted(password)
+        ));
+    }
+    let mut code = imports.join("\n");
+    code.push_str("\n\n");
+    if let HTTPRedirectPolicy::Limit { max } = outline.redirect {
+        code.push_str(&format!(
+            "session = requests.Session()\nsession.max_redirects = {}\n\
+             response = session.request(\n",
+            max
+        ));
+    } else {
+        code.push_str("response = requests.request(\n");
+    }
+    if let HTTPRedirectPolicy::None = outline.redirect {
+        args.push("allow_redirects=False".to_string());
+    }
+    for arg in args {
+        code.push_str(&format!("    {},\n", arg));
+    }
+    code.push_str(")\nprint(response.status_code)\nprint(response.text)\n");
+    code
+}
+
+fn javascript(outline: &Outline) -> String {
+    let mut code = String::new();
+    let mut options = vec![format!("method: {}", quoted(&outline.method))];
+    if !outline.headers.is_empty() {
+        let headers: Vec<String> = outline
+            .headers
+            .iter()
+            .map(|(k, v)| format!("    {}: {},", quoted(k), quoted(v)))
+            .collect();
+        options.push(format!("headers: {{\n{}\n  }}", headers.join("\n")));
+    }
+    match &outline.body {
+        Body::None => {}
+        Body::Text(body) => options.push(format!("body: {}", quoted(body))),
+        Body::Form(params) => {
+            let params: Vec<String> = params
+                .iter()
+                .map(|(k, v)| format!("    [{}, {}],", quoted(k), quoted(v)))
+                .collect();
+            options.push(format!(
+                "body: new URLSearchParams([\n{}\n  ])",
+                params.join("\n")
+            ));
+        }
+        Body::Multipart(parts) => {
+            if parts.iter().any(|p| p.file.is_some()) {
+                code.push_str("import { openAsBlob } from \"node:fs\";\n\n");
+            }
+            code.push_str("const form = new FormData();\n");
+            for part in parts {
+                match &part.file {
+                    Some(file) => {
+                        let file_name = match &part.file_name {
+                            Some(file_name) => format!(", {}", quoted(file_name)),
+                            None => String::new(),
+                        };
+                        code.push_str(&format!(
+                            "form.append({}, await openAsBlob({}){});\n",
+                            quoted(&part.name),
+                            quoted(file),
+                            file_name
+                        ));
+                    }
+                    None => code.push_str(&format!(
+                        "form.append({}, {});\n",
+                        quoted(&part.name),
+                        quoted(&part.value)
+                    )),
+                }
+            }
+            code.push('\n');
+            options.push("body: form".to_string());
+        }
+    }
+    match outline.redirect {
+        HTTPRedirectPolicy::None => options.push("redirect: \"manual\"".to_string()),
+        HTTPRedirectPolicy::Limit { .. } | HTTPRedirectPolicy::Follow => {}
+    }
+    code.push_str(&format!(
+        "const response = await fetch({}, {{\n",
+        quoted(&outline.url)
+    ));
+    for option in options {
+        code.push_str(&format!("  {},\n", option));
+    }
+    code.push_str("});\nconsole.log(response.status);\nconsole.log(await response.text());\n");
+    code
+}
+
+fn go(outline: &Outline) -> String {
+    let mut imports: BTreeSet<&str> = ["fmt", "io", "net/http"].into_iter().collect();
+    let mut body = Vec::new();
+    let reader = match &outline.body {
+        Body::None => "nil".to_string(),
+        Body::Text(text) => {
+            imports.insert("strings");
+            format!("strings.NewReader({})", quoted(text))
+        }
+        Body::Form(params) => {
+            imports.extend(["strings", "net/url"]);
+            body.push("form := url.Values{}".to_string());
+            for (k, v) in params {
+                body.push(format!("form.Add({}, {})", quoted(k), quoted(v)));
+            }
+            "strings.NewReader(form.Encode())".to_string()
+        }
+        Body::Multipart(parts) => {
+            imports.extend(["bytes", "mime/multipart"]);
+            body.push("body := &bytes.Buffer{}".to_string());
+            body.push("writer := multipart.NewWriter(body)".to_string());
+            for part in parts {
+                match &part.file {
+                    Some(file) => {
+                        imports.insert("os");
+                        let file_name = match &part.file_name {
+                            Some(file_name) => quoted(file_name),
+                            None => {
+                                imports.insert("path/filepath");
+                                format!("filepath.Base({})", quoted(file))
+                            }
+                        };
+                        body.push("{".to_string());
+                        body.push(format!("\tfile, err := os.Open({})", quoted(file)));
+                        body.push("\tif err != nil {\n\t\tpanic(err)\n\t}".to_string());
+                        body.push("\tdefer file.Close()".to_string());
+                        body.push(format!(
+                            "\tpart, err := writer.CreateFormFile({}, {})",
+                            quoted(&part.name),
+                            file_name
+                        ));
+                        body.push("\tif err != nil {\n\t\tpanic(err)\n\t}".to_string());
+                        body.push(
+                            "\tif _, err := io.Copy(part, file); err != nil {\n\t\tpanic(err)\n\t}"
+                                .to_string(),
+                        );
+                        body.push("}".to_string());
+                    }
+                    None => body.push(format!(
+                        "writer.WriteField({}, {})",
+                        quoted(&part.name),
+                        quoted(&part.value)
+                    )),
+                }
+            }
+            body.push("writer.Close()".to_string());
+            "body".to_string()
+        }
+    };
+    body.push(format!(
+        "req, err := http.NewRequest({}, {}, {})",
+        quoted(&outline.method),
+        quoted(&outline.url),
+        reader
+    ));
+    body.push("if err != nil {\n\tpanic(err)\n}".to_string());
+    for (k, v) in &outline.headers {
+        body.push(format!("req.Header.Add({}, {})", quoted(k), quoted(v)));
+    }
+    match &outline.body {
+        Body::Form(_) => body.push(
+            "req.Header.Set(\"Content-Type\", \"application/x-www-form-urlencoded\")".to_string(),
+        ),
+        Body::Multipart(_) => {
+            body.push("req.Header.Set(\"Content-Type\", writer.FormDataContentType())".to_string())
+        }
+        Body::None | Body::Text(_) => {}
+    }
+    if let Some((username, password)) = &outline.basic {
+        body.push(format!(
+            "req.SetBasicAuth({}, {})",
+            quoted(username),
+            quoted(password)
+        ));
+    }
+    body.push(String::new());
+    let check_redirect = match outline.redirect {
+        HTTPRedirectPolicy::Follow => None,
+        HTTPRedirectPolicy::None => Some("return http.ErrUseLastResponse".to_string()),
+        HTTPRedirectPolicy::Limit { max } => {
+            imports.insert("errors");
+            Some(format!(
+                "if len(via) >= {} {{\n\t\t\treturn errors.New(\"stopped after {} redirects\")\n\
+                 \t\t}}\n\t\treturn nil",
+                max, max
+            ))
+        }
+    };
+    match check_redirect {
+        Some(check) => body.push(format!(
+            "client := &http.Client{{\n\tCheckRedirect: \
+             func(req *http.Request, via []*http.Request) error {{\n\t\t{}\n\t}},\n}}",
+            check
+        )),
+        None => body.push("client := &http.Client{}".to_string()),
+    }
+    body.push("res, err := client.Do(req)".to_string());
+    body.push("if err != nil {\n\tpanic(err)\n}".to_string());
+    body.push("defer res.Body.Close()".to_string());
+    body.push("data, err := io.ReadAll(res.Body)".to_string());
+    body.push("if err != nil {\n\tpanic(err)\n}".to_string());
+    body.push("fmt.Println(res.Status)".to_string());
+    body.push("fmt.Println(string(data))".to_string());
+
+    let imports: Vec<String> = imports.iter().map(|i| format!("\t{}", quoted(i))).collect();
+    let body: Vec<String> = body
+        .iter()
+        .flat_map(|statement| statement.split('\n'))
+        .map(|line| match line {
+            "" => String::new(),
+            line => format!("\t{}", line),
+        })
+        .collect();
+    format!(
+        "package main\n\nimport (\n{}\n)\n\nfunc main() {{\n{}\n}}\n",
+        imports.join("\n"),
+        body.join("\n")
+    )
+}
+
+fn rust(outline: &Outline) -> String {
+    let mut code = vec![
+        "#[tokio::main]".to_string(),
+        "async fn main() -> Result<(), Box<dyn std::error::Error>> {".to_string(),
+    ];
+    let policy = match outline.redirect {
+        HTTPRedirectPolicy::Follow => None,
+        HTTPRedirectPolicy::None => Some("reqwest::redirect::Policy::none()".to_string()),
+        HTTPRedirectPolicy::Limit { max } => {
+            Some(format!("reqwest::redirect::Policy::limited({})", max))
+        }
+    };
+    let mut builder: Vec<String> = policy
+        .map(|policy| format!("        .redirect({})", policy))
+        .into_iter()
+        .collect();
+    match outline.http_version {
+        HTTPVersion::Negotiate => {}
+        HTTPVersion::Http1 => builder.push("        .http1_only()".to_string()),
+        HTTPVersion::Http2 => builder.push("        .http2_prior_knowledge()".to_string()),
+    }
+    if builder.is_empty() {
+        code.push("    let client = reqwest::Client::new();".to_string());
+    } else {
+        code.push(format!(
+            "    let client = reqwest::Client::builder()\n{}\n        .build()?;",
+            builder.join("\n")
+        ));
+    }
+    if let Body::Multipart(parts) = &outline.body {
+        let mut form = vec!["    let form = reqwest::multipart::Form::new()".to_string()];
+        for part in parts {
+            form.push(match &part.file {
+                Some(file) => format!(
+                    "        .file({}, {})\n        .await?",
+                    rust_quoted(&part.name),
+                    rust_quoted(file)
+                ),
+                None => format!(
+                    "        .text({}, {})",
+                    rust_quoted(&part.name),
+                    rust_quoted(&part.value)
+                ),
+            });
+        }
+        code.push(format!("{};", form.join("\n")));
+    }
+
+    let method = match outline.method.as_str() {
+        method @ ("GET" | "POST" | "PUT" | "DELETE" | "HEAD" | "OPTIONS" | "CONNECT" | "PATCH"
+        | "TRACE") => format!("reqwest::Method::{}", method),
+        method => format!(
+            "reqwest::Method::from_bytes(b\"{}\")?",
+            method.as_bytes().escape_ascii()
+        ),
+    };
+    let mut chain = vec![format!(
+        "        .request({}, {})",
+        method,
+        rust_quoted(&outline.url)
+    )];
+    for (k, v) in &outline.headers {
+        chain.push(format!(
+            "        .header({}, {})",
+            rust_quoted(k),
+            rust_quoted(v)
+        ));
+    }
+    if let Some((username, password)) = &outline.basic {
+        chain.push(format!(
+            "        .basic_auth({}, Some({}))",
+            rust_quoted(username),
+            rust_quoted(password)
+        ));
+    }
+    match &outline.body {
+        Body::None => {}
+        Body::Text(body) => chain.push(format!("        .body({})", rust_quoted(body))),
+        Body::Form(params) => {
+            let params: Vec<String> = params
+                .iter()
+                .map(|(k, v)| format!("({}, {})", rust_quoted(k), rust_quoted(v)))
+                .collect();
+            chain.push(format!("        .form(&[{}])", params.join(", ")));
+        }
+        Body::Multipart(_) => chain.push("        .multipart(form)".to_string()),
+    }
+    code.push(format!(
+        "    let response = client\n{}\n        .send()\n        .await?;",
+        chain.join("\n")
+    ));
+    code.push("    println!(\"{}\", response.status());".to_string());
+    code.push("    println!(\"{}\", response.text().await?);".to_string());
+    code.push("    Ok(())".to_string());
+    code.push("}".to_string());
+    let mut code = code.join("\n");
+    code.push('\n');
+    code
+}
+
+pub mod commands {
+    use super::*;
+    use tauri::command;
+
+    /// Generates code sending `req`, with its placeholders resolved. Secret variables
+    /// are left as placeholders unless `include_secrets` is set.
+    #[command(async)]
+    pub async fn http_export(
+        mut req: HTTPRequest,
+        format: HTTPExportFormat,
+        include_secrets: Option<bool>,
+    ) -> ApiResult<HTTPExport> {
+        let variables = match req.environment.take() {
+            Some(name) => {
+                let environment = HTTPEnvironment::load(&name)?;
+                if include_secrets.unwrap_or(false) {
+                    Variables::from(&environment)
+                } else {
+                    Variables::without_secrets(&environment)
+                }
+            }
+            None => Variables::default(),
+        };
+        req.resolve_variables(&variables)?;
+        export(&req, format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::http::HTTPRequestKVParam;
+
+    fn request() -> HTTPRequest {
+        HTTPRequest {
+            method: "post".to_string(),
+            url: "https://api.example.com/notes".to_string(),
+            body: "{\"text\":\"it's \\\"quoted\\\"\"}".to_string(),
+            content_type: "application/json".to_string(),
+            headers: vec![HTTPRequestKVParam {
+                key: "X-Trace".to_string(),
+                value: "a b".to_string(),
+                enabled: true,
+            }],
+            query: vec![HTTPRequestKVParam {
+                key: "draft".to_string(),
+                value: "true".to_string(),
+                enabled: true,
+            }],
+            auth: Some(HTTPAuth::Basic {
+                username: "me".to_string(),
+                password: "pw".to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_export_curl() {
+        let export = export(&request(), HTTPExportFormat::Curl).expect("export");
+        assert_eq!(
+            export.code,
+            [
+                "curl 'https://api.example.com/notes?draft=true'",
+                "-H 'X-Trace: a b'",
+                "-H 'Content-Type: application/json'",
+                "-u me:pw",
+                r#"--data-raw '{"text":"it'\''s \"quoted\""}'"#,
+                "-L",
+            ]
+            .join(" \\\n  ")
+        );
+        assert!(export.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_export_snippets() {
+        let python = export(&request(), HTTPExportFormat::Python).expect("python");
+        assert!(python
+            .code
+            .contains(r#"data="{\"text\":\"it's \\\"quoted\\\"\"}","#));
+        assert!(python.code.contains(r#"auth=("me", "pw"),"#));
+
+        let javascript = export(&request(), HTTPExportFormat::JavaScript).expect("javascript");
+        assert!(javascript
+            .code
+            .contains(r#""Authorization": "Basic bWU6cHc=","#));
+
+        let go = export(&request(), HTTPExportFormat::Go).expect("go");
+        assert!(go.code.contains("\t\"strings\"\n"));
+        assert!(go.code.contains("\treq.SetBasicAuth(\"me\", \"pw\")\n"));
+
+        let rust = export(&request(), HTTPExportFormat::Rust).expect("rust");
+        assert!(rust.code.contains(".request(reqwest::Method::POST, "));
+
+        let mut custom = request();
+        custom.method = "PRÜF\"".to_string();
+        custom.redirect = HTTPRedirectPolicy::Limit { max: 3 };
+        custom.http_version = HTTPVersion::Http2;
+        let rust = export(&custom, HTTPExportFormat::Rust).expect("rust");
+        assert!(rust
+            .code
+            .contains(r#"reqwest::Method::from_bytes(b"PR\xc3\x9cF\"")?"#));
+        assert!(rust.code.contains(
+            ".redirect(reqwest::redirect::Policy::limited(3))\n        .http2_prior_knowledge()"
+        ));
+        assert!(rust.warnings.is_empty());
+        let javascript = export(&custom, HTTPExportFormat::JavaScript).expect("javascript");
+        assert_eq!(javascript.warnings.len(), 2);
+
+        let mut aws = request();
+        aws.auth = Some(HTTPAuth::AwsSigV4 {
+            profile: "default".to_string(),
+            region: None,
+            service: "execute-api".to_string(),
+        });
+        let httpie = export(&aws, HTTPExportFormat::Httpie).expect("httpie");
+        assert!(httpie.code.starts_with("http \\\n  --follow \\\n  POST"));
+        assert_eq!(
+            httpie.warnings,
+            vec!["AWS SigV4 signing is not exported".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_export_go_multipart() {
+        let part = |file_name: Option<&str>| HTTPMultipartPart {
+            name: "upload".to_string(),
+            value: String::new(),
+            file: Some("/tmp/report.csv".to_string()),
+            file_name: file_name.map(str::to_string),
+            content_type: None,
+            enabled: true,
+        };
+        let mut upload = request();
+        upload.body_type = HTTPBodyType::Multipart {
+            parts: vec![part(Some("march.csv"))],
+        };
+        let go = export(&upload, HTTPExportFormat::Go).expect("go");
+        assert!(go
+            .code
+            .contains("writer.CreateFormFile(\"upload\", \"march.csv\")"));
+        assert!(go.code.contains("\t\"os\"\n"));
+        assert!(!go.code.contains("path/filepath"));
+
+        upload.body_type = HTTPBodyType::Multipart {
+            parts: vec![part(None)],
+        };
+        let go = export(&upload, HTTPExportFormat::Go).expect("go");
+        assert!(go.code.contains("\t\"path/filepath\"\n"));
+        assert!(go.code.contains("filepath.Base(\"/tmp/report.csv\")"));
+    }
+}
@@ -0,0 +1,338 @@
+use crate::errors::{APIError, ApiResult};
+use crate::services::http::{self, HTTPBodyType, HTTPRequest, HTTPResponse};
+use crate::services::http_assert;
+use crate::store;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+
+const SCHEMA_DIR: &str = "graphql";
+
+/// The introspection query of the GraphQL reference implementation.
+pub const INTROSPECTION_QUERY: &str = r#"query IntrospectionQuery {
+  __schema {
+    queryType { name }
+    mutationType { name }
+    subscriptionType { name }
+    types { ...FullType }
+    directives {
+      name
+      description
+      locations
+      args { ...InputValue }
+    }
+  }
+}
+
+fragment FullType on __Type {
+  kind
+  name
+  description
+  fields(includeDeprecated: true) {
+    name
+    description
+    args { ...InputValue }
+    type { ...TypeRef }
+    isDeprecated
+    deprecationReason
+  }
+  inputFields { ...InputValue }
+  interfaces { ...TypeRef }
+  enumValues(includeDeprecated: true) {
+    name
+    description
+    isDeprecated
+    deprecationReason
+  }
+  possibleTypes { ...TypeRef }
+}
+
+fragment InputValue on __InputValue {
+  name
+  description
+  type { ...TypeRef }
+  defaultValue
+}
+
+fragment TypeRef on __Type {
+  kind
+  name
+  ofType {
+    kind
+    name
+    ofType {
+      kind
+      name
+      ofType {
+        kind
+        name
+        ofType {
+          kind
+          name
+          ofType {
+            kind
+            name
+            ofType {
+              kind
+              name
+              ofType { kind name }
+            }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
+/// A GraphQL response split into its `data` and `errors` members.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPGraphQLResult {
+    pub data: Option<Value>,
+    pub errors: Vec<HTTPGraphQLError>,
+    #[serde(default)]
+    pub extensions: Option<Value>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPGraphQLError {
+    pub message: String,
+    /// Where in the query the error happened.
+    #[serde(default)]
+    pub locations: Vec<HTTPGraphQLLocation>,
+    /// Field names and list indexes leading to the value that failed.
+    #[serde(default)]
+    pub path: Vec<Value>,
+    #[serde(default)]
+    pub extensions: Option<Value>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPGraphQLLocation {
+    pub line: u32,
+    pub column: u32,
+}
+
+/// The introspected schema of an endpoint, saved as `graphql/<sha256 of url>.json`.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPGraphQLSchema {
+    pub url: String,
+    /// Unix timestamp in ms.
+    pub fetched: i64,
+    /// The `__schema` member of the introspection result.
+    pub schema: Value,
+}
+
+impl HTTPGraphQLSchema {
+    /// The schema saved for `url`, None when it was never introspected.
+    pub fn cached(url: &str) -> ApiResult<Option<HTTPGraphQLSchema>> {
+        let path = Self::path(url)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+    }
+
+    fn save(&self) -> ApiResult<()> {
+        fs::write(Self::path(&self.url)?, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    fn path(url: &str) -> ApiResult<PathBuf> {
+        let name = format!("{:x}", Sha256::digest(url.as_bytes()));
+        Ok(store::dir(SCHEMA_DIR)?.join(store::file_name(&name, "json")?))
+    }
+}
+
+/// The standard `application/json` POST body: `query`, `variables` when given and
+/// `operationName` when set. `variables` is the JSON text of an object.
+pub fn body(query: &str, variables: &str, operation_name: Option<&str>) -> ApiResult<String> {
+    let mut body = Map::new();
+    body.insert("query".to_string(), Value::from(query));
+    if !variables.trim().is_empty() {
+        let variables: Value = serde_json::from_str(variables)?;
+        if !variables.is_object() {
+            return Err(APIError::Parser(
+                "GraphQL variables must be a JSON object".to_string(),
+            ));
+        }
+        body.insert("variables".to_string(), variables);
+    }
+    if let Some(name) = operation_name.filter(|name| !name.is_empty()) {
+        body.insert("operationName".to_string(), Value::from(name));
+    }
+    Ok(Value::Object(body).to_string())
+}
+
+/// Splits the body of `response` into `data` and `errors`, None when it is not a
+/// GraphQL response.
+pub fn split(response: &HTTPResponse) -> Option<HTTPGraphQLResult> {
+    let body: Value = serde_json::from_str(&http_assert::body_text(response)).ok()?;
+    let body = body.as_object()?;
+    if !body.contains_key("data") && !body.contains_key("errors") {
+        return None;
+    }
+    Some(HTTPGraphQLResult {
+        data: body.get("data").filter(|data| !data.is_null()).cloned(),
+        errors: body
+            .get("errors")
+            .and_then(|errors| serde_json::from_value(errors.clone()).ok())
+            .unwrap_or_default(),
+        extensions: body.get("extensions").cloned(),
+    })
+}
+
+/// Fetches the schema of the endpoint `request` is sent to with its headers and auth, or
+/// returns the one saved earlier unless `refresh` is set.
+pub async fn introspect(mut request: HTTPRequest, refresh: bool) -> ApiResult<HTTPGraphQLSchema> {
+    request.resolve()?;
+    if !refresh {
+        if let Some(schema) = HTTPGraphQLSchema::cached(&request.url)? {
+            return Ok(schema);
+        }
+    }
+    let url = request.url.clone();
+    request.id = None;
+    request.method = "POST".to_string();
+    request.stream = false;
+    request.body = String::new();
+    request.body_type = HTTPBodyType::GraphQL {
+        query: INTROSPECTION_QUERY.to_string(),
+        variables: String::new(),
+        operation_name: Some("IntrospectionQuery".to_string()),
+    };
+    request.assertions = vec![];
+    let response = http::request(request, None, None).await?;
+
+    let result = split(&response).ok_or_else(|| {
+        APIError::Http(format!(
+            "{} answered {} without a GraphQL response",
+            url, response.status
+        ))
+    })?;
+    if !result.errors.is_empty() {
+        let messages: Vec<_> = result.errors.iter().map(|e| e.message.as_str()).collect();
+        return Err(APIError::Http(format!(
+            "Introspection failed: {}",
+            messages.join("; ")
+        )));
+    }
+    let schema = result
+        .data
+        .and_then(|mut data| data.get_mut("__schema").map(Value::take))
+        .ok_or_else(|| APIError::Http("Introspection returned no __schema".to_string()))?;
+    let schema = HTTPGraphQLSchema {
+        url,
+        fetched: chrono::Utc::now().timestamp_millis(),
+        schema,
+    };
+    schema.save()?;
+    Ok(schema)
+}
+
+pub mod commands {
+    use super::*;
+    use tauri::command;
+
+    #[command(async)]
+    pub async fn http_graphql_schema(
+        req: HTTPRequest,
+        refresh: Option<bool>,
+    ) -> ApiResult<HTTPGraphQLSchema> {
+        log::info!("Introspecting GraphQL schema of {}", req.url);
+        introspect(req, refresh.unwrap_or(false)).await
+    }
+
+    #[command(async)]
+    pub async fn http_graphql_cached_schema(url: String) -> ApiResult<Option<HTTPGraphQLSchema>> {
+        HTTPGraphQLSchema::cached(&url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::http_proxy::HTTPProxySettings;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_body_and_split() {
+        let query = "query Me($id: ID) { me(id: $id) { name } }";
+        let sent = body(query, r#"{"id": 1}"#, Some("Me")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&sent).unwrap(),
+            json!({"query": query, "variables": {"id": 1}, "operationName": "Me"})
+        );
+        let sent = body("{ me { id } }", " ", None).unwrap();
+        assert_eq!(sent, r#"{"query":"{ me { id } }"}"#);
+        assert!(body("{ me { id } }", "[1]", None).is_err());
+
+        let result = split(&HTTPResponse::json(
+            r#"{"data": {"me": null}, "errors": [{"message": "denied",
+                "locations": [{"line": 1, "column": 3}], "path": ["me"]}]}"#,
+        ))
+        .unwrap();
+        assert_eq!(result.data, Some(json!({"me": null})));
+        assert_eq!(result.errors[0].message, "denied");
+        assert_eq!(
+            result.errors[0].locations,
+            [HTTPGraphQLLocation { line: 1, column: 3 }]
+        );
+        assert_eq!(result.errors[0].path, [json!("me")]);
+        assert!(split(&HTTPResponse::json(r#"{"id": 1}"#)).is_none());
+        assert!(split(&HTTPResponse::json("not json")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_introspection_is_cached() {
+        store::init_for_tests();
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicU64::new(0));
+        let counter = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counter.fetch_add(1, Ordering::Relaxed);
+                let mut buf = vec![0u8; 16384];
+                let _ = socket.read(&mut buf).await;
+                let body = r#"{"data": {"__schema": {"queryType": {"name": "Query"}}}}"#;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                    Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let url = format!("http://{}/graphql", addr);
+        let request = HTTPRequest {
+            url: url.clone(),
+            proxy: Some(HTTPProxySettings::Direct),
+            ..Default::default()
+        };
+        assert!(HTTPGraphQLSchema::cached(&url).unwrap().is_none());
+        let schema = introspect(request.clone(), false).await.unwrap();
+        assert_eq!(schema.schema["queryType"]["name"], "Query");
+        let cached = HTTPGraphQLSchema::cached(&url).unwrap().unwrap();
+        assert_eq!(cached.fetched, schema.fetched);
+        assert_eq!(
+            introspect(request.clone(), false).await.unwrap().fetched,
+            schema.fetched
+        );
+        assert_eq!(requests.load(Ordering::Relaxed), 1);
+        introspect(request, true).await.unwrap();
+        assert_eq!(requests.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,526 @@
+use crate::errors::{APIError, ApiResult};
+use crate::services::http::{HTTPRequest, HTTPResponse};
+use crate::services::http_env::{HTTPEnvironment, Variables};
+use crate::store;
+use once_cell::sync::{Lazy, OnceCell};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+const HISTORY_DIR: &str = "history";
+const SETTINGS_DIR: &str = "settings";
+
+static HISTORY_DB: OnceCell<Mutex<Connection>> = OnceCell::new();
+
+/// Loaded from `settings/history.json` on first use.
+static HISTORY_SETTINGS: Lazy<RwLock<Option<HTTPHistorySettings>>> =
+    Lazy::new(|| RwLock::new(None));
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPHistorySettings {
+    pub enabled: bool,
+    /// Oldest entries are dropped past this count.
+    pub max_entries: u32,
+    /// Entries older than this are dropped, 0 keeps them forever.
+    pub retention_days: u32,
+    /// Response bodies are cut to this many bytes when recorded.
+    pub max_body_size: usize,
+}
+
+impl Default for HTTPHistorySettings {
+    fn default() -> Self {
+        HTTPHistorySettings {
+            enabled: true,
+            max_entries: 1000,
+            retention_days: 30,
+            max_body_size: 1024 * 1024,
+        }
+    }
+}
+
+/// A recorded exchange, as listed.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPHistorySummary {
+    pub id: i64,
+    /// Unix timestamp in milliseconds of when the request was sent.
+    pub timestamp: i64,
+    /// Milliseconds until the response completed or the request failed.
+    pub duration: u32,
+    pub method: String,
+    pub url: String,
+    /// `None` when no response was received.
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPHistoryEntry {
+    #[serde(flatten)]
+    pub summary: HTTPHistorySummary,
+    /// The request as sent but for the placeholders of secret variables, resolved from
+    /// its environment when replayed.
+    pub request: HTTPRequest,
+    pub response: Option<HTTPResponse>,
+    /// The response body was cut to [`HTTPHistorySettings::max_body_size`].
+    pub truncated: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPHistoryFilter {
+    /// Part of the url, ignoring case.
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(default)]
+    pub status: Option<u16>,
+    /// Defaults to 100.
+    #[serde(default)]
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub offset: u32,
+}
+
+impl HTTPHistorySettings {
+    pub fn get() -> ApiResult<HTTPHistorySettings> {
+        if let Some(settings) = HISTORY_SETTINGS
+            .read()
+            .map_err(|e| APIError::General(e.to_string()))?
+            .as_ref()
+        {
+            return Ok(settings.clone());
+        }
+        let path = Self::path()?;
+        let settings = if path.exists() {
+            serde_json::from_str(&fs::read_to_string(&path)?)?
+        } else {
+            HTTPHistorySettings::default()
+        };
+        Self::cache(settings.clone())?;
+        Ok(settings)
+    }
+
+    pub fn set(settings: HTTPHistorySettings) -> ApiResult<()> {
+        fs::write(Self::path()?, serde_json::to_string_pretty(&settings)?)?;
+        Self::cache(settings)?;
+        with_db(prune)
+    }
+
+    fn cache(settings: HTTPHistorySettings) -> ApiResult<()> {
+        let mut cached = HISTORY_SETTINGS
+            .write()
+            .map_err(|e| APIError::General(e.to_string()))?;
+        *cached = Some(settings);
+        Ok(())
+    }
+
+    fn path() -> ApiResult<PathBuf> {
+        Ok(store::dir(SETTINGS_DIR)?.join("history.json"))
+    }
+}
+
+fn with_db<T>(run: impl FnOnce(&Connection) -> ApiResult<T>) -> ApiResult<T> {
+    let db = HISTORY_DB.get_or_try_init(|| -> ApiResult<_> {
+        let db = Connection::open(store::dir(HISTORY_DIR)?.join("history.db"))?;
+        db.execute_batch(
+            "CREATE TABLE IF NOT EXISTS entries (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                duration INTEGER NOT NULL,
+                method TEXT NOT NULL,
+                url TEXT NOT NULL,
+                status INTEGER,
+                error TEXT,
+                request TEXT NOT NULL,
+                response TEXT,
+                truncated INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE INDEX IF NOT EXISTS entries_timestamp ON entries (timestamp);",
+        )?;
+        Ok(Mutex::new(db))
+    })?;
+    let db = db.lock().map_err(|e| APIError::General(e.to_string()))?;
+    run(&db)
+}
+
+/// Adds the outcome of sending `request` to the history, unless it is disabled.
+/// `request` is stored as given, see [`HTTPRequest::resolve_keeping_secrets`]. The values
+/// of secret variables are masked wherever they show up in the text of the response or
+/// in the error, base64 encoded bodies are stored as they are.
+pub fn record(
+    request: &HTTPRequest,
+    result: &ApiResult<HTTPResponse>,
+    duration: Duration,
+) -> ApiResult<()> {
+    let settings = HTTPHistorySettings::get()?;
+    if !settings.enabled {
+        return Ok(());
+    }
+    let secrets = match &request.environment {
+        Some(name) => Variables::secrets(&HTTPEnvironment::load(name)?),
+        None => Variables::default(),
+    };
+    let timestamp = chrono::Utc::now().timestamp_millis() - duration.as_millis() as i64;
+    let (status, error, response, truncated) = match result {
+        Ok(response) => {
+            let mut response = response.clone();
+            mask_response(&mut response, &secrets);
+            let (response, truncated) = stored_response(response, settings.max_body_size);
+            (Some(response.status), None, Some(response), truncated)
+        }
+        Err(e) => (None, Some(secrets.mask(&e.to_string())), None, false),
+    };
+    let duration = response
+        .as_ref()
+        .map_or(duration.as_millis() as u32, |r| r.latency);
+    with_db(|db| {
+        db.execute(
+            "INSERT INTO entries
+                (timestamp, duration, method, url, status, error, request, response, truncated)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                timestamp,
+                duration,
+                request.method.to_uppercase(),
+                request.url,
+                status,
+                error,
+                serde_json::to_string(request)?,
+                response.as_ref().map(serde_json::to_string).transpose()?,
+                truncated,
+            ],
+        )?;
+        prune(db)
+    })
+}
+
+/// Puts the placeholders of `secrets` back in the url, headers, body, redirects, test
+/// results and GraphQL result of `response`.
+fn mask_response(response: &mut HTTPResponse, secrets: &Variables) {
+    let mask_headers = |headers: &mut HashMap<String, Vec<String>>| {
+        for value in headers.values_mut().flatten() {
+            *value = secrets.mask(value);
+        }
+    };
+    response.url = secrets.mask(&response.url);
+    mask_headers(&mut response.headers);
+    response.body = secrets.mask(&response.body);
+    for hop in &mut response.redirects {
+        hop.url = secrets.mask(&hop.url);
+        hop.location = secrets.mask(&hop.location);
+        mask_headers(&mut hop.headers);
+    }
+    if let Some(tests) = &mut response.tests {
+        for result in &mut tests.results {
+            result.actual = result.actual.as_deref().map(|actual| secrets.mask(actual));
+            result.message = secrets.mask(&result.message);
+        }
+    }
+    if let Some(graphql) = &mut response.graphql {
+        for value in graphql.data.iter_mut().chain(&mut graphql.extensions) {
+            mask_json(value, secrets);
+        }
+        for error in &mut graphql.errors {
+            error.message = secrets.mask(&error.message);
+            for value in error.path.iter_mut().chain(&mut error.extensions) {
+                mask_json(value, secrets);
+            }
+        }
+    }
+}
+
+fn mask_json(value: &mut serde_json::Value, secrets: &Variables) {
+    match value {
+        serde_json::Value::String(text) => *text = secrets.mask(text),
+        serde_json::Value::Array(items) => {
+            for item in items {
+                mask_json(item, secrets);
+            }
+        }
+        serde_json::Value::Object(members) => {
+            for member in members.values_mut() {
+                mask_json(member, secrets);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `response` as stored in the history, its body cut to `max_body_size` bytes.
+fn stored_response(mut stored: HTTPResponse, max_body_size: usize) -> (HTTPResponse, bool) {
+    let mut truncated = false;
+    if stored.body.len() > max_body_size {
+        let mut end = max_body_size;
+        while !stored.body.is_char_boundary(end) {
+            end -= 1;
+        }
+        stored.body.truncate(end);
+        truncated = true;
+    }
+    if stored
+        .body_base64
+        .as_ref()
+        .is_some_and(|b| b.len() > max_body_size)
+    {
+        stored.body_base64 = None;
+        truncated = true;
+    }
+    (stored, truncated)
+}
+
+fn prune(db: &Connection) -> ApiResult<()> {
+    let settings = HTTPHistorySettings::get()?;
+    if settings.retention_days > 0 {
+        let oldest = chrono::Utc::now().timestamp_millis()
+            - i64::from(settings.retention_days) * 24 * 60 * 60 * 1000;
+        db.execute("DELETE FROM entries WHERE timestamp < ?1", [oldest])?;
+    }
+    db.execute(
+        "DELETE FROM entries WHERE id NOT IN
+            (SELECT id FROM entries ORDER BY id DESC LIMIT ?1)",
+        [settings.max_entries],
+    )?;
+    Ok(())
+}
+
+fn summary(row: &Row) -> rusqlite::Result<HTTPHistorySummary> {
+    Ok(HTTPHistorySummary {
+        id: row.get("id")?,
+        timestamp: row.get("timestamp")?,
+        duration: row.get("duration")?,
+        method: row.get("method")?,
+        url: row.get("url")?,
+        status: row.get("status")?,
+        error: row.get("error")?,
+    })
+}
+
+/// Entries matching `filter`, newest first.
+pub fn list(filter: &HTTPHistoryFilter) -> ApiResult<Vec<HTTPHistorySummary>> {
+    let mut sql = String::from(
+        "SELECT id, timestamp, duration, method, url, status, error FROM entries WHERE 1 = 1",
+    );
+    let mut values = Vec::new();
+    if let Some(url) = filter.url.as_ref().filter(|u| !u.is_empty()) {
+        sql.push_str(" AND instr(lower(url), lower(?)) > 0");
+        values.push(Value::Text(url.clone()));
+    }
+    if let Some(method) = filter.method.as_ref().filter(|m| !m.is_empty()) {
+        sql.push_str(" AND method = ?");
+        values.push(Value::Text(method.to_uppercase()));
+    }
+    if let Some(status) = filter.status {
+        sql.push_str(" AND status = ?");
+        values.push(Value::Integer(status.into()));
+    }
+    sql.push_str(" ORDER BY id DESC LIMIT ? OFFSET ?");
+    values.push(Value::Integer(filter.limit.unwrap_or(100).into()));
+    values.push(Value::Integer(filter.offset.into()));
+
+    with_db(|db| {
+        let mut statement = db.prepare(&sql)?;
+        let summaries = statement
+            .query_map(params_from_iter(values), summary)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(summaries)
+    })
+}
+
+pub fn entry(id: i64) -> ApiResult<HTTPHistoryEntry> {
+    with_db(|db| {
+        let entry = db
+            .query_row("SELECT * FROM entries WHERE id = ?1", [id], |row| {
+                let request: String = row.get("request")?;
+                let response: Option<String> = row.get("response")?;
+                Ok((summary(row)?, request, response, row.get("truncated")?))
+            })
+            .optional()?;
+        let (summary, request, response, truncated) =
+            entry.ok_or_else(|| APIError::General(format!("History entry {} not found", id)))?;
+        Ok(HTTPHistoryEntry {
+            summary,
+            request: serde_json::from_str(&request)?,
+            response: response.map(|r| serde_json::from_str(&r)).transpose()?,
+            truncated,
+        })
+    })
+}
+
+pub fn delete(id: i64) -> ApiResult<()> {
+    with_db(|db| {
+        db.execute("DELETE FROM entries WHERE id = ?1", [id])?;
+        Ok(())
+    })
+}
+
+pub fn clear() -> ApiResult<()> {
+    with_db(|db| {
+        db.execute("DELETE FROM entries", [])?;
+        Ok(())
+    })
+}
+
+pub mod commands {
+    use super::*;
+    use crate::services::http::commands::send_recorded;
+    use crate::services::http::RequestTimeout;
+    use tauri::{command, AppHandle};
+
+    #[command(async)]
+    pub async fn http_history(
+        filter: Option<HTTPHistoryFilter>,
+    ) -> ApiResult<Vec<HTTPHistorySummary>> {
+        list(&filter.unwrap_or_default())
+    }
+
+    #[command(async)]
+    pub async fn http_history_entry(id: i64) -> ApiResult<HTTPHistoryEntry> {
+        entry(id)
+    }
+
+    /// Sends the request of entry `id` again, recording it as a new entry. `request_id`
+    /// names the new request, needed when the entry streams its body.
+    #[command(async)]
+    pub async fn http_replay_history(
+        app: AppHandle,
+        id: i64,
+        request_id: Option<String>,
+        timeout: Option<RequestTimeout>,
+    ) -> ApiResult<HTTPResponse> {
+        log::info!("Replaying history entry {}", id);
+        let mut request = entry(id)?.request;
+        request.id = request_id;
+        send_recorded(app, request, timeout).await
+    }
+
+    #[command(async)]
+    pub async fn http_delete_history_entry(id: i64) -> ApiResult<()> {
+        delete(id)
+    }
+
+    #[command(async)]
+    pub async fn http_clear_history() -> ApiResult<()> {
+        log::info!("Clearing request history");
+        clear()
+    }
+
+    #[command(async)]
+    pub async fn http_history_settings() -> ApiResult<HTTPHistorySettings> {
+        HTTPHistorySettings::get()
+    }
+
+    #[command(async)]
+    pub async fn http_set_history_settings(settings: HTTPHistorySettings) -> ApiResult<()> {
+        log::info!("Setting history settings: {:?}", settings);
+        HTTPHistorySettings::set(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_filter() {
+        store::init_for_tests();
+        let request: HTTPRequest = serde_json::from_value(serde_json::json!({
+            "method": "post",
+            "url": "https://api.example.com/users",
+            "body": "{}",
+            "contentType": "application/json",
+            "headers": [],
+            "query": [],
+        }))
+        .expect("request");
+        let failed = Err(APIError::Http("connection refused".to_string()));
+        record(&request, &failed, Duration::from_millis(12)).expect("record");
+
+        let found = list(&HTTPHistoryFilter {
+            url: Some("EXAMPLE.com/users".to_string()),
+            method: Some("post".to_string()),
+            ..Default::default()
+        })
+        .expect("list");
+        let recorded = found
+            .iter()
+            .find(|s| s.url == request.url)
+            .expect("recorded");
+        assert_eq!(recorded.method, "POST");
+        assert_eq!(recorded.status, None);
+        assert_eq!(recorded.duration, 12);
+
+        let entry = entry(recorded.id).expect("entry");
+        assert_eq!(entry.request.body, "{}");
+        assert!(entry.response.is_none());
+        assert!(list(&HTTPHistoryFilter {
+            status: Some(200),
+            ..Default::default()
+        })
+        .expect("list")
+        .iter()
+        .all(|s| s.id != recorded.id));
+    }
+
+    #[test]
+    fn test_record_masks_secrets() {
+        store::init_for_tests();
+        let environment: HTTPEnvironment = serde_json::from_value(serde_json::json!({
+            "name": "history-secrets",
+            "variables": [
+                {"key": "host", "value": "secrets.example.com", "enabled": true},
+                {"key": "key", "value": "s3cr3t", "secret": true, "enabled": true},
+            ],
+        }))
+        .expect("environment");
+        environment.save().expect("save");
+        let mut sent: HTTPRequest = serde_json::from_value(serde_json::json!({
+            "method": "get",
+            "url": "https://{{host}}/?key={{key}}",
+            "body": "",
+            "contentType": "",
+            "headers": [],
+            "query": [],
+            "environment": "history-secrets",
+        }))
+        .expect("request");
+        let request = sent.resolve_keeping_secrets().expect("resolve");
+        assert_eq!(sent.url, "https://secrets.example.com/?key=s3cr3t");
+        let failed = Err(APIError::Http(
+            "error sending request for url (https://secrets.example.com/?key=s3cr3t)".to_string(),
+        ));
+        record(&request, &failed, Duration::from_millis(5)).expect("record");
+
+        let mut response = HTTPResponse::json(r#"{"key":"s3cr3t"}"#);
+        response.url = sent.url.clone();
+        response.headers = HashMap::from([("x-echo".to_string(), vec!["key=s3cr3t".to_string()])]);
+        record(&request, &Ok(response), Duration::from_millis(7)).expect("record");
+
+        let found = list(&HTTPHistoryFilter {
+            url: Some("secrets.example.com".to_string()),
+            ..Default::default()
+        })
+        .expect("list");
+        assert_eq!(found[0].url, "https://secrets.example.com/?key={{key}}");
+        let recorded = entry(found[0].id).expect("entry");
+        assert_eq!(recorded.request.url, found[0].url);
+        assert_eq!(
+            recorded.request.environment.as_deref(),
+            Some("history-secrets")
+        );
+        let response = recorded.response.expect("response");
+        assert_eq!(response.url, "https://secrets.example.com/?key={{key}}");
+        assert_eq!(response.headers["x-echo"], vec!["key={{key}}".to_string()]);
+        assert_eq!(response.body, r#"{"key":"{{key}}"}"#);
+        let failed = entry(found[1].id).expect("failed entry");
+        assert!(!failed.summary.error.unwrap().contains("s3cr3t"));
+    }
+}
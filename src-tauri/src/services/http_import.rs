@@ -0,0 +1,1612 @@
+use crate::errors::{APIError, ApiResult};
+use crate::services::http::{
+    HTTPBodyType, HTTPMultipartPart, HTTPRedirectPolicy, HTTPRequest, HTTPRequestKVParam,
+    HTTPVersion,
+};
+use crate::services::http_auth::HTTPAuth;
+use crate::services::http_collection::{HTTPCollectionItem, HTTPFolder, HTTPSavedRequest};
+use crate::services::http_env::HTTPVariable;
+use crate::services::http_proxy::{HTTPProxy, HTTPProxySettings};
+use crate::services::http_tls::{HTTPClientIdentity, HTTPTlsVersion};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, VecDeque};
+use url::form_urlencoded;
+
+/// Operations of an OpenAPI path item, in the order they are imported.
+const OPENAPI_METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum HTTPImportFormat {
+    Curl,
+    Har,
+    Postman,
+    OpenApi,
+}
+
+/// Requests read from another tool, ready to be saved as a collection.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPImport {
+    pub format: HTTPImportFormat,
+    pub collection: HTTPFolder,
+    /// Variables the requests refer to, like the server url of an OpenAPI document.
+    pub variables: Vec<HTTPVariable>,
+    /// What was found but could not be imported.
+    pub warnings: Vec<String>,
+}
+
+impl HTTPImportFormat {
+    /// Tells the format from the content, `None` when it is none of the supported ones.
+    pub fn detect(content: &str) -> Option<HTTPImportFormat> {
+        let trimmed = content.trim_start();
+        if trimmed.starts_with("curl ") || trimmed.starts_with("curl.exe ") {
+            return Some(HTTPImportFormat::Curl);
+        }
+        let doc = parse_document(content).ok()?;
+        if doc.pointer("/log/entries").is_some() {
+            return Some(HTTPImportFormat::Har);
+        }
+        let postman_schema = doc
+            .pointer("/info/schema")
+            .and_then(Value::as_str)
+            .is_some_and(|schema| schema.contains("getpostman.com"));
+        if postman_schema || doc.pointer("/info/_postman_id").is_some() {
+            return Some(HTTPImportFormat::Postman);
+        }
+        if doc.get("openapi").is_some() {
+            return Some(HTTPImportFormat::OpenApi);
+        }
+        None
+    }
+}
+
+/// Reads `content` as `format`, detecting the format when it is not given.
+pub fn import(content: &str, format: Option<HTTPImportFormat>) -> ApiResult<HTTPImport> {
+    let format = format
+        .or_else(|| HTTPImportFormat::detect(content))
+        .ok_or_else(|| APIError::Parser("Unrecognized import format".to_string()))?;
+    let mut import = HTTPImport {
+        format,
+        collection: HTTPFolder::default(),
+        variables: Vec::new(),
+        warnings: Vec::new(),
+    };
+    match format {
+        HTTPImportFormat::Curl => {
+            let request = parse_curl(content, &mut import.warnings)?;
+            import.collection.name = "cURL".to_string();
+            import
+                .collection
+                .items
+                .push(saved(request_name(&request), String::new(), request));
+        }
+        HTTPImportFormat::Har => {
+            import.collection = parse_har(&parse_document(content)?, &mut import.warnings)?;
+        }
+        HTTPImportFormat::Postman => parse_postman(&parse_document(content)?, &mut import)?,
+        HTTPImportFormat::OpenApi => parse_openapi(&parse_document(content)?, &mut import)?,
+    }
+    Ok(import)
+}
+
+/// Parses JSON, or YAML which OpenAPI documents are often written in.
+fn parse_document(content: &str) -> ApiResult<Value> {
+    match serde_json::from_str(content) {
+        Ok(doc) => Ok(doc),
+        Err(_) => Ok(serde_yaml::from_str(content)?),
+    }
+}
+
+fn kv(key: &str, value: &str) -> HTTPRequestKVParam {
+    HTTPRequestKVParam {
+        key: key.to_string(),
+        value: value.to_string(),
+        enabled: true,
+    }
+}
+
+fn saved(name: String, description: String, request: HTTPRequest) -> HTTPCollectionItem {
+    HTTPCollectionItem::Request(HTTPSavedRequest {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        description,
+        request,
+        extract: vec![],
+    })
+}
+
+fn folder(name: &str) -> HTTPFolder {
+    HTTPFolder {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        ..Default::default()
+    }
+}
+
+/// `METHOD /path`, used when the source has no name for a request.
+fn request_name(request: &HTTPRequest) -> String {
+    let path = match request.url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |i| &rest[i..]),
+        None => &request.url,
+    };
+    format!("{} {}", request.method, path)
+}
+
+/// Splits the query off `url` into params, leaving placeholders like `{{host}}` untouched.
+fn split_query(url: &str) -> (String, Vec<HTTPRequestKVParam>) {
+    let url = url.split('#').next().unwrap_or_default();
+    match url.split_once('?') {
+        Some((base, query)) => (
+            base.to_string(),
+            form_urlencoded::parse(query.as_bytes())
+                .map(|(k, v)| kv(&k, &v))
+                .collect(),
+        ),
+        None => (url.to_string(), Vec::new()),
+    }
+}
+
+/// Moves a `Content-Type` header to [`HTTPRequest::content_type`].
+fn take_content_type(request: &mut HTTPRequest) {
+    if let Some(index) = request
+        .headers
+        .iter()
+        .position(|h| h.enabled && h.key.eq_ignore_ascii_case("content-type"))
+    {
+        request.content_type = request.headers.remove(index).value;
+    }
+}
+
+fn is_json_type(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    mime.ends_with("/json") || mime.ends_with("+json")
+}
+
+/// Sends a body declared as JSON as [`HTTPBodyType::Json`] when it is valid JSON.
+fn json_body_type(request: &mut HTTPRequest) {
+    if is_json_type(&request.content_type) && serde_json::from_str::<Value>(&request.body).is_ok() {
+        request.body_type = HTTPBodyType::Json;
+    }
+}
+
+/// Splits a shell command line into words the way a POSIX shell does, including
+/// the `$'...'` quoting browsers use in "Copy as cURL".
+fn shell_words(command: &str) -> ApiResult<Vec<String>> {
+    let unterminated = || APIError::Parser("Unterminated quote in command".to_string());
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\n') | None => {}
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                }
+                Some(next) => {
+                    word.push(next);
+                    in_word = true;
+                }
+            },
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next().ok_or_else(unterminated)? {
+                        '\'' => break,
+                        c => word.push(c),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next().ok_or_else(unterminated)? {
+                        '"' => break,
+                        '\\' => match chars.next().ok_or_else(unterminated)? {
+                            '\n' => {}
+                            c @ ('"' | '\\' | '$' | '`') => word.push(c),
+                            c => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                        },
+                        c => word.push(c),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                in_word = true;
+                loop {
+                    match chars.next().ok_or_else(unterminated)? {
+                        '\'' => break,
+                        '\\' => {
+                            let escaped = chars.next().ok_or_else(unterminated)?;
+                            match escaped {
+                                'n' => word.push('\n'),
+                                'r' => word.push('\r'),
+                                't' => word.push('\t'),
+                                '0' => word.push('\0'),
+                                'x' | 'u' => {
+                                    let len = if escaped == 'x' { 2 } else { 4 };
+                                    let mut hex = String::new();
+                                    while hex.len() < len
+                                        && chars.peek().is_some_and(|c| c.is_ascii_hexdigit())
+                                    {
+                                        hex.extend(chars.next());
+                                    }
+                                    match u32::from_str_radix(&hex, 16)
+                                        .ok()
+                                        .and_then(char::from_u32)
+                                    {
+                                        Some(c) => word.push(c),
+                                        None => {
+                                            word.push('\\');
+                                            word.push(escaped);
+                                            word.push_str(&hex);
+                                        }
+                                    }
+                                }
+                                c @ ('\\' | '\'' | '"' | '?') => word.push(c),
+                                c => {
+                                    word.push('\\');
+                                    word.push(c);
+                                }
+                            }
+                        }
+                        c => word.push(c),
+                    }
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Short curl options followed by a value, which may be attached as in `-XPOST`.
+const CURL_SHORT_WITH_VALUE: &str = "XHdFuAebxUEoCmwrT";
+
+/// Options that only change what curl prints or how long it waits, not the request.
+const CURL_IGNORED: [&str; 14] = [
+    "-s",
+    "--silent",
+    "-S",
+    "--show-error",
+    "-v",
+    "--verbose",
+    "-i",
+    "--include",
+    "-f",
+    "--fail",
+    "-g",
+    "--globoff",
+    "--compressed",
+    "--no-progress-meter",
+];
+
+fn parse_curl(command: &str, warnings: &mut Vec<String>) -> ApiResult<HTTPRequest> {
+    let mut args: VecDeque<String> = shell_words(command)?.into();
+    match args.pop_front() {
+        Some(program)
+            if program == "curl" || program.ends_with("/curl") || program == "curl.exe" => {}
+        _ => return Err(APIError::Parser("Not a curl command".to_string())),
+    }
+
+    let mut request = HTTPRequest {
+        // curl only follows redirects with --location
+        redirect: HTTPRedirectPolicy::None,
+        ..Default::default()
+    };
+    let mut url = None;
+    let mut data: Vec<String> = Vec::new();
+    let mut parts = Vec::new();
+    let mut get = false;
+    let mut json = false;
+    let mut digest = false;
+    let mut user = None;
+    let mut max_redirects = None;
+    let mut proxy: Option<HTTPProxy> = None;
+    let mut cert_type = String::new();
+    let mut cert = None;
+    let mut key = None;
+
+    while let Some(arg) = args.pop_front() {
+        let (flag, mut inline) = if arg.starts_with("--") || arg == "-" || !arg.starts_with('-') {
+            (arg, None)
+        } else {
+            let mut chars = arg[1..].chars();
+            let short = chars.next().unwrap_or_default();
+            let rest = chars.as_str().to_string();
+            if rest.is_empty() {
+                (arg, None)
+            } else if CURL_SHORT_WITH_VALUE.contains(short) {
+                (format!("-{}", short), Some(rest))
+            } else {
+                // combined flags like -sSL
+                args.push_front(format!("-{}", rest));
+                (format!("-{}", short), None)
+            }
+        };
+        if !flag.starts_with('-') || flag == "-" {
+            if url.is_some() {
+                warnings.push(format!("Only the first url is imported, ignored {}", flag));
+            } else {
+                url = Some(flag);
+            }
+            continue;
+        }
+        let mut value = || {
+            inline
+                .take()
+                .or_else(|| args.pop_front())
+                .ok_or_else(|| APIError::Parser(format!("Missing value for {}", flag)))
+        };
+        match flag.as_str() {
+            "--url" => url = Some(value()?),
+            "-X" | "--request" => request.method = value()?.to_uppercase(),
+            "-I" | "--head" => request.method = "HEAD".to_string(),
+            "-G" | "--get" => get = true,
+            "-H" | "--header" => {
+                let header = value()?;
+                match header.split_once(':') {
+                    Some((name, value)) => request.headers.push(kv(name.trim(), value.trim())),
+                    None => warnings.push(format!("Ignored malformed header {:?}", header)),
+                }
+            }
+            "-d" | "--data" | "--data-ascii" | "--data-binary" | "--data-raw" | "--json" => {
+                let body = value()?;
+                json |= flag == "--json";
+                if body.starts_with('@') && flag != "--data-raw" {
+                    warnings.push(format!(
+                        "Reading {} from file {} is not supported",
+                        flag,
+                        &body[1..]
+                    ));
+                } else {
+                    data.push(body);
+                }
+            }
+            "--data-urlencode" => {
+                let param = value()?;
+                let (name, content) = match param.split_once('=') {
+                    Some((name, content)) => (name, content),
+                    None => ("", param.as_str()),
+                };
+                if content.starts_with('@') || name.contains('@') {
+                    warnings.push(format!("Reading {} from a file is not supported", flag));
+                    continue;
+                }
+                let encoded: String = form_urlencoded::byte_serialize(content.as_bytes()).collect();
+                data.push(match name {
+                    "" => encoded,
+                    name => format!("{}={}", name, encoded),
+                });
+            }
+            "-F" | "--form" | "--form-string" => {
+                let field = value()?;
+                let (name, content) = field.split_once('=').unwrap_or((field.as_str(), ""));
+                let mut part = HTTPMultipartPart {
+                    name: name.to_string(),
+                    value: content.to_string(),
+                    file: None,
+                    file_name: None,
+                    content_type: None,
+                    enabled: true,
+                };
+                if flag != "--form-string" {
+                    // name=@file;type=mime;filename=name
+                    let mut attributes = content.split(';');
+                    let first = attributes.next().unwrap_or_default();
+                    if let Some(file) = first.strip_prefix('@') {
+                        part.value = String::new();
+                        part.file = Some(file.to_string());
+                    } else if first.starts_with('<') {
+                        warnings.push(format!(
+                            "Form field {} read from a file is not supported",
+                            name
+                        ));
+                        continue;
+                    } else {
+                        part.value = first.to_string();
+                    }
+                    for attribute in attributes {
+                        match attribute.split_once('=') {
+                            Some(("type", mime)) => part.content_type = Some(mime.to_string()),
+                            Some(("filename", file_name)) => {
+                                part.file_name = Some(file_name.trim_matches('"').to_string())
+                            }
+                            _ => warnings.push(format!(
+                                "Ignored form field attribute {:?} of {}",
+                                attribute, name
+                            )),
+                        }
+                    }
+                }
+                parts.push(part);
+            }
+            "-u" | "--user" => user = Some(value()?),
+            "--basic" => digest = false,
+            "--digest" => digest = true,
+            "--oauth2-bearer" => request.auth = Some(HTTPAuth::Bearer { token: value()? }),
+            "-b" | "--cookie" => {
+                let cookie = value()?;
+                if cookie.contains('=') {
+                    request.headers.push(kv("Cookie", &cookie));
+                } else {
+                    warnings.push(format!("Cookie file {} is not supported", cookie));
+                }
+            }
+            "-A" | "--user-agent" => request.headers.push(kv("User-Agent", &value()?)),
+            "-e" | "--referer" => request.headers.push(kv("Referer", &value()?)),
+            "-L" | "--location" => request.redirect = HTTPRedirectPolicy::Follow,
+            "--max-redirs" => {
+                let max = value()?;
+                match max.parse::<usize>() {
+                    Ok(max) => max_redirects = Some(max),
+                    Err(_) => warnings.push(format!("Ignored invalid --max-redirs {}", max)),
+                }
+            }
+            "-k" | "--insecure" => request.tls.accept_invalid_certs = true,
+            "--cacert" => request.tls.ca_files.push(value()?),
+            "-E" | "--cert" => cert = Some(value()?),
+            "--key" => key = Some(value()?),
+            "--cert-type" => cert_type = value()?,
+            "--tlsv1.2" => request.tls.min_version = Some(HTTPTlsVersion::Tls12),
+            "--tlsv1.3" => request.tls.min_version = Some(HTTPTlsVersion::Tls13),
+            "--http1.1" => request.http_version = HTTPVersion::Http1,
+            "--http2" => request.http_version = HTTPVersion::Negotiate,
+            "--http2-prior-knowledge" => request.http_version = HTTPVersion::Http2,
+            "-x" | "--proxy" => proxy.get_or_insert_with(empty_proxy).url = value()?,
+            "-U" | "--proxy-user" => {
+                let credentials = value()?;
+                let (username, password) = credentials
+                    .split_once(':')
+                    .unwrap_or((credentials.as_str(), ""));
+                let proxy = proxy.get_or_insert_with(empty_proxy);
+                proxy.username = Some(username.to_string());
+                proxy.password = Some(password.to_string());
+            }
+            "--noproxy" => {
+                let no_proxy = value()?;
+                if let Some(proxy) = proxy.as_mut() {
+                    proxy.no_proxy = no_proxy;
+                }
+            }
+            "-o" | "--output" | "-w" | "--write-out" => {
+                value()?;
+            }
+            "-m" | "--max-time" | "--connect-timeout" => {
+                let secs = value()?;
+                warnings.push(format!(
+                    "{} {} is not imported, timeouts are set when sending",
+                    flag, secs
+                ));
+            }
+            flag if CURL_IGNORED.contains(&flag) => {}
+            flag if flag.len() == 2 && CURL_SHORT_WITH_VALUE.contains(&flag[1..]) => {
+                let ignored = value()?;
+                warnings.push(format!("Unsupported option {} {}", flag, ignored));
+            }
+            flag => warnings.push(format!("Unsupported option {}", flag)),
+        }
+    }
+
+    let url = url.ok_or_else(|| APIError::Parser("No url in curl command".to_string()))?;
+    let url = if url.contains("://") || url.starts_with("{{") {
+        url
+    } else {
+        format!("http://{}", url)
+    };
+    let (url, query) = split_query(&url);
+    request.url = url;
+    request.query = query;
+    take_content_type(&mut request);
+
+    if let Some(max) = max_redirects {
+        if matches!(request.redirect, HTTPRedirectPolicy::Follow) {
+            request.redirect = HTTPRedirectPolicy::Limit { max };
+        }
+    }
+    if let Some(proxy) = proxy.filter(|p| !p.url.is_empty()) {
+        request.proxy = Some(HTTPProxySettings::Manual(proxy));
+    }
+    if let Some(cert) = cert {
+        request.tls.identity = Some(if cert_type.eq_ignore_ascii_case("p12") {
+            let (file, password) = cert.split_once(':').unwrap_or((cert.as_str(), ""));
+            HTTPClientIdentity::Pkcs12 {
+                file: file.to_string(),
+                password: password.to_string(),
+            }
+        } else {
+            HTTPClientIdentity::Pem { cert, key }
+        });
+    }
+    if let Some(user) = user {
+        let (username, password) = user.split_once(':').unwrap_or((user.as_str(), ""));
+        let (username, password) = (username.to_string(), password.to_string());
+        request.auth = Some(if digest {
+            HTTPAuth::Digest { username, password }
+        } else {
+            HTTPAuth::Basic { username, password }
+        });
+    }
+
+    if get {
+        for param in data
+            .iter()
+            .flat_map(|d| form_urlencoded::parse(d.as_bytes()))
+        {
+            request.query.push(kv(&param.0, &param.1));
+        }
+    } else if !data.is_empty() {
+        request.body = data.join("&");
+        if json {
+            if request.content_type.is_empty() {
+                request.content_type = "application/json".to_string();
+            }
+            request.headers.push(kv("Accept", "application/json"));
+        } else if request.content_type.is_empty() {
+            request.content_type = "application/x-www-form-urlencoded".to_string();
+        }
+        json_body_type(&mut request);
+    }
+    if !parts.is_empty() {
+        if !data.is_empty() {
+            warnings.push("Both --data and --form given, the form is imported".to_string());
+            request.body = String::new();
+        }
+        request.body_type = HTTPBodyType::Multipart { parts };
+        request.content_type = String::new();
+    }
+    if request.method.is_empty() {
+        let has_body = (!data.is_empty() && !get)
+            || matches!(request.body_type, HTTPBodyType::Multipart { .. });
+        request.method = if has_body { "POST" } else { "GET" }.to_string();
+    }
+    Ok(request)
+}
+
+fn empty_proxy() -> HTTPProxy {
+    HTTPProxy {
+        url: String::new(),
+        username: None,
+        password: None,
+        no_proxy: String::new(),
+    }
+}
+
+fn parse_har(doc: &Value, warnings: &mut Vec<String>) -> ApiResult<HTTPFolder> {
+    let entries = doc
+        .pointer("/log/entries")
+        .and_then(Value::as_array)
+        .ok_or_else(|| APIError::Parser("HAR file has no log entries".to_string()))?;
+    let mut collection = folder("HAR");
+    for (i, entry) in entries.iter().enumerate() {
+        let har = match entry.get("request") {
+            Some(har) => har,
+            None => {
+                warnings.push(format!("Entry {} has no request", i));
+                continue;
+            }
+        };
+        let (url, query) = split_query(str_field(har, "url"));
+        let mut request = HTTPRequest {
+            method: str_field(har, "method").to_uppercase(),
+            url,
+            query,
+            ..Default::default()
+        };
+        for header in array_field(har, "headers") {
+            let name = str_field(header, "name");
+            // HTTP/2 pseudo headers and the length computed when sending
+            if name.starts_with(':') || name.eq_ignore_ascii_case("content-length") {
+                continue;
+            }
+            request.headers.push(kv(name, str_field(header, "value")));
+        }
+        take_content_type(&mut request);
+
+        if let Some(post) = har.get("postData") {
+            let mime = str_field(post, "mimeType");
+            if request.content_type.is_empty() {
+                request.content_type = mime.to_string();
+            }
+            let params = array_field(post, "params");
+            if mime.starts_with("multipart/form-data") && !params.is_empty() {
+                let parts = params
+                    .iter()
+                    .map(|param| {
+                        let file_name = param.get("fileName").and_then(Value::as_str);
+                        if let Some(file_name) = file_name {
+                            warnings.push(format!(
+                                "Content of file {} in entry {} is not included in HAR files",
+                                file_name, i
+                            ));
+                        }
+                        HTTPMultipartPart {
+                            name: str_field(param, "name").to_string(),
+                            value: str_field(param, "value").to_string(),
+                            file: None,
+                            file_name: file_name.map(str::to_string),
+                            content_type: param
+                                .get("contentType")
+                                .and_then(Value::as_str)
+                                .map(str::to_string),
+                            enabled: true,
+                        }
+                    })
+                    .collect();
+                request.body_type = HTTPBodyType::Multipart { parts };
+                request.content_type = String::new();
+            } else if mime.starts_with("application/x-www-form-urlencoded") && !params.is_empty() {
+                let params = params
+                    .iter()
+                    .map(|p| kv(str_field(p, "name"), str_field(p, "value")))
+                    .collect();
+                request.body_type = HTTPBodyType::UrlEncoded { params };
+                request.content_type = String::new();
+            } else {
+                request.body = str_field(post, "text").to_string();
+                json_body_type(&mut request);
+            }
+        }
+        collection
+            .items
+            .push(saved(request_name(&request), String::new(), request));
+    }
+    Ok(collection)
+}
+
+fn str_field<'a>(value: &'a Value, name: &str) -> &'a str {
+    value.get(name).and_then(Value::as_str).unwrap_or_default()
+}
+
+fn array_field<'a>(value: &'a Value, name: &str) -> &'a [Value] {
+    value
+        .get(name)
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+/// Postman descriptions are either a string or `{ "content": ... }`.
+fn postman_description(value: &Value) -> String {
+    match value.get("description") {
+        Some(Value::String(description)) => description.clone(),
+        Some(description) => str_field(description, "content").to_string(),
+        None => String::new(),
+    }
+}
+
+fn parse_postman(doc: &Value, import: &mut HTTPImport) -> ApiResult<()> {
+    let info = doc
+        .get("info")
+        .ok_or_else(|| APIError::Parser("Postman collection has no info".to_string()))?;
+    let schema = str_field(info, "schema");
+    if !schema.is_empty() && !schema.contains("v2.1") {
+        import.warnings.push(format!(
+            "Only Postman v2.1 collections are supported, got {}",
+            schema
+        ));
+    }
+    let warnings = &mut import.warnings;
+    let mut collection = folder(str_field(info, "name"));
+    collection.description = postman_description(info);
+    collection.auth = postman_auth(doc.get("auth"), &collection.name, warnings);
+    if !array_field(doc, "event").is_empty() {
+        warnings.push(format!("{}: scripts are not supported", collection.name));
+    }
+    collection.items = postman_items(array_field(doc, "item"), warnings);
+    import.collection = collection;
+    import.variables = array_field(doc, "variable")
+        .iter()
+        .map(|v| HTTPVariable {
+            key: str_field(v, "key").to_string(),
+            value: match v.get("value") {
+                Some(Value::String(value)) => value.clone(),
+                Some(value) => value.to_string(),
+                None => String::new(),
+            },
+            secret: str_field(v, "type") == "secret",
+            enabled: !v.get("disabled").and_then(Value::as_bool).unwrap_or(false),
+        })
+        .collect();
+    Ok(())
+}
+
+fn postman_items(items: &[Value], warnings: &mut Vec<String>) -> Vec<HTTPCollectionItem> {
+    let mut imported = Vec::new();
+    for item in items {
+        let name = str_field(item, "name");
+        if !array_field(item, "event").is_empty() {
+            warnings.push(format!("{}: scripts are not supported", name));
+        }
+        if let Some(children) = item.get("item").and_then(Value::as_array) {
+            let mut folder = folder(name);
+            folder.description = postman_description(item);
+            folder.auth = postman_auth(item.get("auth"), name, warnings);
+            folder.items = postman_items(children, warnings);
+            imported.push(HTTPCollectionItem::Folder(folder));
+        } else if let Some(request) = item.get("request") {
+            let request = postman_request(request, name, warnings);
+            imported.push(saved(name.to_string(), postman_description(item), request));
+        }
+    }
+    imported
+}
+
+fn postman_request(postman: &Value, name: &str, warnings: &mut Vec<String>) -> HTTPRequest {
+    if let Value::String(url) = postman {
+        let (url, query) = split_query(url);
+        return HTTPRequest {
+            method: "GET".to_string(),
+            url,
+            query,
+            ..Default::default()
+        };
+    }
+    let mut request = HTTPRequest {
+        method: match str_field(postman, "method") {
+            "" => "GET".to_string(),
+            method => method.to_uppercase(),
+        },
+        ..Default::default()
+    };
+    match postman.get("url") {
+        Some(Value::String(raw)) => (request.url, request.query) = split_query(raw),
+        Some(url) => {
+            (request.url, request.query) = split_query(str_field(url, "raw"));
+            if let Some(query) = url.get("query").and_then(Value::as_array) {
+                request.query = query.iter().map(postman_param).collect();
+            }
+        }
+        None => warnings.push(format!("{}: request has no url", name)),
+    }
+    request.headers = array_field(postman, "header")
+        .iter()
+        .map(postman_param)
+        .collect();
+    take_content_type(&mut request);
+    request.auth = postman_auth(postman.get("auth"), name, warnings);
+
+    let body = match postman.get("body") {
+        Some(body) => body,
+        None => return request,
+    };
+    match str_field(body, "mode") {
+        "raw" => {
+            request.body = str_field(body, "raw").to_string();
+            if request.content_type.is_empty() {
+                request.content_type = match body
+                    .pointer("/options/raw/language")
+                    .and_then(Value::as_str)
+                {
+                    Some("json") => "application/json",
+                    Some("xml") => "application/xml",
+                    Some("html") => "text/html",
+                    Some("javascript") => "application/javascript",
+                    _ => "text/plain",
+                }
+                .to_string();
+            }
+            json_body_type(&mut request);
+        }
+        "urlencoded" => {
+            let params = array_field(body, "urlencoded")
+                .iter()
+                .map(postman_param)
+                .collect();
+            request.body_type = HTTPBodyType::UrlEncoded { params };
+            request.content_type = String::new();
+        }
+        "formdata" => {
+            let parts = array_field(body, "formdata")
+                .iter()
+                .map(|field| {
+                    let file = match field.get("src") {
+                        Some(Value::String(src)) => Some(src.clone()),
+                        Some(Value::Array(srcs)) => {
+                            if srcs.len() > 1 {
+                                warnings.push(format!(
+                                    "{}: only the first file of form field {} is imported",
+                                    name,
+                                    str_field(field, "key")
+                                ));
+                            }
+                            srcs.first().and_then(Value::as_str).map(str::to_string)
+                        }
+                        _ => None,
+                    };
+                    HTTPMultipartPart {
+                        name: str_field(field, "key").to_string(),
+                        value: str_field(field, "value").to_string(),
+                        file: file.filter(|_| str_field(field, "type") == "file"),
+                        file_name: None,
+                        content_type: field
+                            .get("contentType")
+                            .and_then(Value::as_str)
+                            .map(str::to_string),
+                        enabled: !field
+                            .get("disabled")
+                            .and_then(Value::as_bool)
+                            .unwrap_or(false),
+                    }
+                })
+                .collect();
+            request.body_type = HTTPBodyType::Multipart { parts };
+            request.content_type = String::new();
+        }
+        "graphql" => {
+            let graphql = body.get("graphql").cloned().unwrap_or_default();
+            request.body_type = HTTPBodyType::GraphQL {
+                query: str_field(&graphql, "query").to_string(),
+                variables: str_field(&graphql, "variables").to_string(),
+                operation_name: None,
+            };
+            request.content_type = "application/json".to_string();
+        }
+        "" => {}
+        mode => warnings.push(format!("{}: body mode {} is not supported", name, mode)),
+    }
+    request
+}
+
+fn postman_param(param: &Value) -> HTTPRequestKVParam {
+    HTTPRequestKVParam {
+        key: str_field(param, "key").to_string(),
+        value: str_field(param, "value").to_string(),
+        enabled: !param
+            .get("disabled")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+    }
+}
+
+fn postman_auth(auth: Option<&Value>, name: &str, warnings: &mut Vec<String>) -> Option<HTTPAuth> {
+    let auth = auth?;
+    let kind = str_field(auth, "type");
+    let params: BTreeMap<&str, String> = array_field(auth, kind)
+        .iter()
+        .map(|p| {
+            let value = match p.get("value") {
+                Some(Value::String(value)) => value.clone(),
+                Some(value) => value.to_string(),
+                None => String::new(),
+            };
+            (str_field(p, "key"), value)
+        })
+        .collect();
+    let param = |key: &str| params.get(key).cloned().unwrap_or_default();
+    match kind {
+        "noauth" | "" => None,
+        "basic" => Some(HTTPAuth::Basic {
+            username: param("username"),
+            password: param("password"),
+        }),
+        "digest" => Some(HTTPAuth::Digest {
+            username: param("username"),
+            password: param("password"),
+        }),
+        "bearer" => Some(HTTPAuth::Bearer {
+            token: param("token"),
+        }),
+        "awsv4" => {
+            warnings.push(format!(
+                "{}: AWS keys are not imported, the default profile is used instead",
+                name
+            ));
+            Some(HTTPAuth::AwsSigV4 {
+                profile: "default".to_string(),
+                region: Some(param("region")).filter(|r| !r.is_empty()),
+                service: param("service"),
+            })
+        }
+        "oauth2" if param("grant_type") == "client_credentials" => Some(HTTPAuth::OAuth2 {
+            token_url: param("accessTokenUrl"),
+            client_id: param("clientId"),
+            client_secret: param("clientSecret"),
+            scope: param("scope"),
+        }),
+        "oauth2" if !param("accessToken").is_empty() => {
+            warnings.push(format!(
+                "{}: only the client credentials grant is supported, the current token is used",
+                name
+            ));
+            Some(HTTPAuth::Bearer {
+                token: param("accessToken"),
+            })
+        }
+        kind => {
+            warnings.push(format!("{}: {} auth is not supported", name, kind));
+            None
+        }
+    }
+}
+
+fn parse_openapi(doc: &Value, import: &mut HTTPImport) -> ApiResult<()> {
+    // YAML reads `openapi: 3.1` as a number
+    let version = match doc.get("openapi") {
+        Some(Value::String(version)) => version.clone(),
+        Some(version) => version.to_string(),
+        None => String::new(),
+    };
+    if !version.starts_with('3') {
+        return Err(APIError::Parser(format!(
+            "Only OpenAPI 3 documents are supported, got {:?}",
+            version
+        )));
+    }
+    let mut openapi = OpenApi {
+        doc,
+        variables: BTreeMap::new(),
+        warnings: Vec::new(),
+    };
+    let info = doc.get("info").cloned().unwrap_or_default();
+    let mut collection = folder(match str_field(&info, "title") {
+        "" => "OpenAPI",
+        title => title,
+    });
+    collection.description = str_field(&info, "description").to_string();
+    let base_url = openapi.server_url();
+    openapi.variable("baseUrl", &base_url, false);
+
+    let paths = doc.get("paths").and_then(Value::as_object);
+    let mut tags: Vec<HTTPFolder> = Vec::new();
+    for (path, item) in paths.into_iter().flatten() {
+        let item = openapi.resolve(item);
+        for method in OPENAPI_METHODS {
+            let operation = match item.get(method) {
+                Some(operation) => openapi.resolve(operation),
+                None => continue,
+            };
+            let request = openapi.request(path, method, item, operation);
+            let name = match (
+                str_field(operation, "summary"),
+                str_field(operation, "operationId"),
+            ) {
+                ("", "") => request_name(&request),
+                ("", id) => id.to_string(),
+                (summary, _) => summary.to_string(),
+            };
+            let saved = saved(
+                name,
+                str_field(operation, "description").to_string(),
+                request,
+            );
+            let tag = operation
+                .get("tags")
+                .and_then(Value::as_array)
+                .and_then(|tags| tags.first())
+                .and_then(Value::as_str);
+            match tag {
+                Some(tag) => match tags.iter_mut().find(|f| f.name == tag) {
+                    Some(folder) => folder.items.push(saved),
+                    None => {
+                        let mut folder = folder(tag);
+                        folder.items.push(saved);
+                        tags.push(folder);
+                    }
+                },
+                None => collection.items.push(saved),
+            }
+        }
+    }
+    // tag folders first, like the OpenAPI document lists them
+    let mut items: Vec<_> = tags.into_iter().map(HTTPCollectionItem::Folder).collect();
+    items.append(&mut collection.items);
+    collection.items = items;
+
+    import.collection = collection;
+    import.variables = openapi
+        .variables
+        .into_iter()
+        .map(|(key, (value, secret))| HTTPVariable {
+            key,
+            value,
+            secret,
+            enabled: true,
+        })
+        .collect();
+    import.warnings.extend(openapi.warnings);
+    Ok(())
+}
+
+struct OpenApi<'a> {
+    doc: &'a Value,
+    /// Values and secrecy of the placeholders used by the requests.
+    variables: BTreeMap<String, (String, bool)>,
+    warnings: Vec<String>,
+}
+
+impl<'a> OpenApi<'a> {
+    /// Follows a local `$ref`, unresolvable references are returned as is.
+    fn resolve(&mut self, value: &'a Value) -> &'a Value {
+        let mut value = value;
+        // bounded to stop at reference cycles
+        for _ in 0..16 {
+            let reference = match value.get("$ref").and_then(Value::as_str) {
+                Some(reference) => reference,
+                None => return value,
+            };
+            match reference
+                .strip_prefix('#')
+                .and_then(|pointer| self.doc.pointer(pointer))
+            {
+                Some(target) => value = target,
+                None => {
+                    self.warnings
+                        .push(format!("Unresolved reference {}", reference));
+                    return value;
+                }
+            }
+        }
+        value
+    }
+
+    fn variable(&mut self, name: &str, value: &str, secret: bool) {
+        self.variables
+            .entry(name.to_string())
+            .or_insert_with(|| (value.to_string(), secret));
+    }
+
+    fn server_url(&mut self) -> String {
+        let server = match self.doc.get("servers").and_then(|s| s.get(0)) {
+            Some(server) => server,
+            None => {
+                self.warnings
+                    .push("No servers declared, set the baseUrl variable".to_string());
+                return String::new();
+            }
+        };
+        let mut url = str_field(server, "url").to_string();
+        if let Some(variables) = server.get("variables").and_then(Value::as_object) {
+            for (name, variable) in variables {
+                url = url.replace(&format!("{{{}}}", name), str_field(variable, "default"));
+            }
+        }
+        url.trim_end_matches('/').to_string()
+    }
+
+    fn request(
+        &mut self,
+        path: &str,
+        method: &str,
+        item: &'a Value,
+        operation: &'a Value,
+    ) -> HTTPRequest {
+        let mut request = HTTPRequest {
+            method: method.to_uppercase(),
+            url: format!(
+                "{{{{baseUrl}}}}{}",
+                path.replace('{', "{{").replace('}', "}}")
+            ),
+            ..Default::default()
+        };
+        let mut cookies = Vec::new();
+        let parameters = array_field(item, "parameters")
+            .iter()
+            .chain(array_field(operation, "parameters"));
+        for parameter in parameters {
+            let parameter = self.resolve(parameter);
+            let name = str_field(parameter, "name");
+            let example = self.parameter_example(parameter);
+            let required = parameter
+                .get("required")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+            match str_field(parameter, "in") {
+                "path" => self.variable(name, &example, false),
+                "query" => request.query.push(HTTPRequestKVParam {
+                    enabled: required,
+                    ..kv(name, &example)
+                }),
+                "header" => request.headers.push(HTTPRequestKVParam {
+                    enabled: required,
+                    ..kv(name, &example)
+                }),
+                "cookie" => cookies.push(format!("{}={}", name, example)),
+                location => self.warnings.push(format!(
+                    "{} {}: parameter {} in {} is not supported",
+                    request.method, path, name, location
+                )),
+            }
+        }
+        if !cookies.is_empty() {
+            request.headers.push(kv("Cookie", &cookies.join("; ")));
+        }
+        if let Some(body) = operation.get("requestBody") {
+            let body = self.resolve(body);
+            self.body(&mut request, body);
+        }
+        let security = operation
+            .get("security")
+            .or_else(|| self.doc.get("security"));
+        if let Some(requirement) = security
+            .and_then(Value::as_array)
+            .and_then(|requirements| requirements.first())
+            .and_then(Value::as_object)
+        {
+            for (scheme, scopes) in requirement {
+                self.security(&mut request, scheme, scopes);
+            }
+        }
+        request
+    }
+
+    fn parameter_example(&mut self, parameter: &'a Value) -> String {
+        let example = match parameter.get("example") {
+            Some(example) => example.clone(),
+            None => match parameter.get("schema") {
+                Some(schema) => self.example(schema, 0),
+                None => Value::Null,
+            },
+        };
+        match example {
+            Value::String(example) => example,
+            Value::Null => String::new(),
+            example => example.to_string(),
+        }
+    }
+
+    fn body(&mut self, request: &mut HTTPRequest, body: &'a Value) {
+        let content = match body.get("content").and_then(Value::as_object) {
+            Some(content) if !content.is_empty() => content,
+            _ => return,
+        };
+        let preferred = [
+            "application/json",
+            "application/x-www-form-urlencoded",
+            "multipart/form-data",
+        ];
+        let (mime, media) = preferred
+            .iter()
+            .find_map(|mime| content.get_key_value(*mime))
+            .or_else(|| content.iter().find(|(mime, _)| is_json_type(mime)))
+            .or_else(|| content.iter().next())
+            .expect("content is not empty");
+        let example = match media
+            .get("example")
+            .or_else(|| media.pointer("/examples").and_then(first_example))
+        {
+            Some(example) => example.clone(),
+            None => match media.get("schema") {
+                Some(schema) => self.example(schema, 0),
+                None => Value::Null,
+            },
+        };
+        if mime == "application/x-www-form-urlencoded" || mime == "multipart/form-data" {
+            let fields: Vec<(String, String)> = match &example {
+                Value::Object(fields) => fields
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.as_str().map_or(v.to_string(), str::to_string)))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            request.body_type = if mime == "multipart/form-data" {
+                HTTPBodyType::Multipart {
+                    parts: fields
+                        .iter()
+                        .map(|(name, value)| HTTPMultipartPart {
+                            name: name.clone(),
+                            value: value.clone(),
+                            file: None,
+                            file_name: None,
+                            content_type: None,
+                            enabled: true,
+                        })
+                        .collect(),
+                }
+            } else {
+                HTTPBodyType::UrlEncoded {
+                    params: fields.iter().map(|(k, v)| kv(k, v)).collect(),
+                }
+            };
+            return;
+        }
+        request.content_type = mime.clone();
+        request.body = match example {
+            Value::Null => String::new(),
+            example if is_json_type(mime) => {
+                serde_json::to_string_pretty(&example).unwrap_or_default()
+            }
+            Value::String(text) => text,
+            _ => {
+                self.warnings.push(format!(
+                    "{} {}: {} example bodies are not supported",
+                    request.method, request.url, mime
+                ));
+                String::new()
+            }
+        };
+        json_body_type(request);
+    }
+
+    /// Builds an example value from `schema`, preferring the examples and defaults it declares.
+    fn example(&mut self, schema: &'a Value, depth: usize) -> Value {
+        if depth > 8 {
+            return Value::Null;
+        }
+        let schema = self.resolve(schema);
+        if let Some(example) = schema.get("example").or_else(|| schema.get("default")) {
+            return example.clone();
+        }
+        if let Some(first) = schema
+            .get("enum")
+            .and_then(Value::as_array)
+            .and_then(|e| e.first())
+        {
+            return first.clone();
+        }
+        if let Some(all_of) = schema.get("allOf").and_then(Value::as_array) {
+            let mut merged = Map::new();
+            for schema in all_of {
+                if let Value::Object(fields) = self.example(schema, depth + 1) {
+                    merged.extend(fields);
+                }
+            }
+            return Value::Object(merged);
+        }
+        if let Some(first) = ["oneOf", "anyOf"]
+            .iter()
+            .find_map(|key| schema.get(*key).and_then(Value::as_array)?.first())
+        {
+            return self.example(first, depth + 1);
+        }
+        let kind = match schema.get("type") {
+            Some(Value::String(kind)) => kind.as_str(),
+            // OpenAPI 3.1 allows a list of types, like ["string", "null"]
+            Some(Value::Array(kinds)) => kinds
+                .iter()
+                .filter_map(Value::as_str)
+                .find(|kind| *kind != "null")
+                .unwrap_or_default(),
+            _ if schema.get("properties").is_some() => "object",
+            _ => "",
+        };
+        match kind {
+            "object" => {
+                let mut fields = Map::new();
+                if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+                    for (name, property) in properties {
+                        fields.insert(name.clone(), self.example(property, depth + 1));
+                    }
+                }
+                Value::Object(fields)
+            }
+            "array" => match schema.get("items") {
+                Some(items) => Value::Array(vec![self.example(items, depth + 1)]),
+                None => Value::Array(Vec::new()),
+            },
+            "string" => Value::String(match str_field(schema, "format") {
+                "date" => "2024-01-01".to_string(),
+                "date-time" => "2024-01-01T00:00:00Z".to_string(),
+                "uuid" => "00000000-0000-0000-0000-000000000000".to_string(),
+                "email" => "user@example.com".to_string(),
+                "binary" | "byte" => String::new(),
+                _ => "string".to_string(),
+            }),
+            "integer" | "number" => Value::from(0),
+            "boolean" => Value::Bool(false),
+            _ => Value::Null,
+        }
+    }
+
+    fn security(&mut self, request: &mut HTTPRequest, name: &str, scopes: &Value) {
+        let scheme = match self
+            .doc
+            .pointer(&format!("/components/securitySchemes/{}", name))
+        {
+            Some(scheme) => self.resolve(scheme),
+            None => {
+                self.warnings
+                    .push(format!("Security scheme {} is not declared", name));
+                return;
+            }
+        };
+        match (
+            str_field(scheme, "type"),
+            str_field(scheme, "scheme").to_lowercase().as_str(),
+        ) {
+            ("http", "basic") | ("http", "digest") => {
+                self.variable("username", "", false);
+                self.variable("password", "", true);
+                let (username, password) = ("{{username}}".to_string(), "{{password}}".to_string());
+                request.auth = Some(
+                    if str_field(scheme, "scheme").eq_ignore_ascii_case("digest") {
+                        HTTPAuth::Digest { username, password }
+                    } else {
+                        HTTPAuth::Basic { username, password }
+                    },
+                );
+            }
+            ("http", "bearer") => {
+                self.variable("token", "", true);
+                request.auth = Some(HTTPAuth::Bearer {
+                    token: "{{token}}".to_string(),
+                });
+            }
+            ("apiKey", _) => {
+                let key = str_field(scheme, "name");
+                self.variable(name, "", true);
+                let value = format!("{{{{{}}}}}", name);
+                match str_field(scheme, "in") {
+                    "header" => request.headers.push(kv(key, &value)),
+                    "query" => request.query.push(kv(key, &value)),
+                    "cookie" => request
+                        .headers
+                        .push(kv("Cookie", &format!("{}={}", key, value))),
+                    location => self
+                        .warnings
+                        .push(format!("API key {} in {} is not supported", key, location)),
+                }
+            }
+            ("oauth2", _) => match scheme.pointer("/flows/clientCredentials/tokenUrl") {
+                Some(Value::String(token_url)) => {
+                    self.variable("clientId", "", false);
+                    self.variable("clientSecret", "", true);
+                    let scope = scopes
+                        .as_array()
+                        .map(|s| {
+                            s.iter()
+                                .filter_map(Value::as_str)
+                                .collect::<Vec<_>>()
+                                .join(" ")
+                        })
+                        .unwrap_or_default();
+                    request.auth = Some(HTTPAuth::OAuth2 {
+                        token_url: token_url.clone(),
+                        client_id: "{{clientId}}".to_string(),
+                        client_secret: "{{clientSecret}}".to_string(),
+                        scope,
+                    });
+                }
+                _ => {
+                    self.variable("token", "", true);
+                    self.warnings.push(format!(
+                        "Security scheme {}: only client credentials are supported",
+                        name
+                    ));
+                    request.auth = Some(HTTPAuth::Bearer {
+                        token: "{{token}}".to_string(),
+                    });
+                }
+            },
+            (kind, _) => self.warnings.push(format!(
+                "Security scheme {} of type {} is not supported",
+                name, kind
+            )),
+        }
+    }
+}
+
+/// The value of the first entry of an OpenAPI `examples` map.
+fn first_example(examples: &Value) -> Option<&Value> {
+    examples.as_object()?.values().next()?.get("value")
+}
+
+pub mod commands {
+    use super::*;
+    use tauri::command;
+
+    /// Reads requests from `content`, detecting its format when `format` is not given.
+    #[command(async)]
+    pub async fn http_import(
+        content: String,
+        format: Option<HTTPImportFormat>,
+    ) -> ApiResult<HTTPImport> {
+        let import = import(&content, format)?;
+        log::info!(
+            "Imported {:?} with {} warnings",
+            import.format,
+            import.warnings.len()
+        );
+        Ok(import)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn only_request(import: &HTTPImport) -> &HTTPRequest {
+        match import.collection.items.first() {
+            Some(HTTPCollectionItem::Request(saved)) => &saved.request,
+            other => panic!("expected a request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_import_curl() {
+        let command = r#"curl 'https://api.example.com/users?page=2' \
+  -H 'authorization: Bearer abc' \
+  -H $'x-note: it\'s' \
+  -H 'content-type: application/json' \
+  --data-raw '{"name":"Ann"}' \
+  --compressed -sSL --bogus"#;
+        let imported = import(command, None).expect("import");
+        assert_eq!(imported.format, HTTPImportFormat::Curl);
+        assert_eq!(
+            imported.warnings,
+            vec!["Unsupported option --bogus".to_string()]
+        );
+
+        let request = only_request(&imported);
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.url, "https://api.example.com/users");
+        assert_eq!(request.query[0].key, "page");
+        assert_eq!(request.headers[1].value, "it's");
+        assert_eq!(request.content_type, "application/json");
+        assert!(matches!(request.body_type, HTTPBodyType::Json));
+        assert!(matches!(request.redirect, HTTPRedirectPolicy::Follow));
+
+        let imported = import(
+            "curl -u me:pw --digest -XPUT example.com -F file=@a.txt --http1.1",
+            None,
+        )
+        .expect("import");
+        let request = only_request(&imported);
+        assert_eq!(request.method, "PUT");
+        assert_eq!(request.url, "http://example.com");
+        assert_eq!(request.http_version, HTTPVersion::Http1);
+        assert!(matches!(request.auth, Some(HTTPAuth::Digest { .. })));
+        assert!(matches!(
+            &request.body_type,
+            HTTPBodyType::Multipart { parts } if parts[0].file.as_deref() == Some("a.txt")
+        ));
+    }
+
+    #[test]
+    fn test_import_postman() {
+        let collection = serde_json::json!({
+            "info": {
+                "name": "Shop",
+                "schema": "https://schema.getpostman.com/json/collection/v2.1.0/collection.json"
+            },
+            "auth": {"type": "bearer", "bearer": [{"key": "token", "value": "{{token}}"}]},
+            "variable": [{"key": "host", "value": "https://shop.test"}],
+            "item": [{
+                "name": "Orders",
+                "item": [{
+                    "name": "Create order",
+                    "event": [{"listen": "test"}],
+                    "request": {
+                        "method": "POST",
+                        "header": [{"key": "X-Trace", "value": "1", "disabled": true}],
+                        "url": {
+                            "raw": "{{host}}/orders?dry=true",
+                            "query": [{"key": "dry", "value": "true"}]
+                        },
+                        "body": {
+                            "mode": "urlencoded",
+                            "urlencoded": [{"key": "sku", "value": "42"}]
+                        }
+                    }
+                }]
+            }]
+        });
+        let import = import(&collection.to_string(), None).expect("import");
+        assert_eq!(import.format, HTTPImportFormat::Postman);
+        assert_eq!(import.collection.name, "Shop");
+        assert!(matches!(
+            import.collection.auth,
+            Some(HTTPAuth::Bearer { .. })
+        ));
+        assert_eq!(import.variables[0].key, "host");
+        assert_eq!(
+            import.warnings,
+            vec!["Create order: scripts are not supported".to_string()]
+        );
+
+        let orders = match &import.collection.items[0] {
+            HTTPCollectionItem::Folder(folder) => folder,
+            other => panic!("expected a folder, got {:?}", other),
+        };
+        let request = match &orders.items[0] {
+            HTTPCollectionItem::Request(saved) => &saved.request,
+            other => panic!("expected a request, got {:?}", other),
+        };
+        assert_eq!(request.url, "{{host}}/orders");
+        assert!(!request.headers[0].enabled);
+        let params = match &request.body_type {
+            HTTPBodyType::UrlEncoded { params } => params,
+            other => panic!("expected urlencoded params, got {:?}", other),
+        };
+        assert_eq!(params[0].value, "42");
+    }
+
+    #[test]
+    fn test_import_openapi() {
+        let document = r#"
+openapi: 3.0.3
+info:
+  title: Pets
+servers:
+  - url: https://{env}.pets.test/v1/
+    variables:
+      env:
+        default: uat
+security:
+  - bearer: []
+components:
+  securitySchemes:
+    bearer:
+      type: http
+      scheme: bearer
+  schemas:
+    Pet:
+      type: object
+      properties:
+        name:
+          type: string
+          example: Rex
+        age:
+          type: integer
+paths:
+  /pets/{petId}:
+    parameters:
+      - name: petId
+        in: path
+        required: true
+        schema:
+          type: integer
+          example: 7
+    put:
+      tags: [pets]
+      operationId: updatePet
+      parameters:
+        - name: dryRun
+          in: query
+          schema:
+            type: boolean
+      requestBody:
+        content:
+          application/json:
+            schema:
+              $ref: '#/components/schemas/Pet'
+"#;
+        let import = import(document, None).expect("import");
+        assert_eq!(import.format, HTTPImportFormat::OpenApi);
+        let variables: BTreeMap<_, _> = import
+            .variables
+            .iter()
+            .map(|v| (v.key.as_str(), v.value.as_str()))
+            .collect();
+        assert_eq!(variables["baseUrl"], "https://uat.pets.test/v1");
+        assert_eq!(variables["petId"], "7");
+        assert!(variables.contains_key("token"));
+
+        let pets = match &import.collection.items[0] {
+            HTTPCollectionItem::Folder(folder) => folder,
+            other => panic!("expected a folder, got {:?}", other),
+        };
+        let saved = match &pets.items[0] {
+            HTTPCollectionItem::Request(saved) => saved,
+            other => panic!("expected a request, got {:?}", other),
+        };
+        assert_eq!(saved.name, "updatePet");
+        assert_eq!(saved.request.url, "{{baseUrl}}/pets/{{petId}}");
+        assert_eq!(saved.request.query[0].value, "false");
+        assert!(!saved.request.query[0].enabled);
+        let body: Value = serde_json::from_str(&saved.request.body).expect("json body");
+        assert_eq!(body, serde_json::json!({"name": "Rex", "age": 0}));
+        assert!(matches!(saved.request.auth, Some(HTTPAuth::Bearer { .. })));
+    }
+}
@@ -0,0 +1,505 @@
+use crate::errors::{APIError, ApiResult};
+use crate::services::http::{self, HTTPClient, HTTPRequest, HTTPResponse, RequestTimeout};
+use crate::services::http_env::{HTTPEnvironment, Variables};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Stop flags of the load tests running, keyed by id.
+static RUNNING_LOAD_TESTS: Lazy<Mutex<HashMap<String, Arc<AtomicBool>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPLoadOptions {
+    /// Progress is emitted as `http_load_{id}`.
+    #[serde(default = "new_id")]
+    pub id: String,
+    /// Stops after this many requests. At least one of `requests` and `duration` is set.
+    #[serde(default)]
+    pub requests: Option<u64>,
+    /// Stops after this many seconds.
+    #[serde(default)]
+    pub duration: Option<u64>,
+    /// Requests in flight at once.
+    #[serde(default = "one")]
+    pub concurrency: usize,
+    /// Requests started per second, as many as `concurrency` allows when unset.
+    #[serde(default)]
+    pub rate: Option<f64>,
+    #[serde(default)]
+    pub timeout: Option<RequestTimeout>,
+}
+
+fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+fn one() -> usize {
+    1
+}
+
+/// Latency of the completed requests in ms, failed ones included.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPLatency {
+    pub min: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+/// Mean time in ms the responses spent in each phase, see [`http::HTTPStats`]. Requests
+/// sent on a pooled connection spend none on DNS, TCP and TLS.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPLoadPhases {
+    pub dns_lookup: f64,
+    pub tcp: f64,
+    pub tls: f64,
+    pub send: f64,
+    pub server_processing: f64,
+    pub content_transfer: f64,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPLoadReport {
+    pub id: String,
+    /// False for the progress reported while the test runs.
+    pub done: bool,
+    /// Requests completed, failed ones included.
+    pub requests: u64,
+    /// In ms since the test started.
+    pub elapsed: u32,
+    /// Requests completed per second.
+    pub throughput: f64,
+    /// Response body bytes received.
+    pub bytes: u64,
+    /// Responses by status code.
+    pub statuses: BTreeMap<u16, u64>,
+    /// Requests that got no response, by error.
+    pub errors: BTreeMap<String, u64>,
+    pub latency: HTTPLatency,
+    pub phases: HTTPLoadPhases,
+}
+
+pub type LoadListener = Box<dyn Fn(HTTPLoadReport) + Send + Sync>;
+
+/// Sub-buckets per power of two, a latency is counted within 1/128 of its value.
+const SUB_BUCKETS: u64 = 128;
+
+/// Latencies counted in buckets that widen with the value, so memory and the work done
+/// per report stay the same however many requests were sent.
+struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    /// In µs, like `min` and `max`.
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            counts: vec![0; Self::index(u64::MAX) + 1],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+}
+
+impl Histogram {
+    fn index(value: u64) -> usize {
+        if value < SUB_BUCKETS {
+            return value as usize;
+        }
+        let shift = value.ilog2() - SUB_BUCKETS.ilog2();
+        ((u64::from(shift) + 1) * SUB_BUCKETS + (value >> shift) - SUB_BUCKETS) as usize
+    }
+
+    /// Largest value counted in the bucket at `index`.
+    fn highest(index: usize) -> u64 {
+        let index = index as u64;
+        if index < SUB_BUCKETS {
+            return index;
+        }
+        let shift = index / SUB_BUCKETS - 1;
+        let base = index % SUB_BUCKETS + SUB_BUCKETS;
+        (base << shift) + ((1 << shift) - 1)
+    }
+
+    fn record(&mut self, latency: Duration) {
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        self.counts[Self::index(micros)] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(micros);
+        self.min = self.min.min(micros);
+        self.max = self.max.max(micros);
+    }
+
+    /// Nearest-rank percentile in ms, `q` between 0 and 1.
+    fn percentile(&self, q: f64) -> f64 {
+        let rank = ((q * self.count as f64).ceil() as u64).clamp(1, self.count);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return millis(Self::highest(index).clamp(self.min, self.max));
+            }
+        }
+        millis(self.max)
+    }
+
+    fn latency(&self) -> HTTPLatency {
+        if self.count == 0 {
+            return HTTPLatency::default();
+        }
+        HTTPLatency {
+            min: millis(self.min),
+            mean: self.sum as f64 / self.count as f64 / 1000.0,
+            p50: self.percentile(0.5),
+            p90: self.percentile(0.9),
+            p99: self.percentile(0.99),
+            max: millis(self.max),
+        }
+    }
+}
+
+fn millis(micros: u64) -> f64 {
+    micros as f64 / 1000.0
+}
+
+#[derive(Default)]
+struct Recorder {
+    latencies: Histogram,
+    responses: u64,
+    /// Sums over the responses, in the order of [`HTTPLoadPhases`].
+    phases: [u64; 6],
+    bytes: u64,
+    statuses: BTreeMap<u16, u64>,
+    errors: BTreeMap<String, u64>,
+}
+
+impl Recorder {
+    fn record(&mut self, latency: Duration, result: &Result<HTTPResponse, APIError>) {
+        self.latencies.record(latency);
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                let kind = match e {
+                    APIError::Timeout(phase, _) => format!("Timeout ({:?})", phase),
+                    e => e.to_string(),
+                };
+                *self.errors.entry(kind).or_default() += 1;
+                return;
+            }
+        };
+        self.responses += 1;
+        self.bytes += response.length;
+        *self.statuses.entry(response.status).or_default() += 1;
+        let stats = &response.stats;
+        let phases = [
+            stats.dns_lookup,
+            stats.tcp,
+            stats.tls,
+            stats.send,
+            stats.server_processing,
+            stats.content_transfer,
+        ];
+        for (sum, phase) in self.phases.iter_mut().zip(phases) {
+            *sum += phase as u64;
+        }
+    }
+
+    fn report(&self, id: &str, elapsed: Duration, done: bool) -> HTTPLoadReport {
+        let requests = self.latencies.count;
+        let mean = |sum: u64| match self.responses {
+            0 => 0.0,
+            n => sum as f64 / n as f64,
+        };
+        let [dns_lookup, tcp, tls, send, server_processing, content_transfer] = self.phases;
+        let secs = elapsed.as_secs_f64();
+        HTTPLoadReport {
+            id: id.to_string(),
+            done,
+            requests,
+            elapsed: elapsed.as_millis() as u32,
+            throughput: if secs > 0.0 {
+                requests as f64 / secs
+            } else {
+                0.0
+            },
+            bytes: self.bytes,
+            statuses: self.statuses.clone(),
+            errors: self.errors.clone(),
+            latency: self.latencies.latency(),
+            phases: HTTPLoadPhases {
+                dns_lookup: mean(dns_lookup),
+                tcp: mean(tcp),
+                tls: mean(tls),
+                send: mean(send),
+                server_processing: mean(server_processing),
+                content_transfer: mean(content_transfer),
+            },
+        }
+    }
+}
+
+/// Sends `request` over and over on one pooled client, `concurrency` at a time, until
+/// `requests` were sent, `duration` passed or [`stop`] is called. `{{name}}` placeholders
+/// are resolved for every request so dynamic values change between them.
+pub async fn run(
+    mut request: HTTPRequest,
+    options: HTTPLoadOptions,
+    on_progress: Option<LoadListener>,
+) -> ApiResult<HTTPLoadReport> {
+    if options.requests.is_none() && options.duration.is_none() {
+        return Err(APIError::General(
+            "Set a number of requests or a duration".to_string(),
+        ));
+    }
+    let variables = match request.environment.take() {
+        Some(name) => Variables::from(&HTTPEnvironment::load(&name)?),
+        None => Variables::default(),
+    };
+    // fails on unresolved placeholders before anything is sent
+    let mut first = request.clone();
+    first.resolve_variables(&variables)?;
+    let timeout = options.timeout.clone().unwrap_or_default();
+    let client = HTTPClient::new(&first, &timeout)?;
+
+    let id = options.id.clone();
+    let stopped = Arc::new(AtomicBool::new(false));
+    RUNNING_LOAD_TESTS
+        .lock()
+        .map_err(|e| APIError::General(e.to_string()))?
+        .insert(id.clone(), stopped.clone());
+    let started = Instant::now();
+    let deadline = options
+        .duration
+        .map(|secs| started + Duration::from_secs(secs));
+    let pacer = options.rate.filter(|rate| *rate > 0.0).map(|rate| {
+        let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        Arc::new(tokio::sync::Mutex::new(interval))
+    });
+    let issued = Arc::new(AtomicU64::new(0));
+    let recorder = Arc::new(Mutex::new(Recorder::default()));
+    // bodies are only counted, neither buffered nor copied to disk
+    request.stream = true;
+    request.skip_body_file = true;
+    let request = Arc::new(request);
+    let variables = Arc::new(variables);
+
+    let mut workers = JoinSet::new();
+    for _ in 0..options.concurrency.max(1) {
+        let (client, request, variables) = (client.clone(), request.clone(), variables.clone());
+        let (stopped, pacer, issued) = (stopped.clone(), pacer.clone(), issued.clone());
+        let (recorder, timeout, max) = (recorder.clone(), timeout.clone(), options.requests);
+        workers.spawn(async move {
+            let running =
+                || !stopped.load(Ordering::Relaxed) && deadline.is_none_or(|d| Instant::now() < d);
+            while running() {
+                if max.is_some_and(|max| issued.fetch_add(1, Ordering::Relaxed) >= max) {
+                    break;
+                }
+                if let Some(pacer) = &pacer {
+                    pacer.lock().await.tick().await;
+                    if !running() {
+                        break;
+                    }
+                }
+                let mut request = (*request).clone();
+                let sent = Instant::now();
+                let result = match request.resolve_variables(&variables) {
+                    Ok(()) => {
+                        let client = Some(client.clone());
+                        http::request_on(client, request, Some(timeout.clone()), None).await
+                    }
+                    Err(e) => Err(e),
+                };
+                if let Ok(mut recorder) = recorder.lock() {
+                    recorder.record(sent.elapsed(), &result);
+                }
+            }
+        });
+    }
+
+    let report = |done: bool| {
+        recorder
+            .lock()
+            .map(|recorder| recorder.report(&id, started.elapsed(), done))
+            .map_err(|e| APIError::General(e.to_string()))
+    };
+    let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            joined = workers.join_next() => match joined {
+                Some(Err(e)) => log::error!("Load test worker failed: {}", e),
+                Some(Ok(())) => {}
+                None => break,
+            },
+            _ = ticker.tick() => {
+                if let Some(listener) = &on_progress {
+                    listener(report(false)?);
+                }
+            }
+        }
+    }
+    if let Ok(mut running) = RUNNING_LOAD_TESTS.lock() {
+        running.remove(&id);
+    }
+    let report = report(true)?;
+    if let Some(listener) = &on_progress {
+        listener(report.clone());
+    }
+    Ok(report)
+}
+
+/// Stops load test `id` once its requests in flight complete, false when it is not running.
+pub fn stop(id: &str) -> bool {
+    let running = match RUNNING_LOAD_TESTS.lock() {
+        Ok(running) => running,
+        Err(_) => return false,
+    };
+    match running.get(id) {
+        Some(stopped) => {
+            stopped.store(true, Ordering::Relaxed);
+            true
+        }
+        None => false,
+    }
+}
+
+pub mod commands {
+    use super::*;
+    use tauri::{command, AppHandle, Emitter};
+
+    #[command(async)]
+    pub async fn http_load_test(
+        app: AppHandle,
+        req: HTTPRequest,
+        options: HTTPLoadOptions,
+    ) -> ApiResult<HTTPLoadReport> {
+        log::info!("Load testing {} {} with {:?}", req.method, req.url, options);
+        let on_progress: LoadListener = Box::new(move |report| {
+            if let Err(e) = app.emit(format!("http_load_{}", report.id).as_str(), &report) {
+                log::error!("Failed to emit load test progress: {}", e);
+            }
+        });
+        run(req, options, Some(on_progress)).await
+    }
+
+    #[command(async)]
+    pub async fn http_stop_load_test(id: String) -> ApiResult<bool> {
+        log::info!("Stopping load test {}", id);
+        Ok(stop(&id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::http_proxy::HTTPProxySettings;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers every request on a connection with `ok` and keeps the connection open.
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    while let Ok(n) = socket.read(&mut buf).await {
+                        if n == 0 {
+                            return;
+                        }
+                        let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+                        if socket.write_all(response.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn get(url: String) -> HTTPRequest {
+        HTTPRequest {
+            method: "GET".to_string(),
+            url,
+            proxy: Some(HTTPProxySettings::Direct),
+            ..Default::default()
+        }
+    }
+
+    fn options(requests: u64, concurrency: usize) -> HTTPLoadOptions {
+        HTTPLoadOptions {
+            id: new_id(),
+            requests: Some(requests),
+            duration: None,
+            concurrency,
+            rate: None,
+            timeout: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_report() {
+        let base = serve().await;
+        let report = run(get(format!("{}/ping", base)), options(40, 4), None)
+            .await
+            .expect("load test");
+        assert!(report.done);
+        assert_eq!(report.requests, 40);
+        assert_eq!(report.statuses, BTreeMap::from([(200, 40)]));
+        assert_eq!(report.bytes, 80);
+        assert!(report.errors.is_empty());
+        let latency = &report.latency;
+        assert!(latency.min <= latency.p50 && latency.p50 <= latency.p99);
+        assert!(latency.p99 <= latency.max);
+
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", closed.local_addr().unwrap());
+        drop(closed);
+        let report = run(get(url), options(3, 1), None).await.expect("load test");
+        assert_eq!(report.requests, 3);
+        assert!(report.statuses.is_empty());
+        assert_eq!(report.errors.values().sum::<u64>(), 3);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::default();
+        for ms in 1..=100 {
+            histogram.record(Duration::from_millis(ms));
+        }
+        assert!((histogram.percentile(0.5) - 50.0).abs() < 0.5);
+        assert!((histogram.percentile(0.99) - 99.0).abs() < 1.0);
+        assert_eq!(histogram.percentile(1.0), 100.0);
+        let latency = histogram.latency();
+        assert_eq!((latency.min, latency.mean, latency.max), (1.0, 50.5, 100.0));
+
+        let mut single = Histogram::default();
+        single.record(Duration::from_millis(7));
+        assert_eq!(single.percentile(0.9), 7.0);
+        assert_eq!(Histogram::highest(Histogram::index(u64::MAX)), u64::MAX);
+    }
+}
@@ -0,0 +1,818 @@
+use crate::errors::{APIError, ApiResult};
+use crate::services::http::{self, HTTPClients, HTTPResponse, RequestTimeout};
+use crate::services::http_assert::{self, HTTPTestReport};
+use crate::services::http_collection::{HTTPCollectionItem, HTTPFolder, HTTPSavedRequest};
+use crate::services::http_env::{HTTPEnvironment, Variables};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use std::cell::OnceCell;
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+/// Takes a value from a response into `variable` for the requests run after it.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HTTPExtraction {
+    /// The first value an RFC 9535 JSONPath selects from the body.
+    JsonPath {
+        variable: String,
+        path: String,
+    },
+    Header {
+        variable: String,
+        name: String,
+    },
+    /// The first capture group of the first match in the body, the whole match without groups.
+    Regex {
+        variable: String,
+        pattern: String,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPRunOptions {
+    /// Progress is emitted as `http_run_{id}`.
+    #[serde(default = "new_id")]
+    pub id: String,
+    /// Id of the folder to run, the whole collection by default.
+    #[serde(default)]
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub environment: Option<String>,
+    /// CSV file with a header row or JSON array of objects. The requests run once per row
+    /// with its values as variables.
+    #[serde(default)]
+    pub data_file: Option<String>,
+    /// How many times the requests run when there is no data file.
+    #[serde(default = "one")]
+    pub iterations: usize,
+    /// Stops at the first request that fails.
+    #[serde(default)]
+    pub bail: bool,
+    #[serde(default)]
+    pub timeout: Option<RequestTimeout>,
+}
+
+impl Default for HTTPRunOptions {
+    fn default() -> Self {
+        HTTPRunOptions {
+            id: new_id(),
+            folder: None,
+            environment: None,
+            data_file: None,
+            iterations: 1,
+            bail: false,
+            timeout: None,
+        }
+    }
+}
+
+fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+fn one() -> usize {
+    1
+}
+
+/// The outcome of sending one request of the run.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPRunResult {
+    /// Starts at 1.
+    pub iteration: usize,
+    pub id: String,
+    pub name: String,
+    /// Names of the folders leading to the request, the collection excluded.
+    pub path: Vec<String>,
+    pub method: String,
+    pub url: String,
+    pub status: Option<u16>,
+    /// In ms.
+    pub duration: u32,
+    /// Why the request could not be sent or a value could not be extracted.
+    pub error: Option<String>,
+    pub tests: Option<HTTPTestReport>,
+    pub passed: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPRunSummary {
+    pub id: String,
+    pub collection: String,
+    /// Unix timestamp in ms.
+    pub started: i64,
+    /// In ms.
+    pub duration: u32,
+    pub iterations: usize,
+    pub passed: usize,
+    /// Results that did not pass, `errors` included.
+    pub failed: usize,
+    pub errors: usize,
+    pub results: Vec<HTTPRunResult>,
+}
+
+/// Emitted after every request of a run.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPRunProgress {
+    pub id: String,
+    pub completed: usize,
+    pub total: usize,
+    pub result: HTTPRunResult,
+}
+
+pub type RunListener = Box<dyn Fn(HTTPRunProgress) + Send + Sync>;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum HTTPRunReportFormat {
+    Junit,
+    Json,
+}
+
+impl HTTPExtraction {
+    fn variable(&self) -> &str {
+        match self {
+            HTTPExtraction::JsonPath { variable, .. }
+            | HTTPExtraction::Header { variable, .. }
+            | HTTPExtraction::Regex { variable, .. } => variable,
+        }
+    }
+
+    fn extract(&self, response: &HTTPResponse, text: &OnceCell<String>) -> Result<String, String> {
+        let body = || {
+            text.get_or_init(|| http_assert::body_text(response))
+                .as_str()
+        };
+        match self {
+            HTTPExtraction::JsonPath { path, .. } => {
+                let json: Value =
+                    serde_json::from_str(body()).map_err(|e| format!("Body is not JSON: {}", e))?;
+                let path = JsonPath::parse(path).map_err(|e| format!("Invalid JSONPath: {}", e))?;
+                let value = path.query(&json).all().into_iter().next();
+                value
+                    .map(http_assert::json_text)
+                    .ok_or_else(|| "Nothing selected".to_string())
+            }
+            HTTPExtraction::Header { name, .. } => response
+                .headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .and_then(|(_, values)| values.first().cloned())
+                .ok_or_else(|| format!("Missing header {}", name)),
+            HTTPExtraction::Regex { pattern, .. } => {
+                let regex = Regex::new(pattern).map_err(|e| format!("Invalid pattern: {}", e))?;
+                let captures = regex
+                    .captures(body())
+                    .ok_or_else(|| format!("Body does not match {}", pattern))?;
+                let found = captures.get(1).or_else(|| captures.get(0));
+                Ok(found.map_or("", |m| m.as_str()).to_string())
+            }
+        }
+    }
+}
+
+/// A saved request with the folders leading to it.
+struct Step<'a> {
+    path: Vec<String>,
+    saved: &'a HTTPSavedRequest,
+}
+
+fn steps<'a>(folder: &'a HTTPFolder, path: &mut Vec<String>, steps: &mut Vec<Step<'a>>) {
+    for item in &folder.items {
+        match item {
+            HTTPCollectionItem::Folder(folder) => {
+                path.push(folder.name.clone());
+                self::steps(folder, path, steps);
+                path.pop();
+            }
+            HTTPCollectionItem::Request(saved) => steps.push(Step {
+                path: path.clone(),
+                saved,
+            }),
+        }
+    }
+}
+
+/// Names of the folders below `folder` leading to folder `id`, `id` included.
+fn folder_path(folder: &HTTPFolder, id: &str) -> Option<Vec<String>> {
+    if folder.id == id {
+        return Some(vec![]);
+    }
+    folder.items.iter().find_map(|item| match item {
+        HTTPCollectionItem::Folder(child) => folder_path(child, id).map(|mut path| {
+            path.insert(0, child.name.clone());
+            path
+        }),
+        HTTPCollectionItem::Request(_) => None,
+    })
+}
+
+/// Rows of a CSV file with a header row or of a JSON array of objects.
+pub fn read_data_file(file: &str) -> ApiResult<Vec<Variables>> {
+    let file = crate::utils::expand_tilde(file);
+    let is_json = file.extension().is_some_and(|ext| ext == "json");
+    if is_json {
+        let rows: Vec<serde_json::Map<String, Value>> =
+            serde_json::from_str(&fs::read_to_string(&file)?)?;
+        return Ok(rows
+            .iter()
+            .map(|row| {
+                let mut variables = Variables::default();
+                for (key, value) in row {
+                    variables.insert(key, &http_assert::json_text(value));
+                }
+                variables
+            })
+            .collect());
+    }
+    let mut reader = csv::Reader::from_path(&file)?;
+    let headers = reader.headers()?.clone();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let mut variables = Variables::default();
+        for (key, value) in headers.iter().zip(record?.iter()) {
+            variables.insert(key, value);
+        }
+        rows.push(variables);
+    }
+    Ok(rows)
+}
+
+/// Sends the requests of `collection` in order, once per iteration or data file row.
+/// Variables come from the environment, then the row, then what earlier requests of the
+/// same iteration extracted. Connections are kept alive across the whole run.
+pub async fn run(
+    collection: &HTTPFolder,
+    options: &HTTPRunOptions,
+    on_progress: Option<RunListener>,
+) -> ApiResult<HTTPRunSummary> {
+    let folder = match &options.folder {
+        Some(id) if *id != collection.id => match collection.find(id) {
+            Some(HTTPCollectionItem::Folder(folder)) => folder,
+            _ => return Err(APIError::General(format!("Folder {} not found", id))),
+        },
+        _ => collection,
+    };
+    let mut path = folder_path(collection, &folder.id).unwrap_or_default();
+    let mut all_steps = Vec::new();
+    steps(folder, &mut path, &mut all_steps);
+
+    let environment = match &options.environment {
+        Some(name) => Variables::from(&HTTPEnvironment::load(name)?),
+        None => Variables::default(),
+    };
+    let rows = match &options.data_file {
+        Some(file) => read_data_file(file)?,
+        None => vec![Variables::default(); options.iterations.max(1)],
+    };
+
+    let started = chrono::Utc::now().timestamp_millis();
+    let clock = Instant::now();
+    let total = all_steps.len() * rows.len();
+    let clients = HTTPClients::default();
+    let mut results = Vec::with_capacity(total);
+    'run: for (i, row) in rows.iter().enumerate() {
+        let mut variables = environment.clone();
+        variables.extend(row);
+        for step in &all_steps {
+            let result = send(collection, &clients, step, i + 1, &mut variables, options).await;
+            let passed = result.passed;
+            if let Some(listener) = &on_progress {
+                listener(HTTPRunProgress {
+                    id: options.id.clone(),
+                    completed: results.len() + 1,
+                    total,
+                    result: result.clone(),
+                });
+            }
+            results.push(result);
+            if options.bail && !passed {
+                break 'run;
+            }
+        }
+    }
+
+    let passed = results.iter().filter(|r| r.passed).count();
+    Ok(HTTPRunSummary {
+        id: options.id.clone(),
+        collection: collection.name.clone(),
+        started,
+        duration: clock.elapsed().as_millis() as u32,
+        iterations: rows.len(),
+        passed,
+        failed: results.len() - passed,
+        errors: results.iter().filter(|r| r.error.is_some()).count(),
+        results,
+    })
+}
+
+async fn send(
+    collection: &HTTPFolder,
+    clients: &HTTPClients,
+    step: &Step<'_>,
+    iteration: usize,
+    variables: &mut Variables,
+    options: &HTTPRunOptions,
+) -> HTTPRunResult {
+    let saved = step.saved;
+    let mut result = HTTPRunResult {
+        iteration,
+        id: saved.id.clone(),
+        name: saved.name.clone(),
+        path: step.path.clone(),
+        method: saved.request.method.to_uppercase(),
+        url: saved.request.url.clone(),
+        status: None,
+        duration: 0,
+        error: None,
+        tests: None,
+        passed: false,
+    };
+    let mut request = match collection.request(&saved.id) {
+        Some(request) => request,
+        None => {
+            result.error = Some(format!("Request {} not found", saved.id));
+            return result;
+        }
+    };
+    request.id = None;
+    request.environment = None;
+    request.stream = false;
+    // run bodies are checked in memory, copies on disk would only evict the app's
+    request.skip_body_file = true;
+    if let Err(e) = request.resolve_variables(variables) {
+        result.error = Some(e.to_string());
+        return result;
+    }
+    result.url = request.url.clone();
+    let client = match clients.get(&request, &options.timeout.clone().unwrap_or_default()) {
+        Ok(client) => client,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+
+    let clock = Instant::now();
+    let response = http::request_on(client, request.clone(), options.timeout.clone(), None).await;
+    result.duration = clock.elapsed().as_millis() as u32;
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+    result.status = Some(response.status);
+    result.tests = http_assert::run(&request.assertions, &response);
+
+    let text = OnceCell::new();
+    let mut failures = Vec::new();
+    for extraction in &saved.extract {
+        match extraction.extract(&response, &text) {
+            Ok(value) => variables.insert(extraction.variable(), &value),
+            Err(e) => failures.push(format!(
+                "Could not extract {}: {}",
+                extraction.variable(),
+                e
+            )),
+        }
+    }
+    if !failures.is_empty() {
+        result.error = Some(failures.join(", "));
+    }
+    result.passed = result.error.is_none() && result.tests.as_ref().is_none_or(|t| t.failed == 0);
+    result
+}
+
+pub fn report(summary: &HTTPRunSummary, format: HTTPRunReportFormat) -> ApiResult<String> {
+    match format {
+        HTTPRunReportFormat::Junit => Ok(junit(summary)),
+        HTTPRunReportFormat::Json => Ok(serde_json::to_string_pretty(summary)?),
+    }
+}
+
+/// A JUnit XML report with a test suite per iteration and a test case per request.
+pub fn junit(summary: &HTTPRunSummary) -> String {
+    let seconds = |ms: u32| format!("{:.3}", ms as f64 / 1000.0);
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{}\">\n",
+        escape(&summary.collection),
+        summary.results.len(),
+        summary.failed - summary.errors,
+        summary.errors,
+        seconds(summary.duration)
+    ));
+    let timestamp = chrono::DateTime::from_timestamp_millis(summary.started)
+        .unwrap_or_default()
+        .format("%Y-%m-%dT%H:%M:%S");
+    for iteration in 1..=summary.iterations {
+        let results: Vec<&HTTPRunResult> = summary
+            .results
+            .iter()
+            .filter(|r| r.iteration == iteration)
+            .collect();
+        if results.is_empty() {
+            continue;
+        }
+        let name = match summary.iterations {
+            1 => summary.collection.clone(),
+            _ => format!("{} #{}", summary.collection, iteration),
+        };
+        let errors = results.iter().filter(|r| r.error.is_some()).count();
+        let failures = results.iter().filter(|r| !r.passed).count() - errors;
+        let time: u32 = results.iter().map(|r| r.duration).sum();
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{}\" \
+             timestamp=\"{}\">\n",
+            escape(&name),
+            results.len(),
+            failures,
+            errors,
+            seconds(time),
+            timestamp
+        ));
+        for result in results {
+            let classname = std::iter::once(&summary.collection)
+                .chain(&result.path)
+                .map(|name| name.replace('.', "_"))
+                .collect::<Vec<_>>()
+                .join(".");
+            xml.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{}\"",
+                escape(&classname),
+                escape(&format!("{} {}", result.method, result.name)),
+                seconds(result.duration)
+            ));
+            if result.passed {
+                xml.push_str("/>\n");
+                continue;
+            }
+            xml.push_str(">\n");
+            if let Some(error) = &result.error {
+                xml.push_str(&format!(
+                    "      <error message=\"{}\">{}</error>\n",
+                    escape(error),
+                    escape(&result.url)
+                ));
+            }
+            let failed = result
+                .tests
+                .iter()
+                .flat_map(|t| &t.results)
+                .filter(|r| !r.passed);
+            for assertion in failed {
+                let actual = assertion.actual.as_deref().unwrap_or("nothing");
+                xml.push_str(&format!(
+                    "      <failure message=\"{}\" type=\"assertion\">{}\nActual: {}</failure>\n",
+                    escape(&assertion.message),
+                    escape(&assertion.assertion.to_string()),
+                    escape(actual)
+                ));
+            }
+            xml.push_str("    </testcase>\n");
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub mod commands {
+    use super::*;
+    use tauri::{command, AppHandle, Emitter};
+
+    #[command(async)]
+    pub async fn http_run_collection(
+        app: AppHandle,
+        collection: String,
+        options: HTTPRunOptions,
+    ) -> ApiResult<HTTPRunSummary> {
+        log::info!("Running collection {}", collection);
+        let collection = HTTPFolder::load(&collection)?;
+        let on_progress: RunListener = Box::new(move |progress| {
+            if let Err(e) = app.emit(format!("http_run_{}", progress.id).as_str(), &progress) {
+                log::error!("Failed to emit run progress: {}", e);
+            }
+        });
+        run(&collection, &options, Some(on_progress)).await
+    }
+
+    #[command(async)]
+    pub async fn http_run_report(
+        summary: HTTPRunSummary,
+        format: HTTPRunReportFormat,
+    ) -> ApiResult<String> {
+        report(&summary, format)
+    }
+}
+
+/// `run` subcommand of the app binary, for running collections in CI.
+pub mod cli {
+    use super::*;
+
+    const USAGE: &str = "Usage: msms-dev-tools run <collection name or file> [options]
+
+Options:
+  --folder <id>        Run the requests of this folder only
+  --env <name>         Resolve {{variables}} from this environment
+  --data <file>        Run once per row of a CSV or JSON data file
+  --iterations <n>     Run n times when there is no data file
+  --bail               Stop at the first failing request
+  --junit <file>       Write a JUnit XML report
+  --json <file>        Write a JSON summary
+  --data-dir <dir>     Where saved collections and environments are read from";
+
+    struct Args {
+        collection: String,
+        options: HTTPRunOptions,
+        junit: Option<String>,
+        json: Option<String>,
+        data_dir: Option<String>,
+    }
+
+    fn parse(args: &[String]) -> Result<Args, String> {
+        let mut parsed = Args {
+            collection: String::new(),
+            options: HTTPRunOptions::default(),
+            junit: None,
+            json: None,
+            data_dir: None,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "--folder" => parsed.options.folder = Some(value()?),
+                "--env" => parsed.options.environment = Some(value()?),
+                "--data" => parsed.options.data_file = Some(value()?),
+                "--iterations" => {
+                    parsed.options.iterations = value()?
+                        .parse()
+                        .map_err(|e| format!("Invalid --iterations: {}", e))?
+                }
+                "--bail" => parsed.options.bail = true,
+                "--junit" => parsed.junit = Some(value()?),
+                "--json" => parsed.json = Some(value()?),
+                "--data-dir" => parsed.data_dir = Some(value()?),
+                "-h" | "--help" => return Err(String::new()),
+                arg if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+                arg if parsed.collection.is_empty() => parsed.collection = arg.to_string(),
+                arg => return Err(format!("Unexpected argument {}", arg)),
+            }
+        }
+        if parsed.collection.is_empty() {
+            return Err("Missing collection".to_string());
+        }
+        Ok(parsed)
+    }
+
+    /// Runs a collection headlessly, printing a line per request. Returns the exit code:
+    /// 0 when everything passed, 1 when something failed and 2 when the run could not start.
+    pub fn main(args: &[String]) -> i32 {
+        let args = match parse(args) {
+            Ok(args) => args,
+            Err(e) => {
+                if !e.is_empty() {
+                    eprintln!("{}\n", e);
+                }
+                eprintln!("{}", USAGE);
+                return 2;
+            }
+        };
+        let data_dir = match &args.data_dir {
+            Some(dir) => crate::utils::expand_tilde(dir),
+            None => dirs_next::data_dir()
+                .unwrap_or_default()
+                .join("work.msms.dev-tools"),
+        };
+        crate::store::init(data_dir);
+
+        let runtime = match tokio::runtime::Runtime::new() {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                eprintln!("Failed to start the runtime: {}", e);
+                return 2;
+            }
+        };
+        match runtime.block_on(run_and_report(&args)) {
+            Ok(summary) if summary.failed == 0 => 0,
+            Ok(_) => 1,
+            Err(e) => {
+                eprintln!("{}", e);
+                2
+            }
+        }
+    }
+
+    async fn run_and_report(args: &Args) -> ApiResult<HTTPRunSummary> {
+        let path = Path::new(&args.collection);
+        let collection = if path.is_file() {
+            HTTPFolder::read(path)?
+        } else {
+            HTTPFolder::load(&args.collection)?
+        };
+        let on_progress: RunListener = Box::new(|progress| {
+            let result = &progress.result;
+            let outcome = match (&result.error, result.passed) {
+                (Some(e), _) => format!("ERROR {}", e),
+                (None, true) => "PASS".to_string(),
+                (None, false) => "FAIL".to_string(),
+            };
+            println!(
+                "[{}/{}] {} {} {} {} ({} ms)",
+                progress.completed,
+                progress.total,
+                outcome,
+                result.method,
+                result.url,
+                result.status.map_or("-".to_string(), |s| s.to_string()),
+                result.duration
+            );
+            let failed = result
+                .tests
+                .iter()
+                .flat_map(|t| &t.results)
+                .filter(|r| !r.passed);
+            for assertion in failed {
+                let actual = assertion.actual.as_deref().unwrap_or("nothing");
+                println!("    {}, got {}", assertion.message, actual);
+            }
+        });
+        let summary = run(&collection, &args.options, Some(on_progress)).await?;
+        println!(
+            "\n{} passed, {} failed, {} errors in {} ms",
+            summary.passed,
+            summary.failed - summary.errors,
+            summary.errors,
+            summary.duration
+        );
+        if let Some(file) = &args.junit {
+            fs::write(file, report(&summary, HTTPRunReportFormat::Junit)?)?;
+        }
+        if let Some(file) = &args.json {
+            fs::write(file, report(&summary, HTTPRunReportFormat::Json)?)?;
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::http::{HTTPRequest, HTTPRequestKVParam};
+    use crate::services::http_assert::HTTPAssertion;
+    use crate::services::http_proxy::HTTPProxySettings;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers `/login?user=<name>` with a token for the user and `/me` with 200 only
+    /// when that token is sent back.
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = vec![0u8; 4096];
+                    let n = socket.read(&mut buf).await.unwrap_or(0);
+                    let head = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                    let (status, body) = if let Some(rest) = head.strip_prefix("get /login?user=") {
+                        let user = rest.split(' ').next().unwrap_or_default();
+                        ("200 OK", format!("{{\"token\":\"t-{}\"}}", user))
+                    } else if head.contains("authorization: bearer t-ada") {
+                        ("200 OK", "{\"name\":\"ada\"}".to_string())
+                    } else {
+                        ("401 Unauthorized", "{}".to_string())
+                    };
+                    let response = format!(
+                        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                         Connection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    );
+                    let _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn saved(name: &str, request: HTTPRequest, extract: Vec<HTTPExtraction>) -> HTTPCollectionItem {
+        HTTPCollectionItem::Request(HTTPSavedRequest {
+            id: name.to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            request,
+            extract,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_run_chains_variables_per_row() {
+        crate::store::init_for_tests();
+        let dir = tempfile::tempdir().unwrap();
+        let data_file = dir.path().join("users.csv");
+        fs::write(&data_file, "user\nada\nbob\n").unwrap();
+
+        let base = serve().await;
+        let request = |url: String, assertions| HTTPRequest {
+            method: "GET".to_string(),
+            url,
+            proxy: Some(HTTPProxySettings::Direct),
+            assertions,
+            ..Default::default()
+        };
+        let mut me = request(
+            format!("{}/me", base),
+            vec![HTTPAssertion::Status { equals: 200 }],
+        );
+        me.headers.push(HTTPRequestKVParam {
+            key: "Authorization".to_string(),
+            value: "Bearer {{token}}".to_string(),
+            enabled: true,
+        });
+        let collection = HTTPFolder {
+            name: "users".to_string(),
+            items: vec![
+                saved(
+                    "login",
+                    request(format!("{}/login?user={{{{user}}}}", base), vec![]),
+                    vec![HTTPExtraction::JsonPath {
+                        variable: "token".to_string(),
+                        path: "$.token".to_string(),
+                    }],
+                ),
+                HTTPCollectionItem::Folder(HTTPFolder {
+                    name: "account".to_string(),
+                    items: vec![saved("me", me, vec![])],
+                    ..Default::default()
+                }),
+            ],
+            ..Default::default()
+        };
+        let options = HTTPRunOptions {
+            data_file: Some(data_file.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+
+        let summary = run(&collection, &options, None).await.expect("run");
+        let outcomes: Vec<(usize, &str, bool)> = summary
+            .results
+            .iter()
+            .map(|r| (r.iteration, r.id.as_str(), r.passed))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (1, "login", true),
+                (1, "me", true),
+                (2, "login", true),
+                (2, "me", false)
+            ]
+        );
+        assert_eq!((summary.passed, summary.failed, summary.errors), (3, 1, 0));
+
+        let xml = junit(&summary);
+        assert!(xml.contains("<testsuite name=\"users #2\" tests=\"2\" failures=\"1\""));
+        assert!(xml.contains("<testcase classname=\"users.account\" name=\"GET me\""));
+        assert!(xml.contains("<failure message=\"Expected status 200\""));
+
+        let mut summary = summary;
+        let failed = summary.results.iter_mut().find(|r| !r.passed).unwrap();
+        failed.tests.as_mut().unwrap().results[0].assertion = HTTPAssertion::Header {
+            name: "X-Id".to_string(),
+            matches: Some("<a&b>".to_string()),
+        };
+        assert!(junit(&summary).contains(">header X-Id matches &lt;a&amp;b&gt;\nActual: "));
+    }
+}
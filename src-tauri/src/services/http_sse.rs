@@ -0,0 +1,551 @@
+use crate::errors::{APIError, ApiResult};
+use crate::services::http::{
+    self, HTTPClient, HTTPCompression, HTTPRequest, HTTPRequestKVParam, RequestTimeout,
+};
+use once_cell::sync::Lazy;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Stop signals of the subscriptions running, keyed by id.
+static RUNNING_SUBSCRIPTIONS: Lazy<Mutex<HashMap<String, Arc<Notify>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPSseOptions {
+    /// Events are emitted as `http_sse_{id}`, state changes as `http_sse_state_{id}`.
+    #[serde(default = "new_id")]
+    pub id: String,
+    /// Resumes the stream after this event id.
+    #[serde(default)]
+    pub last_event_id: Option<String>,
+    /// Delay in ms before reconnecting, until the server sends a `retry` hint.
+    #[serde(default = "default_retry")]
+    pub retry: u64,
+    /// Gives up after this many reconnects in a row without an event, never when unset.
+    #[serde(default)]
+    pub max_reconnects: Option<u32>,
+    #[serde(default)]
+    pub timeout: Option<RequestTimeout>,
+}
+
+impl Default for HTTPSseOptions {
+    fn default() -> Self {
+        HTTPSseOptions {
+            id: new_id(),
+            last_event_id: None,
+            retry: default_retry(),
+            max_reconnects: None,
+            timeout: None,
+        }
+    }
+}
+
+fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+fn default_retry() -> u64 {
+    3000
+}
+
+/// An event dispatched by the server.
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPSseEvent {
+    pub seq: u64,
+    /// Unix timestamp in ms.
+    pub time: i64,
+    /// The `event` field, `message` when the server left it out.
+    pub event: String,
+    /// The `data` lines, joined by newlines.
+    pub data: String,
+    /// The last `id` the server sent, sent back as `Last-Event-ID` when reconnecting.
+    pub id: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum HTTPSseState {
+    /// A connection attempt started, 0 for the first one.
+    Connecting { attempt: u32 },
+    Open {
+        status: u16,
+        headers: HashMap<String, Vec<String>>,
+    },
+    /// The stream broke, a new connection is attempted after `delay` ms.
+    Reconnecting { error: String, delay: u64 },
+    /// The subscription ended: stopped, refused by the server or out of reconnects.
+    Closed { error: Option<String> },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPSseSummary {
+    pub id: String,
+    pub events: u64,
+    pub reconnects: u32,
+    pub last_event_id: Option<String>,
+    /// Why the subscription ended, None when it was stopped or the server sent 204.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum HTTPSseMessage {
+    Event(HTTPSseEvent),
+    State(HTTPSseState),
+}
+
+pub type SseListener = Box<dyn Fn(HTTPSseMessage) + Send + Sync>;
+
+/// Incremental `text/event-stream` parser following the WHATWG interpretation rules.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    /// Bytes of the line being received.
+    line: Vec<u8>,
+    /// A CR ended the last line, so a LF right after it is part of the same line break.
+    after_cr: bool,
+    started: bool,
+    event: String,
+    data: String,
+    has_data: bool,
+    /// The last event id buffer, kept across events and connections.
+    pub last_event_id: Option<String>,
+    /// The latest `retry` hint in ms.
+    pub retry: Option<u64>,
+}
+
+impl SseParser {
+    /// Feeds the next bytes of the stream, returns the events they complete.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<HTTPSseEvent> {
+        let mut events = Vec::new();
+        for &byte in chunk {
+            let after_cr = std::mem::take(&mut self.after_cr);
+            match byte {
+                b'\n' if after_cr => {}
+                b'\n' | b'\r' => {
+                    self.after_cr = byte == b'\r';
+                    let line = std::mem::take(&mut self.line);
+                    events.extend(self.line_done(&String::from_utf8_lossy(&line)));
+                }
+                _ => self.line.push(byte),
+            }
+        }
+        events
+    }
+
+    /// Drops what was received of an incomplete event, the stream broke off.
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.after_cr = false;
+        self.started = false;
+        self.event.clear();
+        self.data.clear();
+        self.has_data = false;
+    }
+
+    fn line_done(&mut self, line: &str) -> Option<HTTPSseEvent> {
+        let line = match self.started {
+            true => line,
+            false => {
+                self.started = true;
+                line.strip_prefix('\u{feff}').unwrap_or(line)
+            }
+        };
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => {
+                if self.has_data {
+                    self.data.push('\n');
+                }
+                self.data.push_str(value);
+                self.has_data = true;
+            }
+            "id" if !value.contains('\0') => self.last_event_id = Some(value.to_string()),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                self.retry = value.parse().ok();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<HTTPSseEvent> {
+        let event = std::mem::take(&mut self.event);
+        let data = std::mem::take(&mut self.data);
+        if !std::mem::take(&mut self.has_data) {
+            return None;
+        }
+        Some(HTTPSseEvent {
+            event: if event.is_empty() {
+                "message".to_string()
+            } else {
+                event
+            },
+            data,
+            id: self.last_event_id.clone().filter(|id| !id.is_empty()),
+            ..Default::default()
+        })
+    }
+}
+
+/// How a connection of a subscription ended.
+enum Ended {
+    Stopped,
+    /// Not worth reconnecting, see [`HTTPSseState::Closed`].
+    Refused(Option<String>),
+    Broken(String),
+}
+
+/// Subscribes to the event stream of `request` until stopped with [`stop`], reconnecting
+/// whenever the stream breaks off. Connections share one [`HTTPClient`].
+pub async fn subscribe(
+    mut request: HTTPRequest,
+    options: HTTPSseOptions,
+    on_message: Option<SseListener>,
+) -> ApiResult<HTTPSseSummary> {
+    request.resolve()?;
+    let has_header = |name: &str| {
+        request
+            .headers
+            .iter()
+            .any(|h| h.enabled && h.key.eq_ignore_ascii_case(name))
+    };
+    let mut defaults = vec![];
+    if !has_header("Accept") {
+        defaults.push(header("Accept", "text/event-stream"));
+    }
+    if !has_header("Cache-Control") {
+        defaults.push(header("Cache-Control", "no-cache"));
+    }
+    request.headers.extend(defaults);
+    // the stream is parsed as it arrives, so it is asked for uncompressed
+    request.compression = HTTPCompression::None;
+    let timeout = options.timeout.clone().unwrap_or_default();
+    let client = HTTPClient::new(&request, &timeout)?;
+
+    let stopped = Arc::new(Notify::new());
+    {
+        let mut running = RUNNING_SUBSCRIPTIONS
+            .lock()
+            .map_err(|e| APIError::General(e.to_string()))?;
+        if running.contains_key(&options.id) {
+            return Err(APIError::Http(format!(
+                "Subscription {} is already running",
+                options.id
+            )));
+        }
+        running.insert(options.id.clone(), stopped.clone());
+    }
+
+    let notify = |message: HTTPSseMessage| {
+        if let Some(listener) = &on_message {
+            listener(message);
+        }
+    };
+    let mut parser = SseParser {
+        last_event_id: options.last_event_id.clone(),
+        ..Default::default()
+    };
+    let mut summary = HTTPSseSummary {
+        id: options.id.clone(),
+        events: 0,
+        reconnects: 0,
+        last_event_id: None,
+        error: None,
+    };
+    let mut failures = 0;
+    loop {
+        notify(HTTPSseMessage::State(HTTPSseState::Connecting {
+            attempt: summary.reconnects,
+        }));
+        let mut attempt = request.clone();
+        if let Some(id) = parser.last_event_id.as_ref().filter(|id| !id.is_empty()) {
+            attempt.headers.push(header("Last-Event-ID", id));
+        }
+        let received = summary.events;
+        let ended = tokio::select! {
+            _ = stopped.notified() => Ended::Stopped,
+            ended = read(&client, attempt, &timeout, &mut parser, &mut summary, &notify) => ended,
+        };
+        parser.reset();
+        if summary.events > received {
+            failures = 0;
+        }
+        let error = match ended {
+            Ended::Stopped => break,
+            Ended::Refused(error) => {
+                summary.error = error;
+                break;
+            }
+            Ended::Broken(error) => error,
+        };
+        failures += 1;
+        if options.max_reconnects.is_some_and(|max| failures > max) {
+            summary.error = Some(error);
+            break;
+        }
+        let delay = parser.retry.unwrap_or(options.retry);
+        notify(HTTPSseMessage::State(HTTPSseState::Reconnecting {
+            error,
+            delay,
+        }));
+        tokio::select! {
+            _ = stopped.notified() => break,
+            _ = tokio::time::sleep(Duration::from_millis(delay)) => {}
+        }
+        summary.reconnects += 1;
+    }
+
+    if let Ok(mut running) = RUNNING_SUBSCRIPTIONS.lock() {
+        running.remove(&options.id);
+    }
+    summary.last_event_id = parser.last_event_id.clone();
+    notify(HTTPSseMessage::State(HTTPSseState::Closed {
+        error: summary.error.clone(),
+    }));
+    Ok(summary)
+}
+
+/// Reads one connection of a subscription until it ends.
+async fn read(
+    client: &HTTPClient,
+    request: HTTPRequest,
+    timeout: &RequestTimeout,
+    parser: &mut SseParser,
+    summary: &mut HTTPSseSummary,
+    notify: &impl Fn(HTTPSseMessage),
+) -> Ended {
+    let mut res = match http::open(client, request, timeout).await {
+        Ok(res) => res,
+        Err(e) => return Ended::Broken(e.to_string()),
+    };
+    let status = res.status();
+    if status == StatusCode::NO_CONTENT {
+        return Ended::Refused(None);
+    }
+    if !status.is_success() {
+        return Ended::Refused(Some(format!("server answered {}", status)));
+    }
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !content_type.starts_with("text/event-stream") {
+        return Ended::Refused(Some(format!(
+            "expected text/event-stream, got {:?}",
+            content_type
+        )));
+    }
+    let headers = match http::response_headers(res.headers()) {
+        Ok(headers) => headers,
+        Err(e) => return Ended::Refused(Some(e.to_string())),
+    };
+    notify(HTTPSseMessage::State(HTTPSseState::Open {
+        status: status.as_u16(),
+        headers,
+    }));
+
+    loop {
+        let chunk = match res.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => return Ended::Broken("stream ended".to_string()),
+            Err(e) => return Ended::Broken(APIError::from(e).to_string()),
+        };
+        for mut event in parser.feed(&chunk) {
+            event.seq = summary.events;
+            event.time = chrono::Utc::now().timestamp_millis();
+            summary.events += 1;
+            notify(HTTPSseMessage::Event(event));
+        }
+    }
+}
+
+fn header(key: &str, value: &str) -> HTTPRequestKVParam {
+    HTTPRequestKVParam {
+        key: key.to_string(),
+        value: value.to_string(),
+        enabled: true,
+    }
+}
+
+/// Ends subscription `id`, false when it is not running.
+pub fn stop(id: &str) -> bool {
+    let running = match RUNNING_SUBSCRIPTIONS.lock() {
+        Ok(running) => running,
+        Err(_) => return false,
+    };
+    match running.get(id) {
+        Some(stopped) => {
+            stopped.notify_one();
+            true
+        }
+        None => false,
+    }
+}
+
+pub mod commands {
+    use super::*;
+    use tauri::{command, AppHandle, Emitter};
+
+    #[command(async)]
+    pub async fn http_sse_subscribe(
+        app: AppHandle,
+        req: HTTPRequest,
+        options: HTTPSseOptions,
+    ) -> ApiResult<HTTPSseSummary> {
+        log::info!("Subscribing to events of {}", req.url);
+        let id = options.id.clone();
+        let on_message: SseListener = Box::new(move |message| {
+            let emitted = match &message {
+                HTTPSseMessage::Event(event) => {
+                    app.emit(format!("http_sse_{}", id).as_str(), event)
+                }
+                HTTPSseMessage::State(state) => {
+                    app.emit(format!("http_sse_state_{}", id).as_str(), state)
+                }
+            };
+            if let Err(e) = emitted {
+                log::error!("Failed to emit server-sent event: {}", e);
+            }
+        });
+        subscribe(req, options, Some(on_message)).await
+    }
+
+    #[command(async)]
+    pub async fn http_sse_stop(id: String) -> ApiResult<bool> {
+        log::info!("Stopping subscription {}", id);
+        Ok(stop(&id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::http_proxy::HTTPProxySettings;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parser() {
+        let mut parser = SseParser::default();
+        let stream = "\u{feff}: comment\r\nretry: 250\r\nevent: tick\r\ndata: one\r\ndata:two\r\n\
+            id: 7\r\n\r\ndata\n\nevent: ignored\n\nid\ndata: after reset";
+        let mut events = vec![];
+        // split on every byte to cover lines and line breaks cut between chunks
+        for byte in stream.as_bytes().chunks(1) {
+            events.extend(parser.feed(byte));
+        }
+        let expected = |event: &str, data: &str, id: Option<&str>| HTTPSseEvent {
+            event: event.to_string(),
+            data: data.to_string(),
+            id: id.map(String::from),
+            ..Default::default()
+        };
+        assert_eq!(
+            events,
+            [
+                expected("tick", "one\ntwo", Some("7")),
+                expected("message", "", Some("7")),
+            ]
+        );
+        assert_eq!(parser.retry, Some(250));
+        assert_eq!(parser.last_event_id.as_deref(), Some(""));
+        parser.reset();
+        assert!(parser.feed(b"\n").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_with_last_event_id() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().unwrap();
+        let (heads, mut received_heads) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut connections = 0;
+            while let Ok((mut socket, _)) = listener.accept().await {
+                connections += 1;
+                let mut buf = vec![0u8; 4096];
+                let n = socket.read(&mut buf).await.unwrap_or(0);
+                let _ = heads.send(String::from_utf8_lossy(&buf[..n]).to_lowercase());
+                let (status, body) = match connections {
+                    1 => ("200 OK", "retry: 10\nid: 1\ndata: first\n\ndata: cut off"),
+                    2 => ("200 OK", "id: 2\nevent: update\ndata: second\n\n"),
+                    _ => ("503 Service Unavailable", ""),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: text/event-stream\r\n\
+                    Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let request = HTTPRequest {
+            method: "GET".to_string(),
+            url: format!("http://{}/events", addr),
+            proxy: Some(HTTPProxySettings::Direct),
+            ..Default::default()
+        };
+        let options = HTTPSseOptions {
+            id: "sse-test".to_string(),
+            retry: 5000,
+            max_reconnects: Some(1),
+            ..Default::default()
+        };
+        let events = Arc::new(Mutex::new(vec![]));
+        let seen = events.clone();
+        let on_message: SseListener = Box::new(move |message| {
+            if let HTTPSseMessage::Event(event) = message {
+                seen.lock().unwrap().push(event);
+            }
+        });
+        let summary = subscribe(request, options, Some(on_message))
+            .await
+            .expect("subscription");
+
+        let events = events.lock().unwrap();
+        let received: Vec<_> = events
+            .iter()
+            .map(|e| (e.seq, e.event.as_str(), e.data.as_str(), e.id.as_deref()))
+            .collect();
+        assert_eq!(
+            received,
+            [
+                (0, "message", "first", Some("1")),
+                (1, "update", "second", Some("2")),
+            ]
+        );
+        // the incomplete event is dropped and the refused third connection ends it
+        assert_eq!(summary.events, 2);
+        assert_eq!(summary.reconnects, 2);
+        assert_eq!(summary.last_event_id.as_deref(), Some("2"));
+        assert!(summary.error.unwrap().contains("503"));
+
+        let first = received_heads.recv().await.unwrap();
+        assert!(first.contains("accept: text/event-stream"));
+        assert!(!first.contains("last-event-id"));
+        let second = received_heads.recv().await.unwrap();
+        assert!(second.contains("last-event-id: 1"));
+        let third = received_heads.recv().await.unwrap();
+        assert!(third.contains("last-event-id: 2"));
+    }
+}
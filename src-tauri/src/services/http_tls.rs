@@ -0,0 +1,289 @@
+use crate::errors::{APIError, ApiResult};
+use crate::utils::expand_tilde;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme, SupportedProtocolVersion,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+use std::sync::Arc;
+use x509_parser::extensions::GeneralName;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPTlsOptions {
+    /// PEM bundles of CAs trusted on top of the built-in roots.
+    #[serde(default)]
+    pub ca_files: Vec<String>,
+    #[serde(default)]
+    pub identity: Option<HTTPClientIdentity>,
+    /// Skips certificate and host name verification, the chain is still reported.
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    #[serde(default)]
+    pub min_version: Option<HTTPTlsVersion>,
+}
+
+/// Client certificate presented for mutual TLS.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum HTTPClientIdentity {
+    /// Certificate chain and private key, `key` may be left out when `cert` holds both.
+    Pem {
+        cert: String,
+        #[serde(default)]
+        key: Option<String>,
+    },
+    Pkcs12 {
+        file: String,
+        #[serde(default)]
+        password: String,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+pub enum HTTPTlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+/// A certificate presented by the server, leaf first.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPCertificate {
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    /// DNS names and IP addresses from the subject alternative name extension.
+    pub sans: Vec<String>,
+    /// Unix timestamps in seconds.
+    pub not_before: i64,
+    pub not_after: i64,
+}
+
+impl HTTPCertificate {
+    fn parse(der: &CertificateDer<'_>) -> Option<HTTPCertificate> {
+        let (_, cert) = x509_parser::parse_x509_certificate(der.as_ref()).ok()?;
+        let sans = match cert.subject_alternative_name() {
+            Ok(Some(san)) => san
+                .value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(dns) => Some(dns.to_string()),
+                    GeneralName::IPAddress(ip) => ip_address(ip),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        };
+        Some(HTTPCertificate {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            serial: cert.raw_serial_as_string(),
+            sans,
+            not_before: cert.validity().not_before.timestamp(),
+            not_after: cert.validity().not_after.timestamp(),
+        })
+    }
+}
+
+fn ip_address(bytes: &[u8]) -> Option<String> {
+    let ip = match bytes.len() {
+        4 => IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?),
+        16 => IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?),
+        _ => return None,
+    };
+    Some(ip.to_string())
+}
+
+/// Called with the chain the server presented, before it is verified.
+pub type PeerChainListener = Arc<dyn Fn(Vec<HTTPCertificate>) + Send + Sync>;
+
+impl HTTPTlsOptions {
+    /// Builds the rustls config handed to reqwest, mirroring what reqwest sets up on its own.
+    pub fn client_config(&self, on_chain: PeerChainListener) -> ApiResult<ClientConfig> {
+        self.client_config_with(Arc::new(rustls::crypto::ring::default_provider()), on_chain)
+    }
+
+    /// Like [`client_config`](Self::client_config), with the cipher suites and key exchange
+    /// of `provider`.
+    pub fn client_config_with(
+        &self,
+        provider: Arc<CryptoProvider>,
+        on_chain: PeerChainListener,
+    ) -> ApiResult<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        for file in &self.ca_files {
+            for cert in read_certs(file)? {
+                roots.add(cert)?;
+            }
+        }
+        let verifier = RecordingVerifier {
+            inner: WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+                .build()
+                .map_err(|e| APIError::Tls(e.to_string()))?,
+            accept_invalid_certs: self.accept_invalid_certs,
+            on_chain,
+        };
+
+        let versions: &[&SupportedProtocolVersion] = match self.min_version {
+            Some(HTTPTlsVersion::Tls13) => &[&rustls::version::TLS13],
+            _ => rustls::ALL_VERSIONS,
+        };
+        let builder = ClientConfig::builder_with_provider(provider)
+            .with_protocol_versions(versions)?
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(verifier));
+        let mut config = match &self.identity {
+            Some(identity) => {
+                let (chain, key) = identity.load()?;
+                builder.with_client_auth_cert(chain, key)?
+            }
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+}
+
+impl HTTPClientIdentity {
+    fn load(&self) -> ApiResult<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+        match self {
+            HTTPClientIdentity::Pem { cert, key } => {
+                let key_file = key.as_deref().filter(|k| !k.is_empty()).unwrap_or(cert);
+                let key = PrivateKeyDer::from_pem_file(expand_tilde(key_file))
+                    .map_err(|e| APIError::Tls(format!("{}: {}", key_file, e)))?;
+                Ok((read_certs(cert)?, key))
+            }
+            HTTPClientIdentity::Pkcs12 { file, password } => {
+                let keystore =
+                    p12_keystore::KeyStore::from_pkcs12(&fs::read(expand_tilde(file))?, password)
+                        .map_err(|e| APIError::Tls(format!("{}: {}", file, e)))?;
+                let (_, key_chain) = keystore
+                    .private_key_chain()
+                    .ok_or_else(|| APIError::Tls(format!("{}: no private key found", file)))?;
+                let chain = key_chain
+                    .chain()
+                    .iter()
+                    .map(|cert| CertificateDer::from(cert.as_der().to_vec()))
+                    .collect();
+                let key = PrivateKeyDer::try_from(key_chain.key().to_vec())
+                    .map_err(|e| APIError::Tls(format!("{}: {}", file, e)))?;
+                Ok((chain, key))
+            }
+        }
+    }
+}
+
+fn read_certs(file: &str) -> ApiResult<Vec<CertificateDer<'static>>> {
+    CertificateDer::pem_file_iter(expand_tilde(file))
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| APIError::Tls(format!("{}: {}", file, e)))
+}
+
+/// Verifies like rustls does by default, reporting the presented chain first.
+struct RecordingVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    accept_invalid_certs: bool,
+    on_chain: PeerChainListener,
+}
+
+impl fmt::Debug for RecordingVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordingVerifier")
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .finish()
+    }
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let chain = std::iter::once(end_entity)
+            .chain(intermediates)
+            .filter_map(HTTPCertificate::parse)
+            .collect();
+        (self.on_chain)(chain);
+        if self.accept_invalid_certs {
+            return Ok(ServerCertVerified::assertion());
+        }
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        if self.accept_invalid_certs {
+            return Ok(HandshakeSignatureValid::assertion());
+        }
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        if self.accept_invalid_certs {
+            return Ok(HandshakeSignatureValid::assertion());
+        }
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_config() {
+        let options = HTTPTlsOptions {
+            min_version: Some(HTTPTlsVersion::Tls13),
+            ..Default::default()
+        };
+        let config = options.client_config(Arc::new(|_| {})).expect("config");
+        assert_eq!(
+            config.alpn_protocols,
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+        );
+
+        let options = HTTPTlsOptions {
+            ca_files: vec!["/nonexistent/ca.pem".to_string()],
+            ..Default::default()
+        };
+        let res = options.client_config(Arc::new(|_| {}));
+        assert!(matches!(res, Err(APIError::Tls(_))));
+    }
+
+    #[test]
+    fn test_ip_address_san() {
+        assert_eq!(ip_address(&[10, 0, 0, 1]), Some("10.0.0.1".to_string()));
+        assert_eq!(ip_address(&[0; 16]), Some("::".to_string()));
+        assert_eq!(ip_address(&[1, 2]), None);
+    }
+}
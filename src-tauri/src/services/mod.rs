@@ -3,20 +3,51 @@
 pub mod aws;
 pub mod aws_s3;
 pub mod dock_progress;
+pub mod grpc;
 pub mod http;
+pub mod http_assert;
+pub mod http_auth;
+pub mod http_body;
+pub mod http_collection;
+pub mod http_cookies;
+pub mod http_env;
+pub mod http_export;
+pub mod http_graphql;
+pub mod http_history;
+pub mod http_import;
+pub mod http_load;
+pub mod http_proxy;
+pub mod http_runner;
+pub mod http_sse;
+pub mod http_tls;
 pub mod kube;
 pub mod kube_config;
 pub mod kube_log;
 pub mod request;
 pub mod shell;
+pub mod websocket;
 
 pub mod commands {
     use crate::errors::{APIError, ApiResult};
     pub use crate::services::aws::commands::*;
     pub use crate::services::aws_s3::commands::*;
     pub use crate::services::dock_progress::commands::*;
+    pub use crate::services::grpc::commands::*;
     pub use crate::services::http::commands::*;
+    pub use crate::services::http_body::commands::*;
+    pub use crate::services::http_collection::commands::*;
+    pub use crate::services::http_cookies::commands::*;
+    pub use crate::services::http_env::commands::*;
+    pub use crate::services::http_export::commands::*;
+    pub use crate::services::http_graphql::commands::*;
+    pub use crate::services::http_history::commands::*;
+    pub use crate::services::http_import::commands::*;
+    pub use crate::services::http_load::commands::*;
+    pub use crate::services::http_proxy::commands::*;
+    pub use crate::services::http_runner::commands::*;
+    pub use crate::services::http_sse::commands::*;
     pub use crate::services::kube_config::commands::*;
+    pub use crate::services::websocket::commands::*;
     use std::env;
     use tauri::ipc::Invoke;
     use tauri::{command, generate_handler};
@@ -36,6 +67,58 @@ pub mod commands {
     pub fn setup_handler() -> fn(Invoke) -> bool {
         generate_handler![
             http_send_request,
+            http_cancel_request,
+            http_clear_connections,
+            http_save_response_body,
+            http_discard_response_body,
+            http_cookie_jars,
+            http_cookies,
+            http_set_cookie,
+            http_delete_cookie,
+            http_clear_cookies,
+            http_environments,
+            http_environment,
+            http_save_environment,
+            http_delete_environment,
+            http_collections,
+            http_collection,
+            http_save_collection,
+            http_delete_collection,
+            http_collection_request,
+            http_save_collection_item,
+            http_delete_collection_item,
+            http_move_collection_item,
+            http_duplicate_collection_item,
+            http_search_collections,
+            http_history,
+            http_history_entry,
+            http_replay_history,
+            http_delete_history_entry,
+            http_clear_history,
+            http_history_settings,
+            http_set_history_settings,
+            http_export,
+            http_graphql_schema,
+            http_graphql_cached_schema,
+            http_import,
+            http_load_test,
+            http_stop_load_test,
+            http_proxy_settings,
+            http_set_proxy_settings,
+            http_run_collection,
+            http_run_report,
+            http_sse_subscribe,
+            http_sse_stop,
+            ws_connect,
+            ws_send,
+            ws_close,
+            ws_remove_session,
+            ws_sessions,
+            ws_frames,
+            ws_export_frames,
+            grpc_services,
+            grpc_call,
+            grpc_cancel,
             load_kube_config,
             aws_profiles,
             aws_s3_buckets,
@@ -36,9 +36,14 @@ pub struct Res {
 
 pub async fn send_request(req: Req) -> Result<Res> {
     let orig: Req = req.clone();
+    let redirect = match (req.allow_redirects, req.max_redirects) {
+        (Some(false), _) => Policy::none(),
+        (_, Some(max)) => Policy::limited(max as usize),
+        _ => Policy::limited(10),
+    };
     let client = ClientBuilder::new()
         .timeout(std::time::Duration::from_secs(10))
-        .redirect(Policy::limited(10))
+        .redirect(redirect)
         .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/58.0.3029.110 Safari/537.3")
         .build()?;
 
@@ -53,10 +58,13 @@ pub async fn send_request(req: Req) -> Result<Res> {
             url = req.url.to_string();
         }
     }
-    let req_builder = client.request(
+    let mut req_builder = client.request(
         (Method::from_str(&req.method.unwrap_or("GET".to_string()))).unwrap_or(Method::GET),
         url,
     );
+    if let Some((username, password)) = req.auth {
+        req_builder = req_builder.basic_auth(username, Some(password));
+    }
     // client.post(req.url)
     //     .json(&params) // Serialize params to JSON
     let response = req_builder.send().await?;
@@ -0,0 +1,655 @@
+use crate::errors::{APIError, ApiResult};
+use crate::services::http::{self, HTTPRequestKVParam};
+use crate::services::http_env::{HTTPEnvironment, Variables};
+use crate::services::http_tls::{HTTPCertificate, HTTPTlsOptions};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::SecondsFormat;
+use futures_util::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::http::{HeaderName, HeaderValue};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::Connector;
+use url::Url;
+
+/// Sessions opened with [`connect`], kept once closed so their frame log can be exported.
+static SESSIONS: Lazy<Mutex<HashMap<String, Arc<Session>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Ids of the sessions still in their opening handshake.
+static CONNECTING: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSocketRequest {
+    /// Id of the session, generated when left out.
+    #[serde(default)]
+    pub id: Option<String>,
+    /// A `ws://` or `wss://` url.
+    pub url: String,
+    #[serde(default)]
+    pub headers: Vec<HTTPRequestKVParam>,
+    #[serde(default)]
+    pub query: Vec<HTTPRequestKVParam>,
+    /// Offered in `Sec-WebSocket-Protocol`, the server picks one of them.
+    #[serde(default)]
+    pub subprotocols: Vec<String>,
+    #[serde(default)]
+    pub tls: HTTPTlsOptions,
+    /// Name of the [`HTTPEnvironment`] `{{name}}` placeholders are resolved from.
+    #[serde(default)]
+    pub environment: Option<String>,
+}
+
+impl WebSocketRequest {
+    /// Replaces the `{{name}}` placeholders of the url, headers, query and subprotocols.
+    fn resolve(&mut self) -> ApiResult<()> {
+        let variables = match self.environment.take() {
+            Some(name) => Variables::from(&HTTPEnvironment::load(&name)?),
+            None => Variables::default(),
+        };
+        let mut missing = BTreeSet::new();
+        let mut values: Vec<&mut String> = vec![&mut self.url];
+        for param in self.headers.iter_mut().chain(self.query.iter_mut()) {
+            values.extend([&mut param.key, &mut param.value]);
+        }
+        values.extend(self.subprotocols.iter_mut());
+        for value in values {
+            *value = variables.render(value, &mut missing);
+        }
+        if !missing.is_empty() {
+            return Err(APIError::UnresolvedVariables(missing.into_iter().collect()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSocketSession {
+    pub id: String,
+    pub url: String,
+    /// Subprotocol the server accepted.
+    pub protocol: Option<String>,
+    /// Headers of the handshake response.
+    pub headers: HashMap<String, Vec<String>>,
+    /// Certificates presented by the server, leaf first.
+    pub certificates: Vec<HTTPCertificate>,
+    /// Unix timestamps in ms.
+    pub opened: i64,
+    pub closed: Option<i64>,
+    /// Why the connection was lost, None when it was closed cleanly.
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum WebSocketDirection {
+    Sent,
+    Received,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum WebSocketFrameKind {
+    Text,
+    Binary,
+    Ping,
+    Pong,
+    Close,
+}
+
+/// An entry of the frame log of a session.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSocketFrame {
+    pub session: String,
+    pub seq: u64,
+    /// Unix timestamp in ms.
+    pub time: i64,
+    pub direction: WebSocketDirection,
+    pub kind: WebSocketFrameKind,
+    /// Text of text frames and close reason, base64 of the other payloads.
+    pub data: String,
+    /// Payload size in bytes.
+    pub length: usize,
+    /// Status code of close frames.
+    #[serde(default)]
+    pub code: Option<u16>,
+}
+
+/// A frame to send, binary payloads are base64.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum WebSocketMessage {
+    Text {
+        data: String,
+    },
+    Binary {
+        data: String,
+    },
+    Ping {
+        #[serde(default)]
+        data: String,
+    },
+    Pong {
+        #[serde(default)]
+        data: String,
+    },
+}
+
+impl WebSocketMessage {
+    fn into_message(self) -> ApiResult<Message> {
+        let decode = |data: String| general_purpose::STANDARD.decode(data);
+        Ok(match self {
+            WebSocketMessage::Text { data } => Message::text(data),
+            WebSocketMessage::Binary { data } => Message::binary(decode(data)?),
+            WebSocketMessage::Ping { data } => Message::Ping(decode(data)?.into()),
+            WebSocketMessage::Pong { data } => Message::Pong(decode(data)?.into()),
+        })
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum WebSocketLogFormat {
+    Json,
+    /// A line per frame.
+    Text,
+}
+
+#[derive(Debug, Clone)]
+pub enum WebSocketEvent {
+    Frame(WebSocketFrame),
+    Closed(WebSocketSession),
+}
+
+pub type WebSocketListener = Box<dyn Fn(WebSocketEvent) + Send + Sync>;
+
+struct Session {
+    id: String,
+    info: Mutex<WebSocketSession>,
+    frames: Mutex<Vec<WebSocketFrame>>,
+    /// Frames the session task writes to the socket.
+    outgoing: mpsc::UnboundedSender<Message>,
+}
+
+impl Session {
+    /// Adds `message` to the frame log, raw frames are never logged.
+    fn log(&self, direction: WebSocketDirection, message: &Message) -> Option<WebSocketFrame> {
+        let encode = |data: &[u8]| general_purpose::STANDARD.encode(data);
+        let (kind, data, length, code) = match message {
+            Message::Text(text) => (WebSocketFrameKind::Text, text.to_string(), text.len(), None),
+            Message::Binary(data) => (WebSocketFrameKind::Binary, encode(data), data.len(), None),
+            Message::Ping(data) => (WebSocketFrameKind::Ping, encode(data), data.len(), None),
+            Message::Pong(data) => (WebSocketFrameKind::Pong, encode(data), data.len(), None),
+            Message::Close(frame) => (
+                WebSocketFrameKind::Close,
+                frame
+                    .as_ref()
+                    .map_or(String::new(), |f| f.reason.to_string()),
+                frame.as_ref().map_or(0, |f| f.reason.len() + 2),
+                frame.as_ref().map(|f| u16::from(f.code)),
+            ),
+            Message::Frame(_) => return None,
+        };
+        let mut frames = self.frames.lock().ok()?;
+        let frame = WebSocketFrame {
+            session: self.id.clone(),
+            seq: frames.len() as u64,
+            time: chrono::Utc::now().timestamp_millis(),
+            direction,
+            kind,
+            data,
+            length,
+            code,
+        };
+        frames.push(frame.clone());
+        Some(frame)
+    }
+
+    fn info(&self) -> ApiResult<WebSocketSession> {
+        self.info
+            .lock()
+            .map(|info| info.clone())
+            .map_err(|e| APIError::General(e.to_string()))
+    }
+
+    fn send(&self, message: Message) -> ApiResult<()> {
+        if self.info()?.closed.is_some() {
+            return Err(APIError::Http(format!(
+                "WebSocket session {} is closed",
+                self.id
+            )));
+        }
+        self.outgoing
+            .send(message)
+            .map_err(|_| APIError::Http(format!("WebSocket session {} is closed", self.id)))
+    }
+}
+
+/// Holds a session id in [`CONNECTING`] until dropped, so no other session takes it
+/// while the handshake runs.
+struct Reservation(String);
+
+impl Reservation {
+    fn new(id: &str) -> ApiResult<Reservation> {
+        let sessions = SESSIONS
+            .lock()
+            .map_err(|e| APIError::General(e.to_string()))?;
+        let mut connecting = CONNECTING
+            .lock()
+            .map_err(|e| APIError::General(e.to_string()))?;
+        if sessions.contains_key(id) || !connecting.insert(id.to_string()) {
+            return Err(APIError::Http(format!(
+                "WebSocket session {} already exists",
+                id
+            )));
+        }
+        Ok(Reservation(id.to_string()))
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Ok(mut connecting) = CONNECTING.lock() {
+            connecting.remove(&self.0);
+        }
+    }
+}
+
+fn session(id: &str) -> ApiResult<Arc<Session>> {
+    SESSIONS
+        .lock()
+        .map_err(|e| APIError::General(e.to_string()))?
+        .get(id)
+        .cloned()
+        .ok_or_else(|| APIError::General(format!("WebSocket session {} not found", id)))
+}
+
+/// Opens a session and keeps reading and writing its frames on a task of its own until
+/// either side closes it. Every frame is logged and passed to `on_event`.
+pub async fn connect(
+    mut request: WebSocketRequest,
+    on_event: Option<WebSocketListener>,
+) -> ApiResult<WebSocketSession> {
+    request.resolve()?;
+    let id = request
+        .id
+        .clone()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let _reservation = Reservation::new(&id)?;
+
+    let mut url = Url::parse(&request.url)?;
+    for q in request.query.iter().filter(|q| q.enabled) {
+        url.query_pairs_mut().append_pair(&q.key, &q.value);
+    }
+    let mut ws_request = url.as_str().into_client_request()?;
+    for h in request.headers.iter().filter(|h| h.enabled) {
+        ws_request.headers_mut().insert(
+            h.key.parse::<HeaderName>()?,
+            HeaderValue::from_str(&h.value)?,
+        );
+    }
+    if !request.subprotocols.is_empty() {
+        ws_request.headers_mut().insert(
+            "Sec-WebSocket-Protocol",
+            HeaderValue::from_str(&request.subprotocols.join(", "))?,
+        );
+    }
+
+    let chain = Arc::new(Mutex::new(Vec::new()));
+    let peer_chain = chain.clone();
+    let mut tls = request.tls.client_config(Arc::new(move |certificates| {
+        if let Ok(mut chain) = peer_chain.lock() {
+            *chain = certificates;
+        }
+    }))?;
+    // the upgrade is an HTTP/1.1 request
+    tls.alpn_protocols = vec![b"http/1.1".to_vec()];
+    let connector = Connector::Rustls(Arc::new(tls));
+    let (stream, response) =
+        tokio_tungstenite::connect_async_tls_with_config(ws_request, None, false, Some(connector))
+            .await?;
+
+    let info = WebSocketSession {
+        id: id.clone(),
+        url: url.to_string(),
+        protocol: response
+            .headers()
+            .get("Sec-WebSocket-Protocol")
+            .and_then(|v| v.to_str().ok())
+            .map(String::from),
+        headers: http::response_headers(response.headers())?,
+        certificates: chain.lock().map(|c| c.clone()).unwrap_or_default(),
+        opened: chrono::Utc::now().timestamp_millis(),
+        closed: None,
+        error: None,
+    };
+    let (outgoing, mut to_send) = mpsc::unbounded_channel();
+    let session = Arc::new(Session {
+        id: id.clone(),
+        info: Mutex::new(info.clone()),
+        frames: Mutex::new(Vec::new()),
+        outgoing,
+    });
+    SESSIONS
+        .lock()
+        .map_err(|e| APIError::General(e.to_string()))?
+        .insert(id, session.clone());
+
+    let emit = move |event: WebSocketEvent| {
+        if let Some(listener) = &on_event {
+            listener(event);
+        }
+    };
+    let (mut sink, mut stream) = stream.split();
+    tokio::spawn(async move {
+        let error = loop {
+            tokio::select! {
+                message = to_send.recv() => match message {
+                    Some(message) => {
+                        let frame = session.log(WebSocketDirection::Sent, &message);
+                        if let Err(e) = sink.send(message).await {
+                            break Some(e.to_string());
+                        }
+                        if let Some(frame) = frame {
+                            emit(WebSocketEvent::Frame(frame));
+                        }
+                    }
+                    None => break None,
+                },
+                message = stream.next() => match message {
+                    Some(Ok(message)) => {
+                        if let Some(frame) = session.log(WebSocketDirection::Received, &message) {
+                            emit(WebSocketEvent::Frame(frame));
+                        }
+                    }
+                    Some(Err(e)) => break Some(e.to_string()),
+                    None => break None,
+                },
+            }
+        };
+        let info = match session.info.lock() {
+            Ok(mut info) => {
+                info.closed = Some(chrono::Utc::now().timestamp_millis());
+                info.error = error;
+                info.clone()
+            }
+            Err(e) => {
+                log::error!("Failed to close WebSocket session {}: {}", session.id, e);
+                return;
+            }
+        };
+        emit(WebSocketEvent::Closed(info));
+    });
+    Ok(info)
+}
+
+/// Sends a frame on session `id`.
+pub fn send(id: &str, message: WebSocketMessage) -> ApiResult<()> {
+    session(id)?.send(message.into_message()?)
+}
+
+/// Starts the closing handshake of session `id`, with 1000 (normal closure) by default.
+pub fn close(id: &str, code: Option<u16>, reason: Option<String>) -> ApiResult<()> {
+    session(id)?.send(Message::Close(Some(CloseFrame {
+        code: CloseCode::from(code.unwrap_or(1000)),
+        reason: reason.unwrap_or_default().into(),
+    })))
+}
+
+/// Closes session `id` if still open and forgets it, false when there is no such session.
+pub fn remove(id: &str) -> ApiResult<bool> {
+    let removed = SESSIONS
+        .lock()
+        .map_err(|e| APIError::General(e.to_string()))?
+        .remove(id);
+    match removed {
+        Some(session) => {
+            if session.info()?.closed.is_none() {
+                let _ = session.outgoing.send(Message::Close(None));
+            }
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+pub fn sessions() -> ApiResult<Vec<WebSocketSession>> {
+    let sessions = SESSIONS
+        .lock()
+        .map_err(|e| APIError::General(e.to_string()))?;
+    let mut sessions = sessions
+        .values()
+        .map(|session| session.info())
+        .collect::<ApiResult<Vec<_>>>()?;
+    sessions.sort_by_key(|session| session.opened);
+    Ok(sessions)
+}
+
+pub fn frames(id: &str) -> ApiResult<Vec<WebSocketFrame>> {
+    session(id)?
+        .frames
+        .lock()
+        .map(|frames| frames.clone())
+        .map_err(|e| APIError::General(e.to_string()))
+}
+
+/// The frame log of session `id` in `format`.
+pub fn export(id: &str, format: WebSocketLogFormat) -> ApiResult<String> {
+    let frames = frames(id)?;
+    match format {
+        WebSocketLogFormat::Json => Ok(serde_json::to_string_pretty(&frames)?),
+        WebSocketLogFormat::Text => Ok(frames.iter().map(log_line).collect()),
+    }
+}
+
+/// `2025-01-02T03:04:05.678Z >> text 5 hello`, `<<` for received frames.
+fn log_line(frame: &WebSocketFrame) -> String {
+    let time = chrono::DateTime::from_timestamp_millis(frame.time)
+        .map(|t| t.to_rfc3339_opts(SecondsFormat::Millis, true))
+        .unwrap_or_default();
+    let arrow = match frame.direction {
+        WebSocketDirection::Sent => ">>",
+        WebSocketDirection::Received => "<<",
+    };
+    let kind = serde_json::to_value(frame.kind)
+        .ok()
+        .and_then(|kind| kind.as_str().map(String::from))
+        .unwrap_or_default();
+    let mut line = format!("{} {} {} {}", time, arrow, kind, frame.length);
+    if let Some(code) = frame.code {
+        line.push_str(&format!(" {}", code));
+    }
+    if !frame.data.is_empty() {
+        line.push(' ');
+        line.push_str(&frame.data.replace('\n', "\\n"));
+    }
+    line.push('\n');
+    line
+}
+
+pub mod commands {
+    use super::*;
+    use tauri::{command, AppHandle, Emitter};
+
+    /// Opens a session whose frames are emitted as `ws_frame_{id}` and whose end is
+    /// emitted as `ws_closed_{id}`.
+    #[command(async)]
+    pub async fn ws_connect(app: AppHandle, req: WebSocketRequest) -> ApiResult<WebSocketSession> {
+        log::info!("Opening WebSocket {}", req.url);
+        let on_event: WebSocketListener = Box::new(move |event| {
+            let emitted = match &event {
+                WebSocketEvent::Frame(frame) => {
+                    app.emit(format!("ws_frame_{}", frame.session).as_str(), frame)
+                }
+                WebSocketEvent::Closed(session) => {
+                    app.emit(format!("ws_closed_{}", session.id).as_str(), session)
+                }
+            };
+            if let Err(e) = emitted {
+                log::error!("Failed to emit WebSocket event: {}", e);
+            }
+        });
+        connect(req, Some(on_event)).await
+    }
+
+    #[command(async)]
+    pub async fn ws_send(id: String, message: WebSocketMessage) -> ApiResult<()> {
+        send(&id, message)
+    }
+
+    #[command(async)]
+    pub async fn ws_close(id: String, code: Option<u16>, reason: Option<String>) -> ApiResult<()> {
+        log::info!("Closing WebSocket session {}", id);
+        close(&id, code, reason)
+    }
+
+    #[command(async)]
+    pub async fn ws_remove_session(id: String) -> ApiResult<bool> {
+        remove(&id)
+    }
+
+    #[command(async)]
+    pub async fn ws_sessions() -> ApiResult<Vec<WebSocketSession>> {
+        sessions()
+    }
+
+    #[command(async)]
+    pub async fn ws_frames(id: String) -> ApiResult<Vec<WebSocketFrame>> {
+        frames(&id)
+    }
+
+    #[command(async)]
+    pub async fn ws_export_frames(id: String, format: WebSocketLogFormat) -> ApiResult<String> {
+        export(&id, format)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Echoes text and binary frames back.
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut ws = match tokio_tungstenite::accept_async(socket).await {
+                        Ok(ws) => ws,
+                        Err(_) => return,
+                    };
+                    while let Some(Ok(message)) = ws.next().await {
+                        if (message.is_text() || message.is_binary())
+                            && ws.send(message).await.is_err()
+                        {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        format!("ws://{}/echo", addr)
+    }
+
+    #[tokio::test]
+    async fn test_session_logs_frames() {
+        let url = serve().await;
+        let (events, mut received) = mpsc::unbounded_channel();
+        let on_event: WebSocketListener = Box::new(move |event| {
+            let _ = events.send(event);
+        });
+        let request = WebSocketRequest {
+            id: Some("echo".to_string()),
+            url,
+            ..Default::default()
+        };
+        let session = connect(request, Some(on_event)).await.expect("connect");
+        assert_eq!(session.id, "echo");
+        assert!(session.closed.is_none());
+
+        let text = WebSocketMessage::Text {
+            data: "hello".to_string(),
+        };
+        send("echo", text).unwrap();
+        let binary = WebSocketMessage::Binary {
+            data: general_purpose::STANDARD.encode([1u8, 2, 3]),
+        };
+        send("echo", binary).unwrap();
+        close("echo", Some(4000), Some("done".to_string())).unwrap();
+
+        let closed = loop {
+            match received.recv().await.expect("event") {
+                WebSocketEvent::Closed(session) => break session,
+                WebSocketEvent::Frame(_) => {}
+            }
+        };
+        assert!(closed.closed.is_some());
+        assert_eq!(closed.error, None);
+        let late = WebSocketMessage::Text {
+            data: "late".to_string(),
+        };
+        assert!(send("echo", late).is_err());
+
+        let log = frames("echo").unwrap();
+        assert!(log.iter().enumerate().all(|(i, f)| f.seq == i as u64));
+        // echoes may be read before the next frame is written
+        for direction in [WebSocketDirection::Sent, WebSocketDirection::Received] {
+            let entries: Vec<_> = log
+                .iter()
+                .filter(|f| f.direction == direction)
+                .map(|f| (f.kind, f.data.as_str(), f.code))
+                .collect();
+            assert_eq!(
+                entries,
+                [
+                    (WebSocketFrameKind::Text, "hello", None),
+                    (WebSocketFrameKind::Binary, "AQID", None),
+                    (WebSocketFrameKind::Close, "done", Some(4000)),
+                ]
+            );
+        }
+        let text = export("echo", WebSocketLogFormat::Text).unwrap();
+        assert_eq!(text.lines().count(), 6);
+        assert!(text.lines().next().unwrap().ends_with(">> text 5 hello"));
+        assert!(remove("echo").unwrap());
+        assert!(frames("echo").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_session_id_is_reserved_while_connecting() {
+        let url = serve().await;
+        let request = WebSocketRequest {
+            id: Some("twin".to_string()),
+            url,
+            ..Default::default()
+        };
+        let (first, second) = tokio::join!(
+            connect(request.clone(), None),
+            connect(request.clone(), None)
+        );
+        assert!(first.is_ok() != second.is_ok());
+        assert!(remove("twin").unwrap());
+
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let refused = WebSocketRequest {
+            id: Some("twin".to_string()),
+            url: format!("ws://{}/", closed.local_addr().unwrap()),
+            ..Default::default()
+        };
+        drop(closed);
+        assert!(connect(refused, None).await.is_err());
+        assert!(connect(request, None).await.is_ok());
+        assert!(remove("twin").unwrap());
+    }
+}
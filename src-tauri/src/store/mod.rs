@@ -1 +1,55 @@
+use crate::errors::{APIError, ApiResult};
+use once_cell::sync::OnceCell;
+use std::fs;
+use std::path::PathBuf;
 
+static DATA_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Sets the directory everything persisted by the app lives under, called once during setup.
+pub fn init(data_dir: PathBuf) {
+    log::info!("Data store initialized at: {:?}", data_dir);
+    if DATA_DIR.set(data_dir).is_err() {
+        log::warn!("Data store already initialized");
+    }
+}
+
+/// Points the store at a temporary directory shared by every test of the process. It is
+/// kept when the tests end, as tests running in parallel may still be using it.
+#[cfg(test)]
+pub fn init_for_tests() {
+    DATA_DIR.get_or_init(|| tempfile::tempdir().expect("temp dir").keep());
+}
+
+/// Returns `<data dir>/<name>`, creating it when missing.
+pub fn dir(name: &str) -> ApiResult<PathBuf> {
+    let root = DATA_DIR
+        .get()
+        .ok_or_else(|| APIError::General("Data store is not initialized".to_string()))?;
+    let dir = root.join(name);
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Turns a user supplied name into a file name that stays inside its directory.
+pub fn file_name(name: &str, extension: &str) -> ApiResult<String> {
+    let name = name.trim();
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\', ':']) {
+        return Err(APIError::General(format!("Invalid name: {:?}", name)));
+    }
+    Ok(format!("{}.{}", name, extension))
+}
+
+/// Names of the `<name>.<extension>` files saved in directory `dir`, sorted.
+pub fn names(dir: &str, extension: &str) -> ApiResult<Vec<String>> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(self::dir(dir)?)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == extension) {
+            if let Some(stem) = path.file_stem() {
+                names.push(stem.to_string_lossy().to_string());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}